@@ -0,0 +1,507 @@
+// config.rs
+use crate::helpers::findings::Severity;
+use crate::helpers::redact::Secret;
+use crate::services::reviewer::PROMPT_OVERHEAD_TOKENS;
+use crate::services::store::DEFAULT_RETENTION_DAYS;
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Config file read when `--config` isn't given, if it exists.
+pub const DEFAULT_CONFIG_PATH: &str = "mechanic.toml";
+/// Upper bound on `review.max_attempts`; the retry backoff doubles with every attempt.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+/// Everything the server needs to run, loaded once at startup.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  pub server: ServerConfig,
+  pub github: GitHubConfig,
+  pub llm: LlmConfig,
+  pub review: ReviewConfig,
+  pub checks: ChecksConfig,
+  pub statuses: StatusesConfig,
+  pub autofix: AutofixConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+  pub bind: SocketAddr,
+  /// Append-only log of every review job.
+  pub job_log: PathBuf,
+  /// Keep each job's intermediate pipeline output here for debugging.
+  pub artifact_dir: Option<PathBuf>,
+  /// Finished jobs are dropped from the job log this many days after they last changed.
+  pub job_retention_days: u64,
+}
+
+impl Default for ServerConfig {
+  fn default() -> Self {
+    ServerConfig {
+      bind: SocketAddr::from(([127, 0, 0, 1], 3000)),
+      job_log: PathBuf::from("data/jobs.log"),
+      artifact_dir: None,
+      job_retention_days: DEFAULT_RETENTION_DAYS,
+    }
+  }
+}
+
+impl ServerConfig {
+  pub fn job_retention(&self) -> Duration {
+    Duration::from_secs(self.job_retention_days * 24 * 60 * 60)
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubConfig {
+  /// REST API root; change for GitHub Enterprise Server or a local fake.
+  pub api_url: String,
+  pub app_id: u64,
+  pub private_key_path: PathBuf,
+  pub webhook_secret: Secret,
+}
+
+impl Default for GitHubConfig {
+  fn default() -> Self {
+    GitHubConfig {
+      api_url: "https://api.github.com".to_string(),
+      app_id: 0,
+      private_key_path: PathBuf::new(),
+      webhook_secret: Secret::default(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+  /// Which backend serves the model.
+  pub provider: ProviderKind,
+  /// API base URL, e.g. `http://localhost:8080/v1` for llama.cpp; empty uses the provider's.
+  pub base_url: String,
+  /// Not needed for local servers.
+  pub api_key: Secret,
+  pub model: String,
+  /// Token budget for each reply.
+  pub max_tokens: u32,
+  /// Model context window in tokens; 0 uses the provider's default.
+  pub context_window: usize,
+  /// How the reply is constrained to the review report schema.
+  pub response_format: ResponseFormat,
+  /// Times the model is asked to fix a reply that isn't a valid report.
+  pub repair_attempts: u32,
+  /// Recorded completions: replayed by the `mock` provider, written by every other one.
+  pub recordings: Option<PathBuf>,
+}
+
+impl Default for LlmConfig {
+  fn default() -> Self {
+    LlmConfig {
+      provider: ProviderKind::Groq,
+      base_url: String::new(),
+      api_key: Secret::default(),
+      model: "deepseek-r1-distill-llama-70b".to_string(),
+      max_tokens: 10000,
+      context_window: 0,
+      response_format: ResponseFormat::JsonObject,
+      repair_attempts: 1,
+      recordings: None,
+    }
+  }
+}
+
+impl LlmConfig {
+  pub fn base_url(&self) -> &str {
+    if self.base_url.is_empty() { self.provider.default_base_url() } else { &self.base_url }
+  }
+
+  pub fn context_window(&self) -> usize {
+    if self.context_window == 0 {
+      self.provider.default_context_window()
+    } else {
+      self.context_window
+    }
+  }
+
+  /// Hosted APIs need a key; local servers normally don't.
+  fn needs_api_key(&self) -> bool {
+    match self.provider {
+      ProviderKind::Groq => true,
+      ProviderKind::OpenAi => self.base_url.is_empty(),
+      ProviderKind::Ollama | ProviderKind::Mock => false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+  #[default]
+  Groq,
+  /// OpenAI or any server with the same chat completions API (llama.cpp, vLLM, LM Studio).
+  #[serde(rename = "openai")]
+  OpenAi,
+  Ollama,
+  /// Replays recorded completions from `recordings`; for tests and offline runs.
+  Mock,
+}
+
+impl ProviderKind {
+  pub fn default_base_url(self) -> &'static str {
+    match self {
+      ProviderKind::Groq => "https://api.groq.com/openai/v1",
+      ProviderKind::OpenAi => "https://api.openai.com/v1",
+      ProviderKind::Ollama => "http://localhost:11434",
+      ProviderKind::Mock => "http://localhost",
+    }
+  }
+
+  pub fn default_context_window(self) -> usize {
+    match self {
+      ProviderKind::Groq => 131_072,
+      ProviderKind::OpenAi => 128_000,
+      ProviderKind::Ollama => 32_768,
+      ProviderKind::Mock => 131_072,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+  /// JSON mode; the schema is spelled out in the prompt. Works with most providers.
+  #[default]
+  JsonObject,
+  /// Structured outputs: the provider enforces the schema itself.
+  JsonSchema,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewConfig {
+  /// Reviews processed concurrently.
+  pub workers: usize,
+  /// Jobs that can wait for a worker before new deliveries are turned away.
+  pub queue_capacity: usize,
+  /// How many times a job runs before it is dropped.
+  pub max_attempts: u32,
+  /// Delay before the first retry; doubled after every failed attempt.
+  pub retry_backoff_secs: u64,
+  /// How long delivery ids and reviewed commits are remembered for deduplication.
+  pub dedup_retention_hours: u64,
+  /// Unchanged lines kept around each change in the diff sent to the model.
+  pub context_lines: usize,
+  /// Upper bound on the diff sent to the model, so it fits in the context window.
+  pub max_diff_chars: usize,
+  /// Findings at or above this severity make the review request changes instead of commenting.
+  pub request_changes_at: Severity,
+  /// Inline comments per review; further findings are listed in the review body.
+  pub max_comments: usize,
+}
+
+impl Default for ReviewConfig {
+  fn default() -> Self {
+    ReviewConfig {
+      workers: 4,
+      queue_capacity: 64,
+      max_attempts: 3,
+      retry_backoff_secs: 2,
+      dedup_retention_hours: 72,
+      context_lines: 3,
+      max_diff_chars: 60_000,
+      request_changes_at: Severity::Error,
+      max_comments: 30,
+    }
+  }
+}
+
+impl ReviewConfig {
+  pub fn retry_backoff(&self) -> Duration {
+    Duration::from_secs(self.retry_backoff_secs)
+  }
+
+  /// How long to wait after failed attempt `attempt` (from 1): the backoff, doubled each time.
+  pub fn retry_delay(&self, attempt: u32) -> Duration {
+    self.retry_backoff().saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+  }
+
+  pub fn dedup_retention(&self) -> Duration {
+    Duration::from_secs(self.dedup_retention_hours * 60 * 60)
+  }
+}
+
+/// The check run published for every reviewed commit.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChecksConfig {
+  pub enabled: bool,
+  /// Shown in the Checks tab and matched by branch protection rules.
+  pub name: String,
+  /// Findings at or above this severity fail the check.
+  pub fail_at: Severity,
+  /// Findings at or above this severity, but below `fail_at`, make the check neutral.
+  pub neutral_at: Severity,
+}
+
+impl Default for ChecksConfig {
+  fn default() -> Self {
+    ChecksConfig {
+      enabled: true,
+      name: "Mechanic".to_string(),
+      fail_at: Severity::Error,
+      neutral_at: Severity::Warning,
+    }
+  }
+}
+
+/// A lighter alternative to the check run: a commit status, pending while the job waits.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusesConfig {
+  pub enabled: bool,
+  /// Label of the status, and what branch protection rules match on.
+  pub context: String,
+  /// Findings at or above this severity make the status a failure.
+  pub fail_at: Severity,
+}
+
+impl Default for StatusesConfig {
+  fn default() -> Self {
+    StatusesConfig {
+      enabled: false,
+      context: "mechanic/review".to_string(),
+      fail_at: Severity::Error,
+    }
+  }
+}
+
+/// Pull requests with the model's suggested fixes, opened against the reviewed branch.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutofixConfig {
+  pub enabled: bool,
+  /// Only fixes the model is at least this sure of, from 0 to 1, are applied.
+  pub min_confidence: f32,
+}
+
+impl Default for AutofixConfig {
+  fn default() -> Self {
+    AutofixConfig { enabled: false, min_confidence: 0.8 }
+  }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+  Io { path: PathBuf, source: std::io::Error },
+  Parse { path: PathBuf, source: toml::de::Error },
+  Env { var: &'static str, message: String },
+  Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConfigError::Io { path, source } => {
+        write!(f, "could not read config file {}: {}", path.display(), source)
+      }
+      ConfigError::Parse { path, source } => {
+        write!(f, "invalid config file {}: {}", path.display(), source)
+      }
+      ConfigError::Env { var, message } => write!(f, "invalid value in ${}: {}", var, message),
+      ConfigError::Invalid(problems) => {
+        writeln!(f, "invalid configuration:")?;
+        for problem in problems {
+          writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+  /// Load the config file (`path`, or `mechanic.toml` if present), apply environment
+  /// overrides and validate the result.
+  pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let default_path = Path::new(DEFAULT_CONFIG_PATH);
+    let path = match path {
+      Some(path) => Some(path),
+      None => default_path.exists().then_some(default_path),
+    };
+
+    let mut config = match path {
+      Some(path) => {
+        let text = std::fs::read_to_string(path)
+          .map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?;
+        Config::from_toml(&text)
+          .map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })?
+      }
+      None => Config::default(),
+    };
+
+    config.apply_overrides(|var| std::env::var(var).ok())?;
+    config.validate()?;
+    Ok(config)
+  }
+
+  pub fn from_toml(text: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(text)
+  }
+
+  /// Override file settings from environment variables, looked up through `lookup`.
+  pub fn apply_overrides(
+    &mut self,
+    lookup: impl Fn(&str) -> Option<String>,
+  ) -> Result<(), ConfigError> {
+    let lookup = |var: &str| lookup(var).filter(|value| !value.is_empty());
+
+    if let Some(value) = lookup("MECHANIC_BIND") {
+      self.server.bind = parse_env("MECHANIC_BIND", &value)?;
+    }
+    if let Some(value) = lookup("MECHANIC_JOB_LOG") {
+      self.server.job_log = PathBuf::from(value);
+    }
+    if let Some(value) = lookup("MECHANIC_JOB_RETENTION_DAYS") {
+      self.server.job_retention_days = parse_env("MECHANIC_JOB_RETENTION_DAYS", &value)?;
+    }
+    if let Some(value) = lookup("MECHANIC_ARTIFACT_DIR") {
+      self.server.artifact_dir = Some(PathBuf::from(value));
+    }
+    if let Some(value) = lookup("MECHANIC_GITHUB_API_URL") {
+      self.github.api_url = value;
+    }
+    if let Some(value) = lookup("MECHANIC_GITHUB_APP_ID") {
+      self.github.app_id = parse_env("MECHANIC_GITHUB_APP_ID", &value)?;
+    }
+    if let Some(value) = lookup("MECHANIC_GITHUB_PRIVATE_KEY_PATH") {
+      self.github.private_key_path = PathBuf::from(value);
+    }
+    if let Some(value) = lookup("GITHUB_WEBHOOK_SECRET") {
+      self.github.webhook_secret = Secret::new(value);
+    }
+    if let Some(value) = lookup("MECHANIC_LLM_PROVIDER") {
+      self.llm.provider = match value.as_str() {
+        "groq" => ProviderKind::Groq,
+        "openai" => ProviderKind::OpenAi,
+        "ollama" => ProviderKind::Ollama,
+        "mock" => ProviderKind::Mock,
+        other => {
+          return Err(ConfigError::Env {
+            var: "MECHANIC_LLM_PROVIDER",
+            message: format!(
+              "unknown provider {:?} (expected groq, openai, ollama or mock)",
+              other
+            ),
+          });
+        }
+      };
+    }
+    if let Some(value) = lookup("MECHANIC_LLM_BASE_URL") {
+      self.llm.base_url = value;
+    }
+    if let Some(value) = lookup("MECHANIC_LLM_API_KEY").or_else(|| lookup("GROQ_API_KEY")) {
+      self.llm.api_key = Secret::new(value);
+    }
+    if let Some(value) = lookup("MECHANIC_LLM_RECORDINGS") {
+      self.llm.recordings = Some(PathBuf::from(value));
+    }
+    if let Some(value) = lookup("MECHANIC_LLM_MODEL") {
+      self.llm.model = value;
+    }
+    if let Some(value) = lookup("MECHANIC_WORKERS") {
+      self.review.workers = parse_env("MECHANIC_WORKERS", &value)?;
+    }
+    Ok(())
+  }
+
+  /// Check everything that would otherwise fail later, reporting all problems at once.
+  pub fn validate(&self) -> Result<(), ConfigError> {
+    let mut problems = Vec::new();
+
+    if !self.github.api_url.starts_with("http://") && !self.github.api_url.starts_with("https://") {
+      problems.push(format!("github.api_url {:?} is not an http(s) URL", self.github.api_url));
+    }
+    if self.github.app_id == 0 {
+      problems.push("github.app_id is required (or set $MECHANIC_GITHUB_APP_ID)".to_string());
+    }
+    if self.github.private_key_path.as_os_str().is_empty() {
+      problems.push(
+        "github.private_key_path is required (or set $MECHANIC_GITHUB_PRIVATE_KEY_PATH)"
+          .to_string(),
+      );
+    } else if !self.github.private_key_path.is_file() {
+      problems.push(format!(
+        "github.private_key_path {} does not exist",
+        self.github.private_key_path.display()
+      ));
+    }
+    if self.github.webhook_secret.expose().is_empty() {
+      problems
+        .push("github.webhook_secret is required (or set $GITHUB_WEBHOOK_SECRET)".to_string());
+    }
+    if self.llm.needs_api_key() && self.llm.api_key.expose().is_empty() {
+      problems.push("llm.api_key is required (or set $MECHANIC_LLM_API_KEY)".to_string());
+    }
+    let base_url = self.llm.base_url();
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+      problems.push(format!("llm.base_url {:?} is not an http(s) URL", base_url));
+    }
+    if self.llm.provider == ProviderKind::Mock && self.llm.recordings.is_none() {
+      problems.push("llm.recordings is required for the mock provider".to_string());
+    }
+    if self.llm.model.is_empty() {
+      problems.push("llm.model must not be empty".to_string());
+    }
+    let reserved = self.llm.max_tokens as usize + PROMPT_OVERHEAD_TOKENS;
+    if self.llm.context_window() <= reserved {
+      problems.push(format!(
+        "llm.context_window ({}) leaves no room for the diff after llm.max_tokens and {} tokens \
+         of instructions",
+        self.llm.context_window(),
+        PROMPT_OVERHEAD_TOKENS
+      ));
+    }
+    if self.review.workers == 0 {
+      problems.push("review.workers must be at least 1".to_string());
+    }
+    if self.review.queue_capacity == 0 {
+      problems.push("review.queue_capacity must be at least 1".to_string());
+    }
+    // The delivery ledger is rebuilt from the job log, so it can't remember further back.
+    if self.server.job_retention() < self.review.dedup_retention() {
+      problems
+        .push("server.job_retention_days must cover review.dedup_retention_hours".to_string());
+    }
+    if !(1..=MAX_ATTEMPTS).contains(&self.review.max_attempts) {
+      problems.push(format!("review.max_attempts must be between 1 and {}", MAX_ATTEMPTS));
+    }
+    if self.checks.enabled && self.checks.name.trim().is_empty() {
+      problems.push("checks.name must not be empty".to_string());
+    }
+    if self.checks.neutral_at > self.checks.fail_at {
+      problems.push("checks.neutral_at must not be above checks.fail_at".to_string());
+    }
+    if self.statuses.enabled && self.statuses.context.trim().is_empty() {
+      problems.push("statuses.context must not be empty".to_string());
+    }
+    if !(0.0..=1.0).contains(&self.autofix.min_confidence) {
+      problems.push("autofix.min_confidence must be between 0 and 1".to_string());
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+  }
+}
+
+fn parse_env<T: std::str::FromStr>(var: &'static str, value: &str) -> Result<T, ConfigError>
+where
+  T::Err: fmt::Display,
+{
+  value.parse().map_err(|e: T::Err| ConfigError::Env { var, message: e.to_string() })
+}
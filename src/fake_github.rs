@@ -0,0 +1,1059 @@
+// fake_github.rs
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use axum::{Json, Router};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// A pull request the fake serves, with the diff returned for `application/vnd.github.diff`.
+#[derive(Debug, Clone)]
+pub struct FakePullRequest {
+  pub number: u64,
+  pub title: String,
+  pub author: String,
+  pub head_ref: String,
+  pub head_sha: String,
+  pub base_ref: String,
+  pub base_sha: String,
+  pub diff: String,
+}
+
+impl FakePullRequest {
+  /// Take the pull request out of a `pull_request` webhook payload.
+  pub fn from_event(payload: &Value, diff: impl Into<String>) -> Option<FakePullRequest> {
+    let pr = &payload["pull_request"];
+    let text = |value: &Value| value.as_str().map(str::to_string);
+    Some(FakePullRequest {
+      number: pr["number"].as_u64()?,
+      title: text(&pr["title"]).unwrap_or_default(),
+      author: text(&pr["user"]["login"])?,
+      head_ref: text(&pr["head"]["ref"])?,
+      head_sha: text(&pr["head"]["sha"])?,
+      base_ref: text(&pr["base"]["ref"])?,
+      base_sha: text(&pr["base"]["sha"])?,
+      diff: diff.into(),
+    })
+  }
+}
+
+/// A review thread served through the GraphQL API, started by the comment `body`.
+#[derive(Debug, Clone)]
+pub struct FakeReviewThread {
+  pub id: String,
+  pub path: String,
+  /// `None` once outdated, like GitHub.
+  pub line: Option<u32>,
+  pub original_line: u32,
+  pub is_outdated: bool,
+  pub body: String,
+}
+
+/// A write the fake received, for tests to assert on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+  pub method: Method,
+  pub path: String,
+  pub body: Value,
+}
+
+#[derive(Default)]
+struct FakeState {
+  /// (`owner/repo`, number) -> pull request.
+  pulls: BTreeMap<(String, u64), FakePullRequest>,
+  /// (`owner/repo`, ref or sha, path) -> file contents.
+  files: HashMap<(String, String, String), String>,
+  /// Modes of files that aren't regular, non-executable files, keyed like `files`.
+  modes: HashMap<(String, String, String), String>,
+  /// (`owner/repo`, `refs/heads/...`) -> sha.
+  refs: BTreeMap<(String, String), String>,
+  /// (`owner/repo`, sha) -> tree sha of commits created through the Git Data API. A tree's
+  /// files are kept under its sha in `files`, like a commit's.
+  commits: HashMap<(String, String), String>,
+  /// Pull requests closed through the API; the rest are open.
+  closed_pulls: HashSet<(String, u64)>,
+  /// `owner/repo` -> issue and pull request comments, in the order they were posted.
+  comments: HashMap<String, Vec<Value>>,
+  /// (`owner/repo`, number) -> review threads, as GraphQL nodes with every comment.
+  threads: BTreeMap<(String, u64), Vec<Value>>,
+  /// (`owner/repo`, number) -> the same threads' comments as the REST API lists them.
+  review_comments: BTreeMap<(String, u64), Vec<Value>>,
+  /// (`owner/repo`, login) -> repository permission; anyone else gets `read`.
+  permissions: HashMap<(String, String), String>,
+  /// Answer `resolveReviewThread` with an error, like a token without that permission.
+  forbid_resolving: bool,
+  requests: Vec<RecordedRequest>,
+  next_id: u64,
+}
+
+impl FakeState {
+  fn next_id(&mut self) -> u64 {
+    self.next_id += 1;
+    self.next_id
+  }
+
+  /// A fresh 40 character hex sha.
+  fn next_sha(&mut self) -> String {
+    let id = self.next_id();
+    hex::encode(Sha256::digest(id.to_be_bytes()))[..40].to_string()
+  }
+
+  fn knows_commit(&self, repo: &str, sha: &str) -> bool {
+    self.refs.iter().any(|((r, _), s)| r == repo && s == sha)
+      || self
+        .pulls
+        .iter()
+        .any(|((r, _), pull)| r == repo && (pull.head_sha == sha || pull.base_sha == sha))
+      || self.commits.contains_key(&(repo.to_string(), sha.to_string()))
+  }
+
+  /// The pull request as the API returns it, closed if it was closed through the API.
+  fn pull_json(&self, repo: &str, pull: &FakePullRequest) -> Value {
+    let mut json = pull_json(repo, pull);
+    if self.closed_pulls.contains(&(repo.to_string(), pull.number)) {
+      json["state"] = json!("closed");
+    }
+    json
+  }
+
+  /// Copy every file at `from` (a commit or tree sha) to `to`.
+  fn copy_files(&mut self, repo: &str, from: &str, to: &str) {
+    let copy = |files: &HashMap<(String, String, String), String>| -> Vec<_> {
+      files
+        .iter()
+        .filter(|((r, git_ref, _), _)| r == repo && git_ref == from)
+        .map(|((_, _, path), value)| {
+          ((repo.to_string(), to.to_string(), path.clone()), value.clone())
+        })
+        .collect()
+    };
+    let (files, modes) = (copy(&self.files), copy(&self.modes));
+    self.files.extend(files);
+    self.modes.extend(modes);
+  }
+
+  /// Add a reply by `author` to the thread started by review comment `in_reply_to`, to both
+  /// the REST comments and the GraphQL thread. Returns the reply's id.
+  fn reply_in_thread(
+    &mut self,
+    key: &(String, u64),
+    in_reply_to: u64,
+    author: Value,
+    body: &str,
+  ) -> Option<u64> {
+    let id = self.next_id();
+    let comments = self.review_comments.get_mut(key)?;
+    let root = comments.iter().find(|comment| comment["id"] == in_reply_to)?;
+    let mut reply = root.clone();
+    reply["id"] = json!(id);
+    reply["in_reply_to_id"] = json!(in_reply_to);
+    reply["user"] = author.clone();
+    reply["body"] = json!(body);
+    comments.push(reply);
+
+    let comment = thread_comment(id, &author, body);
+    let threads = self.threads.get_mut(key).into_iter().flatten();
+    if let Some(node) = threads.into_iter().find(|node| node["rootId"] == in_reply_to)
+      && let Some(comments) = node["comments"].as_array_mut()
+    {
+      comments.push(comment);
+    }
+    Some(id)
+  }
+}
+
+/// In-process stand-in for the parts of the GitHub REST API Mechanic uses, serving fixture data
+/// and recording every write, so the whole bot can run without network access.
+/// Point `github.api_url` at [`FakeGitHub::start`]'s URL; any app JWT is accepted.
+#[derive(Clone, Default)]
+pub struct FakeGitHub {
+  state: Arc<Mutex<FakeState>>,
+}
+
+type Shared = State<FakeGitHub>;
+
+impl FakeGitHub {
+  pub fn new() -> FakeGitHub {
+    FakeGitHub::default()
+  }
+
+  /// Serve `pull` in `repo` (`owner/name`). Its head and base branches are added as refs.
+  pub fn add_pull_request(&self, repo: &str, pull: FakePullRequest) {
+    let mut state = self.state.lock().unwrap();
+    state.refs.insert((repo.to_string(), heads(&pull.head_ref)), pull.head_sha.clone());
+    state.refs.insert((repo.to_string(), heads(&pull.base_ref)), pull.base_sha.clone());
+    state.pulls.insert((repo.to_string(), pull.number), pull);
+  }
+
+  /// Serve `contents` for `path` at `git_ref` (a branch name or commit sha).
+  pub fn add_file(&self, repo: &str, git_ref: &str, path: &str, contents: impl Into<String>) {
+    let key = (repo.to_string(), git_ref.to_string(), path.to_string());
+    self.state.lock().unwrap().files.insert(key, contents.into());
+  }
+
+  /// Give `path` at `git_ref` a mode other than a regular file's, e.g. `100755`.
+  pub fn set_file_mode(&self, repo: &str, git_ref: &str, path: &str, mode: &str) {
+    let key = (repo.to_string(), git_ref.to_string(), path.to_string());
+    self.state.lock().unwrap().modes.insert(key, mode.to_string());
+  }
+
+  /// Serve `thread` on pull request `number` in `repo`, started by Mechanic. Returns the id of
+  /// its first comment.
+  pub fn add_review_thread(&self, repo: &str, number: u64, thread: FakeReviewThread) -> u64 {
+    self.add_review_thread_by(repo, number, thread, &bot())
+  }
+
+  /// Serve `thread` on pull request `number` in `repo`, started by `login` instead of Mechanic.
+  pub fn add_foreign_review_thread(
+    &self,
+    repo: &str,
+    number: u64,
+    thread: FakeReviewThread,
+    login: &str,
+  ) -> u64 {
+    self.add_review_thread_by(repo, number, thread, &user(login, 4))
+  }
+
+  fn add_review_thread_by(
+    &self,
+    repo: &str,
+    number: u64,
+    thread: FakeReviewThread,
+    author: &Value,
+  ) -> u64 {
+    let mut state = self.state.lock().unwrap();
+    let root_id = state.next_id();
+    let node = json!({
+      "id": thread.id,
+      "rootId": root_id,
+      "isResolved": false,
+      "isOutdated": thread.is_outdated,
+      "path": thread.path,
+      "line": thread.line,
+      "originalLine": thread.original_line,
+      "comments": [thread_comment(root_id, author, &thread.body)],
+    });
+    let comment = json!({
+      "id": root_id,
+      "body": thread.body,
+      "user": author,
+      "path": thread.path,
+      "line": thread.line,
+      "original_line": thread.original_line,
+      "diff_hunk": "",
+      "in_reply_to_id": null,
+      "pull_request_url": format!("https://api.github.com/repos/{}/pulls/{}", repo, number),
+    });
+    let key = (repo.to_string(), number);
+    state.threads.entry(key.clone()).or_default().push(node);
+    state.review_comments.entry(key).or_default().push(comment);
+    root_id
+  }
+
+  /// Comment on pull request `number` as `login`, like a developer would. Returns its id.
+  pub fn add_comment(&self, repo: &str, number: u64, login: &str, body: &str) -> u64 {
+    let mut state = self.state.lock().unwrap();
+    let id = state.next_id();
+    let comment = issue_comment(repo, number, id, user(login, 4), &json!(body));
+    state.comments.entry(repo.to_string()).or_default().push(comment);
+    id
+  }
+
+  /// Reply to the thread started by review comment `in_reply_to` as `login`, like a developer
+  /// answering Mechanic. Returns the reply's id.
+  pub fn add_review_reply(
+    &self,
+    repo: &str,
+    number: u64,
+    in_reply_to: u64,
+    login: &str,
+    body: &str,
+  ) -> Option<u64> {
+    let key = (repo.to_string(), number);
+    self.state.lock().unwrap().reply_in_thread(&key, in_reply_to, user(login, 4), body)
+  }
+
+  /// Give `login` the `permission` (`admin`, `write`, `read` or `none`) on `repo`.
+  pub fn set_permission(&self, repo: &str, login: &str, permission: &str) {
+    let key = (repo.to_string(), login.to_string());
+    self.state.lock().unwrap().permissions.insert(key, permission.to_string());
+  }
+
+  /// Refuse to resolve review threads from now on.
+  pub fn forbid_resolving_threads(&self) {
+    self.state.lock().unwrap().forbid_resolving = true;
+  }
+
+  /// Whether the thread `id` is resolved and its comment bodies, oldest first.
+  pub fn review_thread(&self, id: &str) -> Option<(bool, Vec<String>)> {
+    let state = self.state.lock().unwrap();
+    let node = state.threads.values().flatten().find(|node| node["id"] == id)?;
+    let comments = node["comments"].as_array()?.iter().filter_map(|c| c["body"].as_str());
+    Some((node["isResolved"] == true, comments.map(str::to_string).collect()))
+  }
+
+  /// Every write received so far, oldest first.
+  pub fn requests(&self) -> Vec<RecordedRequest> {
+    self.state.lock().unwrap().requests.clone()
+  }
+
+  /// Writes to paths ending in `suffix`, e.g. `/pulls/7/reviews`.
+  pub fn requests_to(&self, method: Method, suffix: &str) -> Vec<RecordedRequest> {
+    let requests = self.requests();
+    requests.into_iter().filter(|r| r.method == method && r.path.ends_with(suffix)).collect()
+  }
+
+  /// Bind to a free local port and serve in the background. Returns the API base URL.
+  pub async fn start(&self) -> std::io::Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let app = self.router();
+    tokio::spawn(async move {
+      if let Err(e) = axum::serve(listener, app).await {
+        println!("Fake GitHub stopped: {}", e);
+      }
+    });
+    Ok(url)
+  }
+
+  pub fn router(&self) -> Router {
+    Router::new()
+      .route("/app", get(get_app))
+      .route("/app/installations/{id}/access_tokens", post(create_installation_token))
+      .route("/user", get(current_user))
+      .route("/graphql", post(graphql))
+      .route("/repos/{owner}/{repo}/pulls", get(list_pulls).post(create_pull))
+      .route("/repos/{owner}/{repo}/pulls/{number}", get(get_pull).patch(update_pull))
+      .route("/repos/{owner}/{repo}/pulls/{number}/reviews", post(create_review))
+      .route("/repos/{owner}/{repo}/pulls/{number}/comments", get(list_review_comments))
+      .route(
+        "/repos/{owner}/{repo}/pulls/{number}/comments/{id}/replies",
+        post(create_review_reply),
+      )
+      .route(
+        "/repos/{owner}/{repo}/issues/{number}/comments",
+        get(list_comments).post(create_comment),
+      )
+      // GitHub takes POST as well as PATCH for comment edits, and some clients send either.
+      .route(
+        "/repos/{owner}/{repo}/issues/comments/{id}",
+        patch(update_comment).post(update_comment),
+      )
+      .route("/repos/{owner}/{repo}/issues/comments/{id}/reactions", post(create_reaction))
+      .route(
+        "/repos/{owner}/{repo}/collaborators/{login}/permission",
+        get(get_collaborator_permission),
+      )
+      .route("/repos/{owner}/{repo}/commits/{sha}", get(get_commit))
+      .route("/repos/{owner}/{repo}/contents/{*path}", get(get_contents))
+      .route("/repos/{owner}/{repo}/git/ref/{*git_ref}", get(get_ref))
+      .route("/repos/{owner}/{repo}/git/refs", post(create_ref))
+      .route("/repos/{owner}/{repo}/git/refs/{*git_ref}", patch(update_ref))
+      .route("/repos/{owner}/{repo}/git/commits/{sha}", get(get_git_commit))
+      .route("/repos/{owner}/{repo}/git/commits", post(create_git_commit))
+      .route("/repos/{owner}/{repo}/git/trees", post(create_tree))
+      .route("/repos/{owner}/{repo}/git/trees/{sha}", get(get_tree))
+      .route("/repos/{owner}/{repo}/check-runs", post(create_check_run))
+      .route("/repos/{owner}/{repo}/check-runs/{id}", patch(update_check_run))
+      .route("/repos/{owner}/{repo}/statuses/{sha}", post(create_status))
+      .fallback(async || not_found())
+      .with_state(self.clone())
+  }
+
+  fn record(&self, method: Method, uri: &Uri, body: &Value) -> u64 {
+    let mut state = self.state.lock().unwrap();
+    let path = uri.path().to_string();
+    state.requests.push(RecordedRequest { method, path, body: body.clone() });
+    state.next_id()
+  }
+}
+
+fn heads(branch: &str) -> String {
+  format!("refs/heads/{}", branch)
+}
+
+fn not_found() -> Response {
+  let body = json!({ "message": "Not Found", "documentation_url": "https://docs.github.com/rest" });
+  (StatusCode::NOT_FOUND, Json(body)).into_response()
+}
+
+/// A user object with every field Octocrab's `Author` requires.
+fn user(login: &str, id: u64) -> Value {
+  let url = format!("https://api.github.com/users/{}", login);
+  json!({
+    "login": login,
+    "id": id,
+    "node_id": format!("U_{}", id),
+    "avatar_url": format!("https://avatars.githubusercontent.com/u/{}", id),
+    "gravatar_id": "",
+    "url": url,
+    "html_url": format!("https://github.com/{}", login),
+    "followers_url": format!("{}/followers", url),
+    "following_url": format!("{}/following", url),
+    "gists_url": format!("{}/gists", url),
+    "starred_url": format!("{}/starred", url),
+    "subscriptions_url": format!("{}/subscriptions", url),
+    "organizations_url": format!("{}/orgs", url),
+    "repos_url": format!("{}/repos", url),
+    "events_url": format!("{}/events", url),
+    "received_events_url": format!("{}/received_events", url),
+    "type": "User",
+    "site_admin": false
+  })
+}
+
+fn bot() -> Value {
+  let mut bot = user("mechanic[bot]", 1);
+  bot["type"] = json!("Bot");
+  bot
+}
+
+/// An issue comment with every field Octocrab's `Comment` requires.
+fn issue_comment(repo: &str, number: u64, id: u64, author: Value, body: &Value) -> Value {
+  let now = Utc::now().to_rfc3339();
+  json!({
+    "id": id,
+    "node_id": format!("IC_{}", id),
+    "url": format!("https://api.github.com/repos/{}/issues/comments/{}", repo, id),
+    "html_url": format!("https://github.com/{}/pull/{}#issuecomment-{}", repo, number, id),
+    "issue_url": format!("https://api.github.com/repos/{}/issues/{}", repo, number),
+    "body": body,
+    "user": author,
+    "author_association": "NONE",
+    "created_at": now,
+    "updated_at": now,
+  })
+}
+
+/// A review thread comment as GraphQL returns it to the app; the app wrote Mechanic's.
+fn thread_comment(id: u64, author: &Value, body: &str) -> Value {
+  json!({ "body": body, "databaseId": id, "viewerDidAuthor": author["login"] == bot()["login"] })
+}
+
+fn pull_json(repo: &str, pull: &FakePullRequest) -> Value {
+  let url = format!("https://api.github.com/repos/{}/pulls/{}", repo, pull.number);
+  json!({
+    "url": url,
+    "id": pull.number,
+    "number": pull.number,
+    "state": "open",
+    "title": pull.title,
+    "html_url": format!("https://github.com/{}/pull/{}", repo, pull.number),
+    "user": user(&pull.author, 2),
+    "locked": false,
+    "maintainer_can_modify": false,
+    "draft": false,
+    "head": { "ref": pull.head_ref, "sha": pull.head_sha, "label": format!("{}:{}", repo, pull.head_ref) },
+    "base": { "ref": pull.base_ref, "sha": pull.base_sha, "label": format!("{}:{}", repo, pull.base_ref) },
+    "_links": {}
+  })
+}
+
+async fn get_app(headers: HeaderMap) -> Response {
+  if !has_app_jwt(&headers) {
+    return jwt_rejected();
+  }
+  Json(json!({ "id": 1, "slug": "mechanic", "name": "Mechanic" })).into_response()
+}
+
+async fn create_installation_token(
+  State(fake): Shared,
+  Path(id): Path<u64>,
+  headers: HeaderMap,
+  method: Method,
+  uri: Uri,
+) -> Response {
+  if !has_app_jwt(&headers) {
+    return jwt_rejected();
+  }
+  fake.record(method, &uri, &Value::Null);
+  let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+  let body = json!({ "token": format!("ghs_fake_installation_{}", id), "expires_at": expires_at });
+  (StatusCode::CREATED, Json(body)).into_response()
+}
+
+/// Any signed-looking app JWT will do; the fake doesn't know the app's public key.
+fn has_app_jwt(headers: &HeaderMap) -> bool {
+  let auth = headers.get("authorization").and_then(|v| v.to_str().ok()).unwrap_or_default();
+  auth.strip_prefix("Bearer ").is_some_and(|jwt| jwt.split('.').count() == 3)
+}
+
+fn jwt_rejected() -> Response {
+  let body = json!({ "message": "A JSON web token could not be decoded" });
+  (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+async fn current_user() -> Json<Value> {
+  Json(bot())
+}
+
+#[derive(Deserialize)]
+struct PullsQuery {
+  state: Option<String>,
+  base: Option<String>,
+}
+
+async fn list_pulls(
+  State(fake): Shared,
+  Path((owner, repo)): Path<(String, String)>,
+  Query(query): Query<PullsQuery>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let state = fake.state.lock().unwrap();
+  let wanted = query.state.unwrap_or_else(|| "open".to_string());
+  // Newest first, like GitHub's default sort.
+  let pulls: Vec<Value> = state
+    .pulls
+    .iter()
+    .rev()
+    .filter(|((r, _), pull)| {
+      *r == full_name && query.base.as_ref().is_none_or(|b| *b == pull.base_ref)
+    })
+    .map(|(_, pull)| state.pull_json(&full_name, pull))
+    .filter(|pull| wanted == "all" || pull["state"] == wanted.as_str())
+    .collect();
+  Json(pulls).into_response()
+}
+
+async fn update_pull(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let key = (format!("{}/{}", owner, repo), number);
+  if !fake.state.lock().unwrap().pulls.contains_key(&key) {
+    return not_found();
+  }
+  fake.record(method, &uri, &body);
+  let mut state = fake.state.lock().unwrap();
+  match body["state"].as_str() {
+    Some("closed") => state.closed_pulls.insert(key.clone()),
+    Some("open") => state.closed_pulls.remove(&key),
+    _ => false,
+  };
+  let pull = state.pulls.get_mut(&key).unwrap();
+  if let Some(title) = body["title"].as_str() {
+    pull.title = title.to_string();
+  }
+  let pull = pull.clone();
+  Json(state.pull_json(&key.0, &pull)).into_response()
+}
+
+async fn get_pull(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+  headers: HeaderMap,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let state = fake.state.lock().unwrap();
+  let Some(pull) = state.pulls.get(&(full_name.clone(), number)) else { return not_found() };
+
+  let accept = headers.get("accept").and_then(|v| v.to_str().ok()).unwrap_or_default();
+  if accept.contains("diff") {
+    ([("content-type", "text/plain; charset=utf-8")], pull.diff.clone()).into_response()
+  } else {
+    Json(state.pull_json(&full_name, pull)).into_response()
+  }
+}
+
+async fn create_review(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let id = fake.record(method, &uri, &body);
+  let html_url =
+    format!("https://github.com/{}/{}/pull/{}#pullrequestreview-{}", owner, repo, number, id);
+  let state = if body["event"] == "REQUEST_CHANGES" { "CHANGES_REQUESTED" } else { "COMMENTED" };
+  let review = json!({
+    "id": id,
+    "node_id": format!("PRR_{}", id),
+    "user": bot(),
+    "body": body["body"],
+    "state": state,
+    "commit_id": body["commit_id"],
+    "html_url": html_url,
+  });
+  Json(review).into_response()
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+  page: Option<u32>,
+}
+
+/// Every review comment on the first page; there is never a second.
+async fn list_review_comments(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+  Query(query): Query<PageQuery>,
+) -> Response {
+  if query.page.is_some_and(|page| page > 1) {
+    return Json(json!([])).into_response();
+  }
+  let key = (format!("{}/{}", owner, repo), number);
+  let state = fake.state.lock().unwrap();
+  Json(state.review_comments.get(&key).cloned().unwrap_or_default()).into_response()
+}
+
+async fn create_review_reply(
+  State(fake): Shared,
+  Path((owner, repo, number, id)): Path<(String, String, u64, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  fake.record(method, &uri, &body);
+  let key = (format!("{}/{}", owner, repo), number);
+  let text = body["body"].as_str().unwrap_or_default();
+  let mut state = fake.state.lock().unwrap();
+  let Some(reply_id) = state.reply_in_thread(&key, id, bot(), text) else { return not_found() };
+  let reply = json!({ "id": reply_id, "in_reply_to_id": id, "body": text, "user": bot() });
+  (StatusCode::CREATED, Json(reply)).into_response()
+}
+
+async fn list_comments(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let issue_url = format!("https://api.github.com/repos/{}/issues/{}", full_name, number);
+  let state = fake.state.lock().unwrap();
+  let comments: Vec<Value> = state
+    .comments
+    .get(&full_name)
+    .into_iter()
+    .flatten()
+    .filter(|comment| comment["issue_url"] == issue_url.as_str())
+    .cloned()
+    .collect();
+  Json(comments).into_response()
+}
+
+async fn create_comment(
+  State(fake): Shared,
+  Path((owner, repo, number)): Path<(String, String, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let id = fake.record(method, &uri, &body);
+  let full_name = format!("{}/{}", owner, repo);
+  let comment = issue_comment(&full_name, number, id, bot(), &body["body"]);
+  fake.state.lock().unwrap().comments.entry(full_name).or_default().push(comment.clone());
+  (StatusCode::CREATED, Json(comment)).into_response()
+}
+
+async fn update_comment(
+  State(fake): Shared,
+  Path((owner, repo, id)): Path<(String, String, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  fake.record(method, &uri, &body);
+  let full_name = format!("{}/{}", owner, repo);
+  let mut state = fake.state.lock().unwrap();
+  let comments = state.comments.entry(full_name).or_default();
+  let Some(comment) = comments.iter_mut().find(|comment| comment["id"] == id) else {
+    return not_found();
+  };
+  comment["body"] = body["body"].clone();
+  comment["updated_at"] = json!(Utc::now().to_rfc3339());
+  Json(comment.clone()).into_response()
+}
+
+async fn create_reaction(
+  State(fake): Shared,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let id = fake.record(method, &uri, &body);
+  let reaction = json!({ "id": id, "node_id": format!("REA_{}", id), "user": bot(), "content": body["content"] });
+  (StatusCode::CREATED, Json(reaction)).into_response()
+}
+
+async fn get_collaborator_permission(
+  State(fake): Shared,
+  Path((owner, repo, login)): Path<(String, String, String)>,
+) -> Response {
+  let key = (format!("{}/{}", owner, repo), login.clone());
+  let state = fake.state.lock().unwrap();
+  let permission = state.permissions.get(&key).map_or("read", String::as_str);
+  Json(json!({ "permission": permission, "role_name": permission, "user": user(&login, 3) }))
+    .into_response()
+}
+
+async fn get_commit(
+  State(fake): Shared,
+  Path((owner, repo, sha)): Path<(String, String, String)>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let state = fake.state.lock().unwrap();
+  if !state.knows_commit(&full_name, &sha) {
+    return not_found();
+  }
+  let files: Vec<Value> = state
+    .files
+    .keys()
+    .filter(|(r, git_ref, _)| *r == full_name && *git_ref == sha)
+    .map(|(_, _, path)| json!({ "filename": path, "status": "modified" }))
+    .collect();
+  Json(json!({ "sha": sha, "commit": { "message": "" }, "files": files })).into_response()
+}
+
+#[derive(Deserialize)]
+struct RefQuery {
+  #[serde(rename = "ref")]
+  git_ref: Option<String>,
+}
+
+async fn get_contents(
+  State(fake): Shared,
+  Path((owner, repo, path)): Path<(String, String, String)>,
+  Query(query): Query<RefQuery>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let git_ref = query.git_ref.unwrap_or_else(|| "main".to_string());
+  let state = fake.state.lock().unwrap();
+  let Some(contents) = state.files.get(&(full_name, git_ref, path.clone())) else {
+    return not_found();
+  };
+  let name = path.rsplit('/').next().unwrap_or(&path);
+  let sha = hex::encode(Sha256::digest(contents))[..40].to_string();
+  let url = format!("https://api.github.com/repos/{}/{}/contents/{}", owner, repo, path);
+  let git_url = format!("https://api.github.com/repos/{}/{}/git/blobs/{}", owner, repo, sha);
+  let html_url = format!("https://github.com/{}/{}/blob/{}/{}", owner, repo, sha, path);
+  Json(json!({
+    "type": "file",
+    "encoding": "base64",
+    "name": name,
+    "path": path,
+    "sha": sha,
+    "size": contents.len(),
+    "url": url,
+    "git_url": git_url,
+    "html_url": html_url,
+    "download_url": null,
+    "content": STANDARD.encode(contents),
+    "_links": { "self": url, "git": git_url, "html": html_url },
+  }))
+  .into_response()
+}
+
+fn ref_json(repo: &str, git_ref: &str, sha: &str) -> Value {
+  json!({
+    "ref": git_ref,
+    "node_id": format!("REF_{}", sha),
+    "url": format!("https://api.github.com/repos/{}/git/{}", repo, git_ref),
+    "object": {
+      "type": "commit",
+      "sha": sha,
+      "url": format!("https://api.github.com/repos/{}/git/commits/{}", repo, sha)
+    }
+  })
+}
+
+async fn get_ref(
+  State(fake): Shared,
+  Path((owner, repo, git_ref)): Path<(String, String, String)>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let git_ref = format!("refs/{}", git_ref);
+  let state = fake.state.lock().unwrap();
+  match state.refs.get(&(full_name.clone(), git_ref.clone())) {
+    Some(sha) => Json(ref_json(&full_name, &git_ref, sha)).into_response(),
+    None => not_found(),
+  }
+}
+
+async fn create_ref(
+  State(fake): Shared,
+  Path((owner, repo)): Path<(String, String)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let (Some(git_ref), Some(sha)) = (body["ref"].as_str(), body["sha"].as_str()) else {
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "message": "Invalid request" })))
+      .into_response();
+  };
+  let key = (full_name.clone(), git_ref.to_string());
+  if fake.state.lock().unwrap().refs.contains_key(&key) {
+    let body = json!({ "message": "Reference already exists" });
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+  }
+  fake.record(method, &uri, &body);
+  fake.state.lock().unwrap().refs.insert(key, sha.to_string());
+  (StatusCode::CREATED, Json(ref_json(&full_name, git_ref, sha))).into_response()
+}
+
+async fn update_ref(
+  State(fake): Shared,
+  Path((owner, repo, git_ref)): Path<(String, String, String)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let key = (full_name.clone(), format!("refs/{}", git_ref));
+  let Some(sha) = body["sha"].as_str() else {
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "message": "Invalid request" })))
+      .into_response();
+  };
+  if !fake.state.lock().unwrap().refs.contains_key(&key) {
+    let body = json!({ "message": "Reference does not exist" });
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+  }
+  fake.record(method, &uri, &body);
+  fake.state.lock().unwrap().refs.insert(key.clone(), sha.to_string());
+  Json(ref_json(&full_name, &key.1, sha)).into_response()
+}
+
+/// Trees of commits the fake didn't create are named after the commit.
+fn tree_of(commit_sha: &str) -> String {
+  format!("tree-{}", commit_sha)
+}
+
+async fn get_git_commit(
+  State(fake): Shared,
+  Path((owner, repo, sha)): Path<(String, String, String)>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let state = fake.state.lock().unwrap();
+  if !state.knows_commit(&full_name, &sha) {
+    return not_found();
+  }
+  let tree = state.commits.get(&(full_name, sha.clone())).cloned().unwrap_or_else(|| tree_of(&sha));
+  Json(json!({ "sha": sha, "tree": { "sha": tree }, "message": "" })).into_response()
+}
+
+/// Every file in a tree, as a recursive listing returns it.
+async fn get_tree(
+  State(fake): Shared,
+  Path((owner, repo, sha)): Path<(String, String, String)>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let commit = sha.strip_prefix("tree-").unwrap_or(&sha);
+  let state = fake.state.lock().unwrap();
+  let mut entries: Vec<Value> = state
+    .files
+    .keys()
+    .filter(|(r, git_ref, _)| *r == full_name && git_ref == commit)
+    .map(|key| {
+      let mode = state.modes.get(key).map(String::as_str).unwrap_or("100644");
+      json!({ "path": key.2, "mode": mode, "type": "blob" })
+    })
+    .collect();
+  if entries.is_empty() && !state.knows_commit(&full_name, commit) {
+    return not_found();
+  }
+  entries.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+  Json(json!({ "sha": sha, "tree": entries, "truncated": false })).into_response()
+}
+
+/// Trees are only built on top of a commit's tree, with whole file contents.
+async fn create_tree(
+  State(fake): Shared,
+  Path((owner, repo)): Path<(String, String)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let base = body["base_tree"].as_str().unwrap_or_default();
+  let entries = body["tree"].as_array().cloned().unwrap_or_default();
+  fake.record(method, &uri, &body);
+  let mut state = fake.state.lock().unwrap();
+  let sha = state.next_sha();
+  let base_commit = base.strip_prefix("tree-").unwrap_or(base).to_string();
+  state.copy_files(&full_name, &base_commit, &sha);
+  state.copy_files(&full_name, base, &sha);
+  for entry in entries {
+    let (Some(path), Some(contents)) = (entry["path"].as_str(), entry["content"].as_str()) else {
+      continue;
+    };
+    let key = (full_name.clone(), sha.clone(), path.to_string());
+    state.files.insert(key.clone(), contents.to_string());
+    match entry["mode"].as_str() {
+      Some("100644") | None => state.modes.remove(&key),
+      Some(mode) => state.modes.insert(key, mode.to_string()),
+    };
+  }
+  (StatusCode::CREATED, Json(json!({ "sha": sha, "tree": [] }))).into_response()
+}
+
+async fn create_git_commit(
+  State(fake): Shared,
+  Path((owner, repo)): Path<(String, String)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let Some(tree) = body["tree"].as_str().map(str::to_string) else {
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "message": "Invalid request" })))
+      .into_response();
+  };
+  fake.record(method, &uri, &body);
+  let mut state = fake.state.lock().unwrap();
+  let sha = state.next_sha();
+  state.copy_files(&full_name, &tree, &sha);
+  state.commits.insert((full_name, sha.clone()), tree.clone());
+  let commit = json!({ "sha": sha, "tree": { "sha": tree }, "message": body["message"] });
+  (StatusCode::CREATED, Json(commit)).into_response()
+}
+
+async fn create_pull(
+  State(fake): Shared,
+  Path((owner, repo)): Path<(String, String)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let full_name = format!("{}/{}", owner, repo);
+  let (Some(head), Some(base)) = (body["head"].as_str(), body["base"].as_str()) else {
+    return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "message": "Invalid request" })))
+      .into_response();
+  };
+  fake.record(method, &uri, &body);
+  let mut state = fake.state.lock().unwrap();
+  let sha_of = |branch: &str| {
+    let tip = state.refs.get(&(full_name.clone(), heads(branch))).cloned();
+    let pulls = state.pulls.iter().filter(|((r, _), _)| *r == full_name);
+    tip.or_else(|| pulls.map(|(_, p)| p).find(|p| p.head_ref == branch).map(|p| p.head_sha.clone()))
+  };
+  let (head_sha, base_sha) = (sha_of(head).unwrap_or_default(), sha_of(base).unwrap_or_default());
+  let number = state.pulls.keys().filter(|(r, _)| *r == full_name).map(|(_, n)| n + 1).max();
+  let pull = FakePullRequest {
+    number: number.unwrap_or(1),
+    title: body["title"].as_str().unwrap_or_default().to_string(),
+    author: "mechanic[bot]".to_string(),
+    head_ref: head.to_string(),
+    head_sha,
+    base_ref: base.to_string(),
+    base_sha,
+    diff: String::new(),
+  };
+  let json = pull_json(&full_name, &pull);
+  state.pulls.insert((full_name, pull.number), pull);
+  (StatusCode::CREATED, Json(json)).into_response()
+}
+
+async fn create_check_run(
+  State(fake): Shared,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let id = fake.record(method, &uri, &body);
+  let mut check_run = body;
+  check_run["id"] = json!(id);
+  check_run["html_url"] = json!(format!("https://github.com/checks/{}", id));
+  (StatusCode::CREATED, Json(check_run)).into_response()
+}
+
+async fn update_check_run(
+  State(fake): Shared,
+  Path((_, _, id)): Path<(String, String, u64)>,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  fake.record(method, &uri, &body);
+  let mut check_run = body;
+  check_run["id"] = json!(id);
+  check_run["html_url"] = json!(format!("https://github.com/checks/{}", id));
+  Json(check_run).into_response()
+}
+
+async fn create_status(
+  State(fake): Shared,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  let id = fake.record(method, &uri, &body);
+  let mut status = body;
+  status["id"] = json!(id);
+  status["creator"] = bot();
+  (StatusCode::CREATED, Json(status)).into_response()
+}
+
+/// The review thread queries and mutations Mechanic sends, told apart by the field they use.
+async fn graphql(
+  State(fake): Shared,
+  method: Method,
+  uri: Uri,
+  Json(body): Json<Value>,
+) -> Response {
+  fake.record(method, &uri, &body);
+  let query = body["query"].as_str().unwrap_or("");
+  let variables = &body["variables"];
+  let mut state = fake.state.lock().unwrap();
+
+  if query.contains("reviewThreads") {
+    let repo = format!(
+      "{}/{}",
+      variables["owner"].as_str().unwrap_or(""),
+      variables["repo"].as_str().unwrap_or("")
+    );
+    let number = variables["number"].as_u64().unwrap_or(0);
+    let nodes: Vec<Value> = state
+      .threads
+      .get(&(repo, number))
+      .into_iter()
+      .flatten()
+      .map(|node| {
+        let mut node = node.clone();
+        node["comments"] = json!({ "nodes": node["comments"].clone() });
+        node
+      })
+      .collect();
+    let threads = json!({ "reviewThreads": { "nodes": nodes } });
+    return Json(json!({ "data": { "repository": { "pullRequest": threads } } })).into_response();
+  }
+
+  let id = variables["id"].as_str().unwrap_or("");
+  let forbid_resolving = state.forbid_resolving;
+  let reply_id =
+    if query.contains("addPullRequestReviewThreadReply") { state.next_id() } else { 0 };
+  let Some(node) = state.threads.values_mut().flatten().find(|node| node["id"] == id) else {
+    return graphql_error(&format!("Could not resolve to a node with the global id of '{}'", id));
+  };
+  if query.contains("resolveReviewThread") {
+    if forbid_resolving {
+      return graphql_error("Resource not accessible by integration");
+    }
+    node["isResolved"] = json!(true);
+    return Json(json!({ "data": { "resolveReviewThread": { "thread": { "id": id } } } }))
+      .into_response();
+  }
+  if query.contains("addPullRequestReviewThreadReply") {
+    let body = variables["body"].as_str().unwrap_or("");
+    let comment = thread_comment(reply_id, &bot(), body);
+    if let Some(comments) = node["comments"].as_array_mut() {
+      comments.push(comment);
+    }
+    let comment = json!({ "comment": { "id": format!("PRRC_{}", id) } });
+    return Json(json!({ "data": { "addPullRequestReviewThreadReply": comment } })).into_response();
+  }
+  graphql_error("Unsupported query")
+}
+
+fn graphql_error(message: &str) -> Response {
+  Json(json!({ "data": null, "errors": [{ "message": message }] })).into_response()
+}
@@ -0,0 +1,25 @@
+// queue.rs
+use crate::services::queue::QueueStats;
+use crate::services::store::JobRecord;
+use crate::state::AppState;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+/// Report how many review jobs are waiting and running.
+pub async fn queue_status_handler(State(state): State<AppState>) -> Json<QueueStats> {
+  Json(state.queue.stats())
+}
+
+/// List every recorded job, most recently updated first.
+pub async fn list_jobs_handler(State(state): State<AppState>) -> Json<Vec<JobRecord>> {
+  Json(state.store.list())
+}
+
+/// Show one job, including its state history and result.
+pub async fn get_job_handler(
+  State(state): State<AppState>,
+  Path(id): Path<String>,
+) -> Result<Json<JobRecord>, StatusCode> {
+  state.store.get(&id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
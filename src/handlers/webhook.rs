@@ -1,20 +1,128 @@
 // webhook.rs
-use crate::helpers::event::process_event_and_get_token;
-use axum::{Json, response::IntoResponse};
+use crate::helpers::event::{
+  DeliveryStatus, EventError, mark_review_queued, mark_review_unqueued, process_github_payload,
+};
+use crate::helpers::redact::redact;
+use crate::helpers::signature::{SIGNATURE_HEADER, verify_signature};
+use crate::services::queue::ReviewJob;
+use crate::services::store::JobState;
+use crate::state::AppState;
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use serde::Serialize;
 use serde_json::Value;
 
+/// JSON body returned to GitHub for every delivery. Never carries credentials.
+#[derive(Debug, Serialize)]
+pub struct WebhookAck {
+  pub delivery_id: Option<String>,
+  pub event: Option<String>,
+  pub status: DeliveryStatus,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl WebhookAck {
+  fn new(headers: &HeaderMap, status: DeliveryStatus) -> Self {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    WebhookAck {
+      delivery_id: header("X-GitHub-Delivery"),
+      event: header("X-GitHub-Event"),
+      status,
+      error: None,
+    }
+  }
+
+  fn with_error(mut self, error: &str) -> Self {
+    self.error = Some(redact(error));
+    self
+  }
+}
+
+/// Verify and decode a delivery, then hand it to the review queue.
+/// Responds before any review work starts so GitHub's 10 second timeout is never hit.
 pub async fn github_wh_test_handler(
-  headers: axum::http::HeaderMap,
-  Json(payload): Json<Value>,
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  body: Bytes,
 ) -> impl IntoResponse {
-  match process_event_and_get_token(&headers, &payload).await {
-    Ok(token) => {
-      println!("Successfully obtained installation token: {}", token);
-      token // Return the token as the response
+  // Reject anything that isn't signed with our webhook secret before touching the payload.
+  let secret = state.config.github.webhook_secret.expose().as_bytes();
+  let signature = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+  if let Err(e) = verify_signature(secret, &body, signature) {
+    println!("Rejected webhook delivery: {}", e);
+    let ack = WebhookAck::new(&headers, DeliveryStatus::Rejected).with_error(&e.to_string());
+    return (StatusCode::UNAUTHORIZED, Json(ack));
+  }
+
+  let payload: Value = match serde_json::from_slice(&body) {
+    Ok(payload) => payload,
+    Err(e) => {
+      println!("Rejected webhook delivery with invalid JSON: {}", e);
+      let error = format!("invalid JSON payload: {}", e);
+      let ack = WebhookAck::new(&headers, DeliveryStatus::Rejected).with_error(&error);
+      return (StatusCode::BAD_REQUEST, Json(ack));
+    }
+  };
+
+  let event = match process_github_payload(&headers, &payload) {
+    Ok(event) => event,
+    Err(e @ EventError::Unsupported { .. }) => {
+      println!("Ignoring webhook delivery: {}", e);
+      return (StatusCode::OK, Json(WebhookAck::new(&headers, DeliveryStatus::Ignored)));
+    }
+    Err(e) => {
+      println!("Rejected webhook delivery: {}", e);
+      let ack = WebhookAck::new(&headers, DeliveryStatus::Rejected).with_error(&e.to_string());
+      return (StatusCode::BAD_REQUEST, Json(ack));
+    }
+  };
+
+  if !event.wants_review() {
+    println!("Nothing to review for {} event, ignoring delivery.", event.name());
+    return (StatusCode::OK, Json(WebhookAck::new(&headers, DeliveryStatus::Ignored)));
+  }
+
+  if state.controls.holds(&event) {
+    println!("Reviews are paused or the commit was skipped, ignoring delivery.");
+    return (StatusCode::OK, Json(WebhookAck::new(&headers, DeliveryStatus::Ignored)));
+  }
+
+  let ack = WebhookAck::new(&headers, DeliveryStatus::Queued);
+  if let Some(duplicate) = state.ledger.admit(ack.delivery_id.as_deref(), &event) {
+    println!("Skipping duplicate delivery ({:?}).", duplicate);
+    return (StatusCode::OK, Json(WebhookAck::new(&headers, DeliveryStatus::Ignored)));
+  }
+
+  let id = match state.store.create(ack.delivery_id.clone(), &event) {
+    Ok(id) => id,
+    Err(e) => {
+      println!("Could not record review job: {}", e);
+      state.ledger.release(ack.delivery_id.as_deref(), &event);
+      let ack = WebhookAck::new(&headers, DeliveryStatus::Rejected).with_error(&e.to_string());
+      return (StatusCode::SERVICE_UNAVAILABLE, Json(ack));
+    }
+  };
+  // Before queueing, so the pending status can't land after the review's own status.
+  mark_review_queued(&event, &state.config, &state.clients).await;
+  let job_event = event.clone();
+  let job = ReviewJob { id: id.clone(), delivery_id: ack.delivery_id.clone(), event };
+  match state.queue.enqueue(job) {
+    Ok(()) => {
+      println!("Queued review job; {} job(s) waiting.", state.queue.depth());
+      (StatusCode::ACCEPTED, Json(ack))
     }
     Err(e) => {
-      println!("Error processing event: {}", e);
-      format!("Error: {}", e)
+      println!("Could not queue review job: {}", e);
+      mark_review_unqueued(&job_event, &state.config, &state.clients, &e.to_string()).await;
+      // GitHub will redeliver after a 503; don't also resume this copy on the next boot.
+      let _ = state.store.transition(&id, JobState::Failed { error: e.to_string() });
+      state.ledger.release(ack.delivery_id.as_deref(), &job_event);
+      let ack = WebhookAck::new(&headers, DeliveryStatus::Rejected).with_error(&e.to_string());
+      (StatusCode::SERVICE_UNAVAILABLE, Json(ack))
     }
   }
 }
@@ -0,0 +1,28 @@
+// artifacts.rs
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Optional per-job directory where each pipeline stage dumps its intermediate output.
+/// Nothing is written unless an artifact root is configured; the pipeline itself never reads these.
+#[derive(Debug, Clone, Default)]
+pub struct Artifacts {
+  dir: Option<PathBuf>,
+}
+
+impl Artifacts {
+  /// Artifacts for `job_id` under `root`, or a no-op sink when `root` is `None`.
+  pub fn for_job(root: Option<&Path>, job_id: &str) -> Artifacts {
+    let job_dir = job_id.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+    Artifacts { dir: root.map(|root| root.join(job_dir)) }
+  }
+
+  /// Write `contents` to `<job dir>/<name>`. Failures are logged, never fatal.
+  pub fn save(&self, name: &str, contents: &str) {
+    let Some(dir) = &self.dir else { return };
+    let path = dir.join(name);
+    match fs::create_dir_all(dir).and_then(|_| fs::write(&path, contents)) {
+      Ok(()) => println!("Artifact saved as: {}", path.display()),
+      Err(e) => println!("Failed to save artifact {}: {}", path.display(), e),
+    }
+  }
+}
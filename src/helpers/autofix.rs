@@ -0,0 +1,99 @@
+// autofix.rs
+use crate::helpers::findings::{ReviewFinding, ReviewReport};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// A file rewritten with the suggested fixes that applied to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedFile {
+  pub path: String,
+  pub contents: String,
+  /// The findings whose fixes were applied, top of the file first.
+  pub fixes: Vec<ReviewFinding>,
+}
+
+/// Findings with a suggested fix the model is at least `min_confidence` sure of, by file.
+pub fn fixes_by_file(
+  report: &ReviewReport,
+  min_confidence: f32,
+) -> BTreeMap<String, Vec<ReviewFinding>> {
+  let mut by_file: BTreeMap<String, Vec<ReviewFinding>> = BTreeMap::new();
+  for finding in &report.findings {
+    if finding.suggested_fix.is_some() && finding.confidence >= min_confidence {
+      by_file.entry(finding.file.clone()).or_default().push(finding.clone());
+    }
+  }
+  by_file
+}
+
+/// Replace each finding's lines in `contents` with its suggested fix. Findings come most
+/// important first; one overlapping lines an earlier one already changed is left out, and so
+/// is one citing lines the file doesn't have or changing nothing. `None` if nothing applied.
+pub fn apply_fixes(path: &str, contents: &str, findings: &[ReviewFinding]) -> Option<FixedFile> {
+  let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+  let mut applied: Vec<&ReviewFinding> = Vec::new();
+  for finding in findings {
+    let (start, end) = (finding.start_line as usize, finding.end_line as usize);
+    let Some(fix) = &finding.suggested_fix else { continue };
+    if start == 0 || start > end || end > lines.len() {
+      continue;
+    }
+    let overlaps =
+      applied.iter().any(|a| start <= a.end_line as usize && a.start_line as usize <= end);
+    if overlaps || lines[start - 1..end].concat() == with_ending(fix, lines[end - 1]) {
+      continue;
+    }
+    applied.push(finding);
+  }
+  if applied.is_empty() {
+    return None;
+  }
+
+  // Bottom up, so earlier line numbers still point at the right lines.
+  applied.sort_by_key(|finding| finding.start_line);
+  let mut fixed = lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+  for finding in applied.iter().rev() {
+    let (start, end) = (finding.start_line as usize, finding.end_line as usize);
+    let fix = finding.suggested_fix.as_deref().unwrap_or_default();
+    let replacement = with_ending(fix, lines[end - 1]);
+    fixed.splice(start - 1..end, [replacement]);
+  }
+  Some(FixedFile {
+    path: path.to_string(),
+    contents: fixed.concat(),
+    fixes: applied.into_iter().cloned().collect(),
+  })
+}
+
+/// The fix, ending the way the last line it replaces did.
+fn with_ending(fix: &str, replaced: &str) -> String {
+  let ending = &replaced[replaced.trim_end_matches(['\r', '\n']).len()..];
+  format!("{}{}", fix.trim_end_matches(['\r', '\n']), ending)
+}
+
+/// Title of the autofix pull request for pull request `pr_number`.
+pub fn autofix_title(pr_number: u64) -> String {
+  format!("Mechanic: suggested fixes for #{}", pr_number)
+}
+
+/// Body of the autofix pull request: where it came from, then every change it makes.
+pub fn autofix_body(pr_number: u64, commit_sha: &str, files: &[FixedFile]) -> String {
+  let count: usize = files.iter().map(|file| file.fixes.len()).sum();
+  let mut body = format!(
+    "Mechanic's suggested fixes from its review of {} on #{}: {} change{} in {} file{}. \
+     Merge this pull request to apply them to the branch.\n",
+    commit_sha,
+    pr_number,
+    count,
+    if count == 1 { "" } else { "s" },
+    files.len(),
+    if files.len() == 1 { "" } else { "s" }
+  );
+  for file in files {
+    let _ = write!(body, "\n### `{}`\n", file.path);
+    for finding in &file.fixes {
+      let _ = writeln!(body, "- `{}` {}", finding.location(), finding.headline());
+    }
+  }
+  body
+}
@@ -0,0 +1,140 @@
+// checks.rs
+use crate::config::ChecksConfig;
+use crate::helpers::findings::{ReviewFinding, ReviewReport, Severity};
+use serde::Serialize;
+
+/// The Checks API accepts at most this many annotations per request.
+pub const ANNOTATIONS_PER_REQUEST: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckConclusion {
+  Success,
+  Neutral,
+  Failure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationLevel {
+  Notice,
+  Warning,
+  Failure,
+}
+
+impl From<Severity> for AnnotationLevel {
+  fn from(severity: Severity) -> Self {
+    match severity {
+      Severity::Info => AnnotationLevel::Notice,
+      Severity::Warning => AnnotationLevel::Warning,
+      Severity::Error => AnnotationLevel::Failure,
+    }
+  }
+}
+
+/// One finding in the shape the Checks API expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckAnnotation {
+  pub path: String,
+  pub start_line: u32,
+  pub end_line: u32,
+  pub annotation_level: AnnotationLevel,
+  pub title: String,
+  pub message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub raw_details: Option<String>,
+}
+
+impl From<&ReviewFinding> for CheckAnnotation {
+  fn from(finding: &ReviewFinding) -> Self {
+    let start_line = finding.start_line.max(1);
+    CheckAnnotation {
+      path: finding.file.clone(),
+      start_line,
+      end_line: finding.end_line.max(start_line),
+      annotation_level: finding.severity.into(),
+      title: format!("{:?} ({})", finding.severity, finding.category.as_str()),
+      message: finding.message.clone(),
+      raw_details: finding.suggested_fix.clone(),
+    }
+  }
+}
+
+/// The `output` of a check run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckOutput {
+  pub title: String,
+  pub summary: String,
+  pub annotations: Vec<CheckAnnotation>,
+}
+
+/// A finished check run: its conclusion and what to show in the Checks tab.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+  pub conclusion: CheckConclusion,
+  pub output: CheckOutput,
+}
+
+impl CheckResult {
+  /// Failing result for a review that could not be completed, so the check neither hangs
+  /// nor passes a commit nobody reviewed.
+  pub fn errored(message: &str) -> CheckResult {
+    CheckResult {
+      conclusion: CheckConclusion::Failure,
+      output: CheckOutput {
+        title: "Review failed".to_string(),
+        summary: format!("Mechanic could not review this commit: {}", message),
+        annotations: Vec::new(),
+      },
+    }
+  }
+
+  /// Successful result for a commit with nothing to review.
+  pub fn no_changes() -> CheckResult {
+    CheckResult {
+      conclusion: CheckConclusion::Success,
+      output: CheckOutput {
+        title: "No changes to review".to_string(),
+        summary: "Mechanic found no reviewable changes in this commit.".to_string(),
+        annotations: Vec::new(),
+      },
+    }
+  }
+}
+
+/// Fail at `checks.fail_at`, go neutral at `checks.neutral_at`, succeed otherwise.
+pub fn conclusion_for(report: &ReviewReport, checks: &ChecksConfig) -> CheckConclusion {
+  match report.findings.iter().map(|f| f.severity).max() {
+    Some(worst) if worst >= checks.fail_at => CheckConclusion::Failure,
+    Some(worst) if worst >= checks.neutral_at => CheckConclusion::Neutral,
+    _ => CheckConclusion::Success,
+  }
+}
+
+/// Turn a report into a check result with one annotation per finding.
+pub fn build_check(report: &ReviewReport, checks: &ChecksConfig) -> CheckResult {
+  let count =
+    |severity: Severity| report.findings.iter().filter(|f| f.severity == severity).count();
+  let title = match report.findings.len() {
+    0 => "No findings".to_string(),
+    total => format!(
+      "{} finding(s): {} error(s), {} warning(s), {} info",
+      total,
+      count(Severity::Error),
+      count(Severity::Warning),
+      count(Severity::Info)
+    ),
+  };
+  let summary = match report.summary.trim() {
+    "" => "Mechanic reviewed this commit.".to_string(),
+    summary => summary.to_string(),
+  };
+  CheckResult {
+    conclusion: conclusion_for(report, checks),
+    output: CheckOutput {
+      title,
+      summary,
+      annotations: report.findings.iter().map(CheckAnnotation::from).collect(),
+    },
+  }
+}
@@ -0,0 +1,64 @@
+// commands.rs
+use crate::helpers::glob::glob_match;
+
+/// Comments addressed to Mechanic start a line with this.
+pub const COMMAND_PREFIX: &str = "/mechanic";
+
+/// Listed in the reply to a command Mechanic doesn't understand.
+pub const COMMAND_USAGE: &str = "\
+| Command | What it does |
+| --- | --- |
+| `/mechanic review` | Review the pull request again |
+| `/mechanic review <path>` | Review only files under `<path>` (or matching it, as a glob) |
+| `/mechanic skip` | Don't review the current head commit |
+| `/mechanic pause` | Stop reviewing new pushes to this pull request |
+| `/mechanic resume` | Review new pushes again |
+| `/mechanic config` | Show the configuration this pull request is reviewed with |";
+
+/// Something a collaborator asked Mechanic to do in a pull request comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+  /// Review the pull request's head now, optionally only the files under `path`.
+  Review {
+    path: Option<String>,
+  },
+  /// Leave the current head commit unreviewed.
+  Skip,
+  /// Stop reviewing pushes automatically until resumed.
+  Pause,
+  Resume,
+  /// Reply with the effective repository policy.
+  Config,
+}
+
+/// Find a command in a comment: the first line starting with `/mechanic`.
+/// `None` if the comment isn't addressed to Mechanic; `Err` describes what couldn't be understood.
+pub fn parse_command(body: &str) -> Option<Result<Command, String>> {
+  let line = body.lines().map(str::trim).find(|line| {
+    line.strip_prefix(COMMAND_PREFIX).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+  })?;
+  let mut words = line[COMMAND_PREFIX.len()..].split_whitespace();
+  let command = match (words.next(), words.next()) {
+    (None, _) => return Some(Err("no command given".to_string())),
+    (Some("review"), path) => Command::Review { path: path.map(String::from) },
+    (Some("skip"), None) => Command::Skip,
+    (Some("pause"), None) => Command::Pause,
+    (Some("resume"), None) => Command::Resume,
+    (Some("config"), None) => Command::Config,
+    (Some(name @ ("skip" | "pause" | "resume" | "config")), Some(_)) => {
+      return Some(Err(format!("`{}` takes no arguments", name)));
+    }
+    (Some(other), _) => return Some(Err(format!("unknown command `{}`", other))),
+  };
+  if words.next().is_some() {
+    return Some(Err("`review` takes at most one path".to_string()));
+  }
+  Some(Ok(command))
+}
+
+/// Whether `path` falls under the `scope` given to `/mechanic review <path>`: the file itself,
+/// a directory containing it, or a glob matching it.
+pub fn in_scope(scope: &str, path: &str) -> bool {
+  let scope = scope.trim_start_matches("./").trim_end_matches('/');
+  path == scope || path.starts_with(&format!("{}/", scope)) || glob_match(scope, path)
+}
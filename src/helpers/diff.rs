@@ -0,0 +1,274 @@
+// diff.rs
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+  Context,
+  Added,
+  Removed,
+}
+
+/// One line of a hunk with its position on each side of the diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+  pub kind: LineKind,
+  pub content: String,
+  /// Line number in the base version; `None` for added lines.
+  pub old_line: Option<u32>,
+  /// Line number in the head version; `None` for removed lines.
+  pub new_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+  pub old_start: u32,
+  pub old_lines: u32,
+  pub new_start: u32,
+  pub new_lines: u32,
+  /// Text after the closing `@@`, usually the enclosing function signature.
+  pub section: String,
+  pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+  /// Path in the head version (the old path for deleted files).
+  pub path: String,
+  pub old_path: Option<String>,
+  pub is_new: bool,
+  pub is_deleted: bool,
+  pub is_binary: bool,
+  pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+  /// Head-side line numbers this diff adds.
+  pub fn added_lines(&self) -> impl Iterator<Item = u32> + '_ {
+    self
+      .hunks
+      .iter()
+      .flat_map(|hunk| &hunk.lines)
+      .filter(|line| line.kind == LineKind::Added)
+      .filter_map(|line| line.new_line)
+  }
+}
+
+/// Parse a `git diff` style unified diff, as returned by GitHub's `application/vnd.github.diff`.
+pub fn parse_unified_diff(diff: &str) -> Vec<FileDiff> {
+  let mut files: Vec<FileDiff> = Vec::new();
+  let mut old_line = 0;
+  let mut new_line = 0;
+
+  for line in diff.lines() {
+    if let Some(rest) = line.strip_prefix("diff --git ") {
+      // `a/<path> b/<path>`; the `---`/`+++` lines below refine this when present.
+      let path = rest.split(" b/").last().unwrap_or(rest).to_string();
+      files.push(FileDiff {
+        path,
+        old_path: None,
+        is_new: false,
+        is_deleted: false,
+        is_binary: false,
+        hunks: Vec::new(),
+      });
+      continue;
+    }
+    let Some(file) = files.last_mut() else { continue };
+
+    if let Some(hunk) = file.hunks.last_mut()
+      && hunk_has_room(hunk, old_line, new_line)
+    {
+      let (kind, content) = match line.chars().next() {
+        Some('+') => (LineKind::Added, &line[1..]),
+        Some('-') => (LineKind::Removed, &line[1..]),
+        Some(' ') => (LineKind::Context, &line[1..]),
+        None => (LineKind::Context, ""),
+        // `\ No newline at end of file`
+        Some(_) => continue,
+      };
+      let (old, new) = match kind {
+        LineKind::Added => (None, Some(new_line)),
+        LineKind::Removed => (Some(old_line), None),
+        LineKind::Context => (Some(old_line), Some(new_line)),
+      };
+      if old.is_some() {
+        old_line += 1;
+      }
+      if new.is_some() {
+        new_line += 1;
+      }
+      hunk.lines.push(DiffLine {
+        kind,
+        content: content.to_string(),
+        old_line: old,
+        new_line: new,
+      });
+      continue;
+    }
+
+    if let Some(header) = line.strip_prefix("@@ ") {
+      if let Some(hunk) = parse_hunk_header(header) {
+        old_line = hunk.old_start;
+        new_line = hunk.new_start;
+        file.hunks.push(hunk);
+      }
+    } else if line.starts_with("new file mode") {
+      file.is_new = true;
+    } else if line.starts_with("deleted file mode") {
+      file.is_deleted = true;
+    } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+      file.is_binary = true;
+    } else if let Some(path) = line.strip_prefix("rename from ") {
+      file.old_path = Some(path.to_string());
+    } else if let Some(path) = line.strip_prefix("--- a/") {
+      if file.old_path.is_none() && path != file.path {
+        file.old_path = Some(path.to_string());
+      }
+    } else if let Some(path) = line.strip_prefix("+++ b/") {
+      file.path = path.to_string();
+    }
+  }
+
+  files
+}
+
+/// Whether the current hunk still expects lines, based on the counts in its header.
+fn hunk_has_room(hunk: &Hunk, old_line: u32, new_line: u32) -> bool {
+  old_line < hunk.old_start + hunk.old_lines || new_line < hunk.new_start + hunk.new_lines
+}
+
+/// Parse `-l,s +l,s @@ section` (the part after the leading `@@ `).
+fn parse_hunk_header(header: &str) -> Option<Hunk> {
+  let (ranges, section) = header.split_once(" @@").unwrap_or((header, ""));
+  let mut parts = ranges.split_whitespace();
+  let (old_start, old_lines) = parse_range(parts.next()?.strip_prefix('-')?)?;
+  let (new_start, new_lines) = parse_range(parts.next()?.strip_prefix('+')?)?;
+
+  Some(Hunk {
+    old_start,
+    old_lines,
+    new_start,
+    new_lines,
+    section: section.trim().to_string(),
+    lines: Vec::new(),
+  })
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+  match range.split_once(',') {
+    Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+    None => Some((range.parse().ok()?, 1)),
+  }
+}
+
+/// A diff rendered for a prompt, and which files made it in whole.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptDiff {
+  pub text: String,
+  /// Paths of the files with every hunk in `text`.
+  pub whole_files: Vec<String>,
+}
+
+/// Closes each file's code block in a prompt.
+const FILE_END: &str = "```\n\n";
+
+/// Render the diff for a prompt: every changed line, at most `context` unchanged lines around
+/// each change, head-side line numbers on every kept line, and no more than `max_chars` in total.
+/// A file that doesn't fit keeps the hunks that do; what was left out is listed at the end so
+/// the model knows.
+pub fn render_for_prompt(files: &[FileDiff], context: usize, max_chars: usize) -> PromptDiff {
+  let mut out = String::new();
+  let mut whole_files = Vec::new();
+  let mut cut_short = Vec::new();
+  let mut omitted = Vec::new();
+
+  for file in files {
+    if file.is_binary || file.is_deleted || file.hunks.is_empty() {
+      continue;
+    }
+
+    let mut rendered = file_header(file);
+    let mut room = max_chars.saturating_sub(out.len() + rendered.len() + FILE_END.len());
+    let mut kept = 0;
+    for hunk in file.hunks.iter().map(|hunk| render_hunk(hunk, context)) {
+      if hunk.len() <= room {
+        room -= hunk.len();
+        rendered.push_str(&hunk);
+        kept += 1;
+      }
+    }
+    match kept {
+      0 => {
+        omitted.push(file.path.as_str());
+        continue;
+      }
+      kept if kept == file.hunks.len() => whole_files.push(file.path.clone()),
+      _ => cut_short.push(file.path.as_str()),
+    }
+    rendered.push_str(FILE_END);
+    out.push_str(&rendered);
+  }
+
+  if !cut_short.is_empty() {
+    let _ = writeln!(
+      out,
+      "(Diff too large; some changes to these files were left out: {})",
+      cut_short.join(", ")
+    );
+  }
+  if !omitted.is_empty() {
+    let _ = writeln!(
+      out,
+      "(Diff too large; changes to these files were left out: {})",
+      omitted.join(", ")
+    );
+  }
+  PromptDiff { text: out, whole_files }
+}
+
+fn file_header(file: &FileDiff) -> String {
+  let mut out = String::new();
+  match &file.old_path {
+    Some(old_path) => {
+      let _ = writeln!(out, "📄 File: {} (renamed from {})", file.path, old_path);
+    }
+    None => {
+      let _ = writeln!(out, "📄 File: {}", file.path);
+    }
+  }
+  out.push_str("```diff\n");
+  out
+}
+
+fn render_hunk(hunk: &Hunk, context: usize) -> String {
+  let mut out = String::new();
+  let _ = writeln!(out, "@@ {} @@", hunk.section);
+  let changed: Vec<usize> = hunk
+    .lines
+    .iter()
+    .enumerate()
+    .filter(|(_, line)| line.kind != LineKind::Context)
+    .map(|(i, _)| i)
+    .collect();
+
+  let mut last_printed: Option<usize> = None;
+  for (i, line) in hunk.lines.iter().enumerate() {
+    let near_change = changed.iter().any(|&c| c.abs_diff(i) <= context);
+    if !near_change {
+      continue;
+    }
+    if last_printed.is_some_and(|last| i > last + 1) {
+      out.push_str("   ...\n");
+    }
+    let (marker, number) = match line.kind {
+      LineKind::Added => ('+', line.new_line),
+      // Removed lines have no head-side number; leave the column blank.
+      LineKind::Removed => ('-', None),
+      LineKind::Context => (' ', line.new_line),
+    };
+    let number = number.map(|n| n.to_string()).unwrap_or_default();
+    let _ = writeln!(out, "{:>5} {}{}", number, marker, line.content);
+    last_printed = Some(i);
+  }
+  out
+}
@@ -1,140 +1,1025 @@
-use crate::helpers::octo::{init_octocrab, reply_to_latest_pr, post_markdown_as_comment};
-use crate::services::groq::{
-  extract_new_functions, json_to_xml, save_xml_to_file, send_request_to_groq,
-}; // Import Groq functions
+use crate::config::Config;
+use crate::helpers::artifacts::Artifacts;
+use crate::helpers::autofix::{apply_fixes, autofix_body, autofix_title, fixes_by_file};
+use crate::helpers::checks::{CheckResult, build_check};
+use crate::helpers::commands::{COMMAND_USAGE, Command, in_scope, parse_command};
+use crate::helpers::diff::FileDiff;
+use crate::helpers::findings::ReviewReport;
+use crate::helpers::functions::{ChangedFunction, extract_functions, supports};
+use crate::helpers::octo::{
+  commit_files, complete_check_run, create_check_run, create_mechanic_branch,
+  find_autofix_pull_request, force_update_branch, get_file_contents, get_pull_request,
+  get_pull_request_diff, has_write_access, list_review_comments, list_review_threads,
+  open_pull_request, post_markdown_as_comment, react_to_comment, reply_to_review_comment,
+  reply_to_review_thread, resolve_review_thread, set_commit_status, submit_review,
+  update_pull_request, upsert_sticky_comment,
+};
+use crate::helpers::redact::redact;
+use crate::helpers::review::build_review;
+use crate::helpers::status::CommitStatus;
+use crate::helpers::sticky::StickyEntry;
+use crate::helpers::threads::{
+  DISMISSED_MARKER, ReviewThread, addressed_reply, drop_dismissed, fixed_threads,
+  parse_finding_marker,
+};
+use crate::services::controls::PullRequestControls;
+use crate::services::conversation::{ThreadMessage, request_thread_reply};
+use crate::services::dedup::CommitKey;
+use crate::services::llm::LlmProvider;
+use crate::services::policy::{PolicyCache, RepoPolicy};
+use crate::services::reviewer::{render_changes, render_functions, request_review};
+use crate::services::tokens::InstallationClients;
+use crate::state::AppState;
 use axum::http::HeaderMap;
-use serde_json::Value;
+use octocrab::Octocrab;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
 
+/// How long the webhook handler waits to mark a queued commit as pending.
+const QUEUED_STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What happened to a webhook delivery, reported back to GitHub in the acknowledgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+  Accepted,
+  Ignored,
+  Queued,
+  Rejected,
+}
+
+/// Why a delivery could not be turned into a [`GitHubEvent`].
 #[derive(Debug)]
-pub struct GitHubEvent {
-  pub owner: String,
-  pub repo: String,
-  pub pull_number: u64,
-  pub installation_id: u64,
-  pub commit_sha: String,
-}
-
-pub fn get_installation_id(payload: &Value) -> Option<u64> {
-  payload
-    .get("installation")
-    .and_then(|installation| installation.get("id"))
-    .and_then(|id| id.as_u64())
-}
-
-/// Process the webhook payload and headers to extract the GitHub event details and trigger Groq processing.
-pub async fn process_github_payload(headers: &HeaderMap, payload: &Value) -> GitHubEvent {
-  let mut owner = String::new();
-  let mut repo = String::new();
-  let mut pull_number = 0;
-  let mut installation_id = 0;
-  let mut commit_sha = String::new();
-
-  if let Some(event) = headers.get("X-GitHub-Event").and_then(|v| v.to_str().ok()) {
-    if event == "pull_request" {
-      if let Some(action) = payload.get("action").and_then(|v| v.as_str()) {
-        if action == "synchronize" {
-          owner = payload["repository"]["owner"]["login"].as_str().unwrap_or("").to_string();
-          repo = payload["repository"]["name"].as_str().unwrap_or("").to_string();
-          pull_number = payload["pull_request"]["number"].as_u64().unwrap_or(0);
-          commit_sha = payload["after"].as_str().unwrap_or("").to_string();
-          if let Some(id) = get_installation_id(payload) {
-            installation_id = id;
-          } else {
-            println!("Installation ID missing in webhook payload");
-          }
-          println!(
-            "Received pull_request.synchronize event for PR #{} in {}/{} with commit SHA {}.",
-            pull_number, owner, repo, commit_sha
-          );
+pub enum EventError {
+  /// The `X-GitHub-Event` header is absent or not valid UTF-8.
+  MissingEventHeader,
+  /// An event or action we don't act on. Not an error from GitHub's point of view.
+  Unsupported { event: String, action: Option<String> },
+  /// The body doesn't match the shape GitHub documents for this event.
+  Malformed { event: String, source: serde_json::Error },
+}
+
+impl fmt::Display for EventError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EventError::MissingEventHeader => write!(f, "X-GitHub-Event header missing"),
+      EventError::Unsupported { event, action: Some(action) } => {
+        write!(f, "unsupported event {}.{}", event, action)
+      }
+      EventError::Unsupported { event, action: None } => write!(f, "unsupported event {}", event),
+      EventError::Malformed { event, source } => {
+        write!(f, "malformed {} payload: {}", event, source)
+      }
+    }
+  }
+}
+
+impl Error for EventError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      EventError::Malformed { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+}
+
+/// A job that failed after its review went out. Running it again would post the review twice,
+/// so it isn't retried.
+#[derive(Debug)]
+pub struct AfterPosting(pub String);
+
+impl fmt::Display for AfterPosting {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl Error for AfterPosting {}
+
+/// Carried from one attempt at a job to the next, so a retry doesn't repeat what the attempts
+/// before it already put on GitHub.
+#[derive(Debug, Default)]
+pub struct Attempt {
+  /// Whether the job runs again if this attempt fails. Until then, failures are left off the
+  /// check run and commit status, which keep showing the review as in progress.
+  pub retries_left: bool,
+  /// The check run the first attempt started, reused by the ones after it.
+  check_run: Mutex<Option<u64>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+  pub login: String,
+  pub id: u64,
+  #[serde(rename = "type", default)]
+  pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Repository {
+  pub id: u64,
+  pub name: String,
+  pub full_name: String,
+  pub owner: Account,
+  #[serde(default)]
+  pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Installation {
+  pub id: u64,
+  #[serde(default)]
+  pub account: Option<Account>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitRef {
+  #[serde(rename = "ref")]
+  pub branch: String,
+  pub sha: String,
+  /// `None` once the fork it lives in is deleted.
+  #[serde(default)]
+  pub repo: Option<RepositoryName>,
+}
+
+/// Just enough of a repository to tell a fork from the repository itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryName {
+  pub full_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+  pub number: u64,
+  pub head: GitRef,
+  pub base: GitRef,
+  pub user: Account,
+  #[serde(default)]
+  pub draft: bool,
+  #[serde(default)]
+  pub html_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PullRequestAction {
+  Opened,
+  Synchronize,
+  Reopened,
+  ReadyForReview,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestEvent {
+  pub action: PullRequestAction,
+  pub pull_request: PullRequest,
+  pub repository: Repository,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushEvent {
+  #[serde(rename = "ref")]
+  pub git_ref: String,
+  pub before: String,
+  pub after: String,
+  pub repository: Repository,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+  pub number: u64,
+  /// Present only when the issue is a pull request.
+  #[serde(default)]
+  pub pull_request: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+  pub id: u64,
+  pub body: String,
+  pub user: Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueCommentEvent {
+  pub action: String,
+  pub issue: Issue,
+  pub comment: Comment,
+  pub repository: Repository,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+/// An inline comment on a pull request's diff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewComment {
+  pub id: u64,
+  pub body: String,
+  pub user: Account,
+  pub path: String,
+  #[serde(default)]
+  pub line: Option<u32>,
+  /// The hunk the thread is attached to; GitHub sends the same one for every reply.
+  #[serde(default)]
+  pub diff_hunk: String,
+  /// The thread's first comment, when this one is a reply.
+  #[serde(default)]
+  pub in_reply_to_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewCommentEvent {
+  pub action: String,
+  pub comment: ReviewComment,
+  pub pull_request: PullRequest,
+  pub repository: Repository,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestRef {
+  pub number: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSuite {
+  pub id: u64,
+  pub head_sha: String,
+  #[serde(default)]
+  pub head_branch: Option<String>,
+  #[serde(default)]
+  pub pull_requests: Vec<PullRequestRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSuiteEvent {
+  pub action: String,
+  pub check_suite: CheckSuite,
+  pub repository: Repository,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallationEvent {
+  pub action: String,
+  pub installation: Installation,
+  pub sender: Account,
+}
+
+/// Every webhook event the bot understands, keyed by the `X-GitHub-Event` header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", content = "payload", rename_all = "snake_case")]
+pub enum GitHubEvent {
+  PullRequest(PullRequestEvent),
+  Push(PushEvent),
+  IssueComment(IssueCommentEvent),
+  PullRequestReviewComment(ReviewCommentEvent),
+  CheckSuite(CheckSuiteEvent),
+  Installation(InstallationEvent),
+}
+
+impl GitHubEvent {
+  /// Build an event from the `X-GitHub-Event` header value and the decoded body.
+  pub fn parse(event: &str, payload: &Value) -> Result<Self, EventError> {
+    let action = payload.get("action").and_then(|v| v.as_str()).map(String::from);
+
+    match event {
+      "pull_request" => {
+        let supported = matches!(
+          action.as_deref(),
+          Some("opened" | "synchronize" | "reopened" | "ready_for_review")
+        );
+        if !supported {
+          return Err(EventError::Unsupported { event: event.to_string(), action });
         }
+        decode(event, payload).map(GitHubEvent::PullRequest)
       }
-    } else {
-      // Handle other events (e.g., push event)
-      owner = payload["repository"]["owner"]["login"].as_str().unwrap_or("").to_string();
-      commit_sha = payload["after"].as_str().unwrap_or("").to_string();
-      repo = payload["repository"]["name"].as_str().unwrap_or("").to_string();
-      println!("Webhook received successfully!");
-      if let Some(id) = get_installation_id(payload) {
-        installation_id = id;
-      } else {
-        println!("Installation ID missing in webhook payload");
+      "push" => decode(event, payload).map(GitHubEvent::Push),
+      "issue_comment" => decode(event, payload).map(GitHubEvent::IssueComment),
+      "pull_request_review_comment" => {
+        decode(event, payload).map(GitHubEvent::PullRequestReviewComment)
       }
+      "check_suite" => decode(event, payload).map(GitHubEvent::CheckSuite),
+      "installation" => decode(event, payload).map(GitHubEvent::Installation),
+      _ => Err(EventError::Unsupported { event: event.to_string(), action }),
     }
-  } else {
-    println!("X-GitHub-Event header missing.");
   }
 
-  println!(
-    "Values were successfully obtained. O: {}; R: {}; PR: #{}; IID: {}; SHA: {}",
-    owner, repo, pull_number, installation_id, commit_sha
-  );
+  /// The `X-GitHub-Event` name this event was delivered as.
+  pub fn name(&self) -> &'static str {
+    match self {
+      GitHubEvent::PullRequest(_) => "pull_request",
+      GitHubEvent::Push(_) => "push",
+      GitHubEvent::IssueComment(_) => "issue_comment",
+      GitHubEvent::PullRequestReviewComment(_) => "pull_request_review_comment",
+      GitHubEvent::CheckSuite(_) => "check_suite",
+      GitHubEvent::Installation(_) => "installation",
+    }
+  }
 
-  GitHubEvent { owner, repo, pull_number, installation_id, commit_sha }
+  pub fn installation_id(&self) -> u64 {
+    match self {
+      GitHubEvent::PullRequest(e) => e.installation.id,
+      GitHubEvent::Push(e) => e.installation.id,
+      GitHubEvent::IssueComment(e) => e.installation.id,
+      GitHubEvent::PullRequestReviewComment(e) => e.installation.id,
+      GitHubEvent::CheckSuite(e) => e.installation.id,
+      GitHubEvent::Installation(e) => e.installation.id,
+    }
+  }
+
+  pub fn repository(&self) -> Option<&Repository> {
+    match self {
+      GitHubEvent::PullRequest(e) => Some(&e.repository),
+      GitHubEvent::Push(e) => Some(&e.repository),
+      GitHubEvent::IssueComment(e) => Some(&e.repository),
+      GitHubEvent::PullRequestReviewComment(e) => Some(&e.repository),
+      GitHubEvent::CheckSuite(e) => Some(&e.repository),
+      GitHubEvent::Installation(_) => None,
+    }
+  }
+
+  /// The pull request this event is about, if any.
+  pub fn pull_number(&self) -> Option<u64> {
+    match self {
+      GitHubEvent::PullRequest(e) => Some(e.pull_request.number),
+      GitHubEvent::IssueComment(e) => e.issue.pull_request.as_ref().map(|_| e.issue.number),
+      GitHubEvent::PullRequestReviewComment(e) => Some(e.pull_request.number),
+      GitHubEvent::CheckSuite(e) => e.check_suite.pull_requests.first().map(|pr| pr.number),
+      GitHubEvent::Push(_) | GitHubEvent::Installation(_) => None,
+    }
+  }
+
+  /// Whether this event should be queued: a pull request to review, a command for Mechanic or
+  /// a reply in one of its review threads.
+  pub fn wants_review(&self) -> bool {
+    matches!(self, GitHubEvent::PullRequest(_))
+      || self.command().is_some()
+      || self.thread_reply().is_some()
+  }
+
+  /// A new reply by a person in a review thread. Whether the thread is Mechanic's is only known
+  /// once its first comment is fetched.
+  pub fn thread_reply(&self) -> Option<&ReviewCommentEvent> {
+    let GitHubEvent::PullRequestReviewComment(e) = self else { return None };
+    let from_bot = e.comment.user.kind.as_deref() == Some("Bot");
+    (e.action == "created" && e.comment.in_reply_to_id.is_some() && !from_bot).then_some(e)
+  }
+
+  /// The slash command in a new pull request comment, if it has one. Comments from bots are
+  /// never obeyed, so Mechanic can't be driven by its own replies.
+  pub fn command(&self) -> Option<Result<Command, String>> {
+    let GitHubEvent::IssueComment(e) = self else { return None };
+    let from_bot = e.comment.user.kind.as_deref() == Some("Bot");
+    if e.action != "created" || e.issue.pull_request.is_none() || from_bot {
+      return None;
+    }
+    parse_command(&e.comment.body)
+  }
+
+  /// The commit the event points at, if the payload carries one.
+  pub fn commit_sha(&self) -> Option<&str> {
+    match self {
+      GitHubEvent::PullRequest(e) => Some(&e.pull_request.head.sha),
+      GitHubEvent::Push(e) => Some(&e.after),
+      GitHubEvent::CheckSuite(e) => Some(&e.check_suite.head_sha),
+      // Replies are about a thread, not a commit, and must not claim one in the ledger.
+      GitHubEvent::IssueComment(_)
+      | GitHubEvent::PullRequestReviewComment(_)
+      | GitHubEvent::Installation(_) => None,
+    }
+  }
 }
 
-/// Process the event and swap the installation ID for an installation token.
-/// This function creates a JWT and then exchanges it for an installation token.
-/// It returns the token as a String.
-pub async fn process_event_and_get_token(
+fn decode<T: DeserializeOwned>(event: &str, payload: &Value) -> Result<T, EventError> {
+  T::deserialize(payload)
+    .map_err(|source| EventError::Malformed { event: event.to_string(), source })
+}
+
+/// Read the `X-GitHub-Event` header and decode the payload into a typed event.
+pub fn process_github_payload(
   headers: &HeaderMap,
   payload: &Value,
-) -> Result<String, Box<dyn Error>> {
-  let event = process_github_payload(headers, payload).await;
+) -> Result<GitHubEvent, EventError> {
+  let event = headers
+    .get("X-GitHub-Event")
+    .and_then(|v| v.to_str().ok())
+    .ok_or(EventError::MissingEventHeader)?;
+
+  let event = GitHubEvent::parse(event, payload)?;
+  if let Some(repository) = event.repository() {
+    println!(
+      "Received {} event for {} (PR: {:?}; IID: {}; SHA: {:?}).",
+      event.name(),
+      repository.full_name,
+      event.pull_number(),
+      event.installation_id(),
+      event.commit_sha()
+    );
+  }
+  Ok(event)
+}
+
+/// Everything a job works with besides its event and installation client.
+struct JobContext<'a> {
+  config: &'a Config,
+  policies: &'a PolicyCache,
+  controls: &'a PullRequestControls,
+  llm: &'a dyn LlmProvider,
+  artifacts: &'a Artifacts,
+  attempt: &'a Attempt,
+  /// The login the app comments as, e.g. `mechanic[bot]`.
+  bot_login: &'a str,
+}
+
+/// Process the event with a client for its installation and run the review.
+/// Installation tokens come from the shared `state.clients` cache and never leave it;
+/// callers only learn what happened to the delivery.
+/// Stages hand their output to each other in memory; `artifacts` only keeps copies for debugging.
+/// Failures wrapped in [`AfterPosting`] must not be retried.
+pub async fn process_event_and_get_token(
+  event: &GitHubEvent,
+  state: &AppState,
+  artifacts: &Artifacts,
+  attempt: &Attempt,
+) -> Result<DeliveryStatus, Box<dyn Error>> {
+  let (config, clients, controls) = (state.config.as_ref(), &state.clients, &state.controls);
+  let (policies, llm) = (&state.policies, state.llm.as_ref());
+  let bot_login = &clients.bot_login().await?;
+  let context = JobContext { config, policies, controls, llm, artifacts, attempt, bot_login };
+  if let GitHubEvent::IssueComment(comment_event) = event
+    && let Some(command) = event.command()
+  {
+    let octo = clients.client(event.installation_id()).await?;
+    return run_command(&octo, comment_event, command, &context).await;
+  }
+  if let Some(reply_event) = event.thread_reply() {
+    let octo = clients.client(event.installation_id()).await?;
+    return answer_thread_reply(&octo, reply_event, &context).await;
+  }
 
-  if event.installation_id == 0 {
-    return Err("No installation ID found in payload".into());
+  // Only pull request events carry everything a review needs.
+  let GitHubEvent::PullRequest(pr_event) = event else {
+    println!("Nothing to review for {} event, ignoring delivery.", event.name());
+    return Ok(DeliveryStatus::Ignored);
+  };
+  let owner = &pr_event.repository.owner.login;
+  let repo = &pr_event.repository.name;
+
+  // Reuse the installation's client while its token is still fresh.
+  let octo = clients.client(event.installation_id()).await?;
+
+  if controls.holds(event) {
+    println!("Reviews are paused or the commit was skipped, ignoring delivery.");
+    // `/mechanic skip` settles its own commit's status.
+    let full_name = &pr_event.repository.full_name;
+    if controls.is_paused(full_name, pr_event.pull_request.number) {
+      settle_unreviewed(&octo, pr_event, config, "reviews are paused").await;
+    }
+    return Ok(DeliveryStatus::Ignored);
   }
 
-  // Create the JWT using your helper function
-  let jwt = crate::helpers::jwt::create_jwt()?;
+  // The base commit's policy, so a pull request can't change how it is itself reviewed.
+  let base_sha = &pr_event.pull_request.base.sha;
+  let policy = policies.load(&octo, owner, repo, base_sha, config).await;
+  if !policy.reviews(pr_event.action) {
+    println!("{}/{} doesn't review {:?} events, ignoring delivery.", owner, repo, pr_event.action);
+    settle_unreviewed(&octo, pr_event, config, "turned off by the repository's policy").await;
+    return Ok(DeliveryStatus::Ignored);
+  }
+  review_and_report(&octo, pr_event, &policy, None, &context).await?;
+  Ok(DeliveryStatus::Accepted)
+}
 
-  // Exchange the JWT for an installation token using your helper function
-  let token =
-    crate::helpers::jwt::exchange_jwt_for_installation_token(&jwt, event.installation_id).await?;
+/// Settle the pending status left when the review was queued, once it turns out not to run.
+/// Otherwise the commit would wait on Mechanic forever.
+async fn settle_unreviewed(
+  octo: &Octocrab,
+  pr_event: &PullRequestEvent,
+  config: &Config,
+  reason: &str,
+) {
+  if !config.statuses.enabled {
+    return;
+  }
+  let (owner, repo) = (&pr_event.repository.owner.login, &pr_event.repository.name);
+  let url = pr_event.pull_request.html_url.clone();
+  let status = CommitStatus::not_reviewed(&config.statuses.context, reason, url);
+  let set = set_commit_status(octo, owner, repo, &pr_event.pull_request.head.sha, &status).await;
+  if let Err(e) = set.map_err(|e| e.to_string()) {
+    eprintln!("Failed to set commit status: {}", e);
+  }
+}
 
-  // Initialize Octocrab with the installation token.
-  let octo = init_octocrab(token.to_string());
+/// Review the pull request's head and report the result on the check run and commit status,
+/// limited to the files under `scope` when a command asked for that.
+async fn review_and_report(
+  octo: &Octocrab,
+  pr_event: &PullRequestEvent,
+  policy: &RepoPolicy,
+  scope: Option<&str>,
+  context: &JobContext<'_>,
+) -> Result<(), Box<dyn Error>> {
+  let config = context.config;
+  let owner = &pr_event.repository.owner.login;
+  let repo = &pr_event.repository.name;
+  let commit_sha = &pr_event.pull_request.head.sha;
 
-  // ✅ **Run the Groq Pipeline for This Commit**
-  if !event.commit_sha.is_empty() {
-    println!("Extracting new functions from commit: {}", event.commit_sha);
+  // Without the Checks permission this fails; the review still goes out as comments.
+  let mut check_run = *context.attempt.check_run.lock().unwrap();
+  if config.checks.enabled && check_run.is_none() {
+    let name = &config.checks.name;
+    match create_check_run(octo, owner, repo, name, commit_sha).await.map_err(|e| e.to_string()) {
+      Ok(id) => check_run = Some(id),
+      Err(e) => eprintln!("Failed to start check run: {}", e),
+    }
+    *context.attempt.check_run.lock().unwrap() = check_run;
+  }
 
-    let _ = extract_new_functions(&event.owner, &event.repo, &event.commit_sha, &octo).await;
-    let xml_output = json_to_xml().await;
-    save_xml_to_file(&xml_output);
+  // Errors end up on GitHub below, so they are redacted first.
+  let (reviewed, after_posting) =
+    match review_pull_request(octo, pr_event, policy, scope, context).await {
+      Ok(posted) => (Ok(posted), false),
+      Err(e) => (Err(redact(&e.to_string())), e.is::<AfterPosting>()),
+    };
+  // A retry may still get the review out; until then the check run stays in progress.
+  if let Err(e) = &reviewed
+    && context.attempt.retries_left
+    && !after_posting
+  {
+    return Err(e.clone().into());
+  }
 
-    println!("Sending extracted functions to Groq AI...");
-    let groq_response = send_request_to_groq().await;
+  if let Some(id) = check_run {
+    let mut result = match &reviewed {
+      Ok(Some(posted)) => build_check(&posted.report, &config.checks),
+      Ok(None) => CheckResult::no_changes(),
+      Err(e) => CheckResult::errored(e),
+    };
+    if let Some(problem) = &policy.invalid_file {
+      result.output.summary = format!("⚠️ {}\n\n{}", problem, result.output.summary);
+    }
+    let completed = complete_check_run(octo, owner, repo, id, &result).await;
+    if let Err(e) = completed.map_err(|e| e.to_string()) {
+      eprintln!("Failed to complete check run {}: {}", id, e);
+    }
+  }
 
-    match groq_response {
-        Ok(response) => {
-            println!("Groq AI Analysis Result:\n{}", response);
-            // Here, assume your response is a markdown payload.
-            if let Err(e) = post_markdown_as_comment(&octo, &event.owner, &event.repo, event.pull_number, &response).await {
-                eprintln!("Failed to post markdown comment: {:?}", e);
-            }
-        }
-        Err(e) => eprintln!("❌ Groq AI Request Failed: {:?}", e),
+  if config.statuses.enabled {
+    let (context, pr_url) = (&config.statuses.context, pr_event.pull_request.html_url.clone());
+    let status = match &reviewed {
+      Ok(Some(posted)) => {
+        let url = posted.url.clone().or(pr_url);
+        CommitStatus::for_report(context, &posted.report, config.statuses.fail_at, url)
+      }
+      Ok(None) => CommitStatus::no_changes(context, pr_url),
+      Err(e) => CommitStatus::errored(context, e, pr_url),
+    };
+    let set = set_commit_status(octo, owner, repo, commit_sha, &status).await;
+    if let Err(e) = set.map_err(|e| e.to_string()) {
+      eprintln!("Failed to set commit status: {}", e);
     }
-} else {
-    println!("⚠️ No commit SHA found, skipping Groq processing.");
+  }
+  match reviewed {
+    Err(e) if after_posting => Err(Box::new(AfterPosting(e))),
+    reviewed => Ok(reviewed.map(|_| ())?),
+  }
 }
 
+/// Carry out a slash command from a pull request comment. Only collaborators with write access
+/// are obeyed; the comment gets a reaction either way so its author knows it was seen.
+async fn run_command(
+  octo: &Octocrab,
+  comment_event: &IssueCommentEvent,
+  command: Result<Command, String>,
+  context: &JobContext<'_>,
+) -> Result<DeliveryStatus, Box<dyn Error>> {
+  let config = context.config;
+  let owner = &comment_event.repository.owner.login;
+  let repo = &comment_event.repository.name;
+  let full_name = &comment_event.repository.full_name;
+  let pr_number = comment_event.issue.number;
+  let comment_id = comment_event.comment.id;
+  let login = &comment_event.comment.user.login;
+
+  if !has_write_access(octo, owner, repo, login).await? {
+    println!("Ignoring command from {} on {}#{}: no write access.", login, full_name, pr_number);
+    react_to_comment(octo, owner, repo, comment_id, "-1").await?;
+    return Ok(DeliveryStatus::Rejected);
+  }
+  let command = match command {
+    Ok(command) => command,
+    Err(e) => {
+      react_to_comment(octo, owner, repo, comment_id, "confused").await?;
+      let reply = format!("Mechanic didn't understand that: {}.\n\n{}", e, COMMAND_USAGE);
+      post_markdown_as_comment(octo, owner, repo, pr_number, &reply).await?;
+      return Ok(DeliveryStatus::Rejected);
+    }
+  };
+
+  let pull_request = get_pull_request(octo, owner, repo, pr_number).await?;
+  let base_sha = &pull_request.base.sha;
+  let policy = context.policies.load(octo, owner, repo, base_sha, config).await;
+  if !policy.enabled {
+    println!("Mechanic is disabled in {}, ignoring {:?}.", full_name, command);
+    return Ok(DeliveryStatus::Ignored);
+  }
+  println!("Running {:?} for {} on {}#{}.", command, login, full_name, pr_number);
+  react_to_comment(octo, owner, repo, comment_id, "+1").await?;
 
-  // Verify authentication by fetching the current user.
-  match octo.current().user().await {
-    Ok(user) => {
-      println!("Authentication verified! Authenticated as: {:?}", user);
+  match command {
+    Command::Review { path } => {
+      let pr_event = PullRequestEvent {
+        action: PullRequestAction::Synchronize,
+        pull_request,
+        repository: comment_event.repository.clone(),
+        installation: comment_event.installation.clone(),
+        sender: comment_event.sender.clone(),
+      };
+      review_and_report(octo, &pr_event, &policy, path.as_deref(), context).await?;
     }
-    Err(err) => {
-      println!("Failed to verify authentication: {:?}", err);
+    Command::Skip => {
+      let head_sha = pull_request.head.sha;
+      let commit =
+        CommitKey { repo: full_name.clone(), pull_number: pr_number, sha: head_sha.clone() };
+      context.controls.skip(commit);
+      // Settle the pending status left when the commit was queued.
+      if config.statuses.enabled {
+        let context = &config.statuses.context;
+        let status = CommitStatus::skipped(context, login, pull_request.html_url);
+        set_commit_status(octo, owner, repo, &head_sha, &status).await?;
+      }
+    }
+    Command::Pause => context.controls.pause(full_name, pr_number)?,
+    Command::Resume => context.controls.resume(full_name, pr_number)?,
+    Command::Config => {
+      let mut reply = format!(
+        "Mechanic reviews this pull request with the configuration at `{}`:\n\n```toml\n{}```",
+        base_sha,
+        policy.to_toml()
+      );
+      if context.controls.is_paused(full_name, pr_number) {
+        reply.push_str("\n\nNew pushes aren't reviewed until `/mechanic resume`.");
+      }
+      post_markdown_as_comment(octo, owner, repo, pr_number, &reply).await?;
     }
   }
+  Ok(DeliveryStatus::Accepted)
+}
+
+/// Mark the pull request's head commit as pending review, when commit statuses are enabled.
+/// Called as the job is queued; gives up quickly so the delivery is still answered in time.
+pub async fn mark_review_queued(
+  event: &GitHubEvent,
+  config: &Config,
+  clients: &InstallationClients,
+) {
+  set_queue_status(event, config, clients, CommitStatus::queued).await;
+}
 
-  // Now automate replying to the latest PR commit with any comment. If it's a change, then reply with a message. Else, reply with mechanic has no comment.
-  // test_list_pull_requests(&octo, &event.owner, &event.repo).await;
-  // reply_to_latest_pr(&octo, &event.owner, &event.repo).await;
+/// Settle the pending status [`mark_review_queued`] left when the job then couldn't be queued,
+/// so the commit isn't left waiting on a review that never runs.
+pub async fn mark_review_unqueued(
+  event: &GitHubEvent,
+  config: &Config,
+  clients: &InstallationClients,
+  reason: &str,
+) {
+  set_queue_status(event, config, clients, |context, url| {
+    CommitStatus::errored(context, reason, url)
+  })
+  .await;
+}
+
+async fn set_queue_status(
+  event: &GitHubEvent,
+  config: &Config,
+  clients: &InstallationClients,
+  status: impl FnOnce(&str, Option<String>) -> CommitStatus,
+) {
+  let GitHubEvent::PullRequest(pr_event) = event else { return };
+  if !config.statuses.enabled {
+    return;
+  }
+  let owner = &pr_event.repository.owner.login;
+  let repo = &pr_event.repository.name;
+  let commit_sha = &pr_event.pull_request.head.sha;
+  let status = status(&config.statuses.context, pr_event.pull_request.html_url.clone());
 
-  Ok(token)
+  let set = async {
+    let octo = clients.client(event.installation_id()).await?;
+    set_commit_status(&octo, owner, repo, commit_sha, &status).await
+  };
+  match tokio::time::timeout(QUEUED_STATUS_TIMEOUT, set).await {
+    Ok(Ok(())) => {}
+    Ok(Err(e)) => eprintln!("Failed to set commit status: {}", e),
+    Err(_) => eprintln!("Timed out setting commit status on {}", commit_sha),
+  }
+}
+
+/// Answer a developer's reply to one of Mechanic's inline comments in the same thread. When the
+/// model agrees the finding doesn't apply, the thread is resolved and the finding dismissed.
+async fn answer_thread_reply(
+  octo: &Octocrab,
+  reply_event: &ReviewCommentEvent,
+  context: &JobContext<'_>,
+) -> Result<DeliveryStatus, Box<dyn Error>> {
+  let owner = &reply_event.repository.owner.login;
+  let repo = &reply_event.repository.name;
+  let pr_number = reply_event.pull_request.number;
+  let Some(root_id) = reply_event.comment.in_reply_to_id else {
+    return Ok(DeliveryStatus::Ignored);
+  };
+
+  let comments = list_review_comments(octo, owner, repo, pr_number).await?;
+  let mut thread: Vec<&ReviewComment> = comments
+    .iter()
+    .filter(|comment| comment.id == root_id || comment.in_reply_to_id == Some(root_id))
+    .collect();
+  thread.sort_by_key(|comment| comment.id);
+  let bot_login = context.bot_login;
+  let started_by_mechanic = thread.first().is_some_and(|root| {
+    root.id == root_id && root.user.login == bot_login && parse_finding_marker(&root.body).is_some()
+  });
+  if !started_by_mechanic {
+    println!("Reply on {}#{} isn't in one of Mechanic's threads, ignoring.", repo, pr_number);
+    return Ok(DeliveryStatus::Ignored);
+  }
+  // The event can arrive before the reply shows up in the list.
+  if !thread.iter().any(|comment| comment.id == reply_event.comment.id) {
+    thread.push(&reply_event.comment);
+  }
+
+  let base_sha = &reply_event.pull_request.base.sha;
+  let policy = context.policies.load(octo, owner, repo, base_sha, context.config).await;
+  if !policy.enabled {
+    return Ok(DeliveryStatus::Ignored);
+  }
+  let messages: Vec<ThreadMessage> = thread
+    .iter()
+    .map(|comment| ThreadMessage {
+      author: comment.user.login.clone(),
+      from_mechanic: comment.user.login == bot_login,
+      body: comment.body.clone(),
+    })
+    .collect();
+  let (config, llm, artifacts) = (context.config, context.llm, context.artifacts);
+  let (path, diff_hunk) = (&reply_event.comment.path, &reply_event.comment.diff_hunk);
+  println!("Answering {} in the review thread on {}.", reply_event.comment.user.login, path);
+  let mut answer =
+    request_thread_reply(path, diff_hunk, &messages, &config.llm, &policy, llm, artifacts).await?;
+
+  let mut body = answer.reply;
+  // Like commands, dismissing a finding is up to collaborators who could push the fix.
+  let replier = &reply_event.comment.user.login;
+  if answer.dismiss && !has_write_access(octo, owner, repo, replier).await? {
+    println!("Not dismissing the finding on {}: {} has no write access.", path, replier);
+    answer.dismiss = false;
+    body.push_str("\n\n_Only collaborators with write access can dismiss Mechanic's findings._");
+  }
+  if answer.dismiss {
+    body.push_str("\n\n");
+    body.push_str(DISMISSED_MARKER);
+  }
+  reply_to_review_comment(octo, owner, repo, pr_number, root_id, &body).await?;
+
+  if answer.dismiss {
+    // Resolving is a courtesy; the marker alone keeps the finding from coming back.
+    let resolved = async {
+      let threads = list_review_threads(octo, owner, repo, pr_number).await?;
+      match threads.iter().find(|thread| thread.root_comment_id == Some(root_id)) {
+        Some(thread) => resolve_review_thread(octo, &thread.id).await,
+        None => Ok(()),
+      }
+    };
+    match resolved.await.map_err(|e| e.to_string()) {
+      Ok(()) => println!("Dismissed the finding on {} at the developer's word.", path),
+      Err(e) => eprintln!("Failed to resolve dismissed review thread: {}", e),
+    }
+  }
+  Ok(DeliveryStatus::Accepted)
+}
+
+/// A review left on the pull request, and a link to it.
+struct PostedReview {
+  report: ReviewReport,
+  url: Option<String>,
+}
+
+/// Review the pull request's diff and post the result. `None` when the diff had nothing to
+/// review.
+async fn review_pull_request(
+  octo: &Octocrab,
+  pr_event: &PullRequestEvent,
+  policy: &RepoPolicy,
+  scope: Option<&str>,
+  context: &JobContext<'_>,
+) -> Result<Option<PostedReview>, Box<dyn Error>> {
+  let (config, llm, artifacts) = (context.config, context.llm, context.artifacts);
+  let owner = &pr_event.repository.owner.login;
+  let repo = &pr_event.repository.name;
+  let pull_number = pr_event.pull_request.number;
+  let commit_sha = &pr_event.pull_request.head.sha;
+
+  // ✅ **Run the Review Pipeline for This Pull Request's Diff**
+  let mut files = get_pull_request_diff(octo, owner, repo, pull_number).await?;
+  files.retain(|file| policy.reviews_path(&file.path));
+  if let Some(scope) = scope {
+    files.retain(|file| in_scope(scope, &file.path));
+  }
+  let note = scope.map(|s| format!("_Only `{}` was reviewed, as asked._\n\n", s));
+  let mut note = note.unwrap_or_default();
+  if let Some(problem) = &policy.invalid_file {
+    note.push_str(&format!("⚠️ {}\n\n", problem));
+  }
+  let diff = render_changes(&files, &config.review, &config.llm, llm);
+  let changes = diff.text;
+  artifacts.save("changes.diff", &changes);
+
+  if changes.is_empty() {
+    println!("No new changes. Exiting.");
+    let markdown = format!("{}Mechanic doesn't have any suggestions to do. Great work!", note);
+    let entry = StickyEntry { commit_sha: commit_sha.clone(), markdown };
+    upsert_sticky_comment(octo, owner, repo, pull_number, context.bot_login, entry).await?;
+    return Ok(None);
+  }
+
+  let functions = changed_functions(octo, owner, repo, commit_sha, &files).await;
+  let json = serde_json::to_string_pretty(&json!({ "functions": functions }));
+  artifacts.save("functions.json", &json.unwrap_or_default());
+  let functions = render_functions(&functions, &changes, &config.review, &config.llm, llm);
+
+  println!("Requesting review of commit {} from {}", commit_sha, llm.name());
+  let mut report =
+    request_review(commit_sha, &changes, &functions, &config.llm, policy, llm, artifacts).await?;
+
+  // Threads from earlier pushes: findings dismissed in them aren't raised again.
+  let threads = list_review_threads(octo, owner, repo, pull_number).await.unwrap_or_else(|e| {
+    eprintln!("Failed to list review threads: {}", e);
+    Vec::new()
+  });
+  drop_dismissed(&mut report, &threads);
+
+  let review =
+    build_review(commit_sha, &report, &files, policy.request_changes_at, policy.max_comments);
+  // A review can be refused (e.g. the head moved on); the sticky comment still gets it out.
+  let submitted = submit_review(octo, owner, repo, pull_number, &review).await;
+  let review_url = submitted.unwrap_or_else(|e| {
+    eprintln!("Failed to submit review, keeping only the sticky comment: {}", e);
+    None
+  });
+
+  // Only files the model saw whole: a thread on a part left out isn't known to be fixed.
+  let reviewed: Vec<&str> = diff.whole_files.iter().map(String::as_str).collect();
+  let resolved = resolve_fixed_threads(octo, pr_event, &threads, &report, &reviewed).await;
+  if let Err(e) = resolved.map_err(|e| e.to_string()) {
+    eprintln!("Failed to resolve fixed review threads: {}", e);
+  }
+
+  let mut autofix_note = String::new();
+  if policy.autofix {
+    let min_confidence = config.autofix.min_confidence;
+    let opened =
+      open_autofix_pull_request(octo, pr_event, &report, min_confidence, context.bot_login)
+        .await
+        .map_err(|e| e.to_string());
+    match opened {
+      Ok(Some(url)) => {
+        autofix_note = format!("\n_Suggested fixes are ready to merge in {}._\n", url)
+      }
+      Ok(None) => println!("No suggested fixes to apply to {}.", commit_sha),
+      Err(e) => eprintln!("Failed to open autofix pull request: {}", e),
+    }
+  }
+
+  // One comment per pull request, edited on every push, instead of a new one each time.
+  let markdown = format!("{}{}{}", note, report.to_markdown(), autofix_note);
+  let entry = StickyEntry { commit_sha: commit_sha.clone(), markdown };
+  let comment_url = upsert_sticky_comment(octo, owner, repo, pull_number, context.bot_login, entry)
+    .await
+    .map_err(|e| AfterPosting(e.to_string()))?;
+  Ok(Some(PostedReview { report, url: review_url.or(Some(comment_url)) }))
+}
+
+/// The functions and impls the diff changes, read from each changed file at `commit_sha`.
+/// A file that can't be fetched only loses its functions.
+async fn changed_functions(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  commit_sha: &str,
+  files: &[FileDiff],
+) -> Vec<ChangedFunction> {
+  let mut functions = Vec::new();
+  for file in
+    files.iter().filter(|file| !file.is_deleted && !file.is_binary && supports(&file.path))
+  {
+    let contents = get_file_contents(octo, owner, repo, &file.path, commit_sha).await;
+    match contents.map_err(|e| e.to_string()) {
+      Ok(Some(contents)) => functions.extend(extract_functions(file, &contents, commit_sha)),
+      Ok(None) => {}
+      Err(e) => eprintln!("Failed to fetch {} to extract its functions: {}", file.path, e),
+    }
+  }
+  functions
+}
+
+/// Commit the suggested fixes that apply cleanly to the reviewed commit and offer them as a
+/// pull request into the author's branch. An autofix pull request left open by an earlier push
+/// has its `mechanic-<pr>` branch moved to the new fixes, or is closed when none apply any more;
+/// otherwise a new branch and pull request are opened. Returns its URL, or `None` if there was
+/// nothing to fix or nowhere Mechanic may push.
+async fn open_autofix_pull_request(
+  octo: &Octocrab,
+  pr_event: &PullRequestEvent,
+  report: &ReviewReport,
+  min_confidence: f32,
+  bot_login: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+  let owner = &pr_event.repository.owner.login;
+  let repo = &pr_event.repository.name;
+  let pull = &pr_event.pull_request;
+  // Mechanic's own pull requests would otherwise get autofix pull requests of their own, and a
+  // fork's branch can't be the base of a pull request here.
+  if pull.user.kind.as_deref() == Some("Bot") {
+    return Ok(None);
+  }
+  let head_repo = pull.head.repo.as_ref().map(|r| r.full_name.as_str());
+  if head_repo != Some(pr_event.repository.full_name.as_str()) {
+    println!("Head of #{} isn't in {}, not opening autofix pull request.", pull.number, repo);
+    return Ok(None);
+  }
+
+  let mut fixed = Vec::new();
+  for (path, findings) in fixes_by_file(report, min_confidence) {
+    let Some(contents) = get_file_contents(octo, owner, repo, &path, &pull.head.sha).await? else {
+      continue;
+    };
+    fixed.extend(apply_fixes(&path, &contents, &findings));
+  }
+  let base = &pull.head.branch;
+  let open = find_autofix_pull_request(octo, owner, repo, pull.number, base, bot_login).await?;
+  if fixed.is_empty() {
+    // The earlier fixes were made against a commit that is gone now.
+    if let Some(open) = open {
+      update_pull_request(octo, owner, repo, open.number, &json!({ "state": "closed" })).await?;
+      println!("Closed autofix pull request {}: nothing left to fix.", open.url);
+    }
+    return Ok(None);
+  }
+
+  let message = format!("Apply Mechanic's suggested fixes for #{}", pull.number);
+  let sha = commit_files(octo, owner, repo, &pull.head.sha, &message, &fixed).await?;
+  let title = autofix_title(pull.number);
+  let body = autofix_body(pull.number, &pull.head.sha, &fixed);
+  let url = match open {
+    Some(open) => {
+      force_update_branch(octo, owner, repo, &open.branch, &sha).await?;
+      let fields = json!({ "title": title, "body": body });
+      update_pull_request(octo, owner, repo, open.number, &fields).await?;
+      println!("Updated autofix pull request {} with fixes for {}.", open.url, pull.head.sha);
+      open.url
+    }
+    None => {
+      let branch = create_mechanic_branch(octo, owner, repo, pull.number, &sha).await?;
+      open_pull_request(octo, owner, repo, &branch, base, &title, &body).await?
+    }
+  };
+  Ok(Some(url))
+}
+
+/// Resolve Mechanic's threads from earlier pushes that this push fixed, among those on the
+/// `reviewed` files. Where the token may not resolve threads, reply once to say the finding
+/// looks addressed instead.
+async fn resolve_fixed_threads(
+  octo: &Octocrab,
+  pr_event: &PullRequestEvent,
+  threads: &[ReviewThread],
+  report: &ReviewReport,
+  reviewed: &[&str],
+) -> Result<(), Box<dyn Error>> {
+  let commit_sha = &pr_event.pull_request.head.sha;
+
+  for thread in fixed_threads(threads, report, reviewed) {
+    let resolved = resolve_review_thread(octo, &thread.id).await.map_err(|e| e.to_string());
+    match resolved {
+      Ok(()) => println!("Resolved review thread on {} (fixed in {}).", thread.path, commit_sha),
+      Err(e) => {
+        eprintln!("Could not resolve review thread {}, replying instead: {}", thread.id, e);
+        reply_to_review_thread(octo, &thread.id, &addressed_reply(commit_sha)).await?;
+      }
+    }
+  }
+  Ok(())
 }
@@ -0,0 +1,260 @@
+// findings.rs
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Info,
+  Warning,
+  Error,
+}
+
+impl Severity {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Severity::Info => "info",
+      Severity::Warning => "warning",
+      Severity::Error => "error",
+    }
+  }
+
+  /// Read a severity, accepting the synonyms models tend to use instead.
+  fn parse(value: &str) -> Option<Severity> {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "info" | "note" | "nit" | "low" | "suggestion" => Some(Severity::Info),
+      "warning" | "warn" | "medium" | "minor" => Some(Severity::Warning),
+      "error" | "high" | "critical" | "major" | "blocker" => Some(Severity::Error),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+  Bug,
+  Security,
+  Performance,
+  Maintainability,
+  Style,
+  Testing,
+  Documentation,
+  Other,
+}
+
+impl Category {
+  const ALL: [Category; 8] = [
+    Category::Bug,
+    Category::Security,
+    Category::Performance,
+    Category::Maintainability,
+    Category::Style,
+    Category::Testing,
+    Category::Documentation,
+    Category::Other,
+  ];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Category::Bug => "bug",
+      Category::Security => "security",
+      Category::Performance => "performance",
+      Category::Maintainability => "maintainability",
+      Category::Style => "style",
+      Category::Testing => "testing",
+      Category::Documentation => "documentation",
+      Category::Other => "other",
+    }
+  }
+
+  /// Unknown categories are kept as `Other` rather than losing the finding.
+  pub(crate) fn parse(value: &str) -> Category {
+    let value = value.trim().to_ascii_lowercase();
+    Category::ALL.into_iter().find(|c| c.as_str() == value).unwrap_or(Category::Other)
+  }
+}
+
+/// One problem the model found, anchored to lines of the head version of a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewFinding {
+  pub file: String,
+  pub start_line: u32,
+  pub end_line: u32,
+  pub severity: Severity,
+  pub category: Category,
+  pub message: String,
+  /// Exact replacement for lines `start_line..=end_line`, if the model has one.
+  pub suggested_fix: Option<String>,
+  /// How sure the model is, from 0 to 1.
+  pub confidence: f32,
+}
+
+/// The model's whole review: a short summary plus the findings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewReport {
+  pub summary: String,
+  pub findings: Vec<ReviewFinding>,
+}
+
+/// JSON schema for [`ReviewReport`], sent to the model as the required response format.
+pub fn report_schema() -> Value {
+  let categories: Vec<&str> = Category::ALL.iter().map(|c| c.as_str()).collect();
+  json!({
+    "type": "object",
+    "additionalProperties": false,
+    "required": ["summary", "findings"],
+    "properties": {
+      "summary": { "type": "string" },
+      "findings": {
+        "type": "array",
+        "items": {
+          "type": "object",
+          "additionalProperties": false,
+          "required": [
+            "file", "start_line", "end_line", "severity", "category", "message",
+            "suggested_fix", "confidence"
+          ],
+          "properties": {
+            "file": { "type": "string" },
+            "start_line": { "type": "integer", "minimum": 1 },
+            "end_line": { "type": "integer", "minimum": 1 },
+            "severity": { "type": "string", "enum": ["info", "warning", "error"] },
+            "category": { "type": "string", "enum": categories },
+            "message": { "type": "string" },
+            "suggested_fix": { "type": ["string", "null"] },
+            "confidence": { "type": "number", "minimum": 0, "maximum": 1 }
+          }
+        }
+      }
+    }
+  })
+}
+
+/// A model reply that could not be read as a report at all; worth asking the model to fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportError(pub String);
+
+impl std::fmt::Display for ReportError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid review report: {}", self.0)
+  }
+}
+
+impl std::error::Error for ReportError {}
+
+/// Parse and validate a model reply against the report schema.
+/// Cheap fixes are applied on the way (code fences, reasoning preambles, synonyms, swapped
+/// line ranges, out-of-range confidence); findings that still don't validate are dropped and
+/// described in the returned list. Only a reply with no usable report at all is an error.
+pub fn parse_report(reply: &str) -> Result<(ReviewReport, Vec<String>), ReportError> {
+  let text = extract_json_object(reply).ok_or_else(|| ReportError("no JSON object".into()))?;
+  let value: Value =
+    serde_json::from_str(text).map_err(|e| ReportError(format!("not valid JSON: {}", e)))?;
+  let object = value.as_object().ok_or_else(|| ReportError("not a JSON object".into()))?;
+
+  let summary = object.get("summary").and_then(Value::as_str).unwrap_or_default().trim();
+  let items = object
+    .get("findings")
+    .and_then(Value::as_array)
+    .ok_or_else(|| ReportError("`findings` must be an array".into()))?;
+
+  let mut findings = Vec::new();
+  let mut dropped = Vec::new();
+  for (i, item) in items.iter().enumerate() {
+    match item.as_object().ok_or("not an object".to_string()).and_then(parse_finding) {
+      Ok(finding) => findings.push(finding),
+      Err(reason) => dropped.push(format!("finding {}: {}", i, reason)),
+    }
+  }
+
+  Ok((ReviewReport { summary: summary.to_string(), findings }, dropped))
+}
+
+fn parse_finding(item: &Map<String, Value>) -> Result<ReviewFinding, String> {
+  let text = |key: &str| item.get(key).and_then(Value::as_str).map(str::trim);
+  let line = |key: &str| {
+    let value = item.get(key)?;
+    value.as_u64().or_else(|| value.as_str()?.trim().parse().ok()).map(|n| n as u32)
+  };
+
+  let file = text("file").filter(|f| !f.is_empty()).ok_or("missing `file`")?;
+  let message = text("message").filter(|m| !m.is_empty()).ok_or("missing `message`")?;
+  let end_line = line("end_line").or_else(|| line("line")).filter(|&n| n > 0);
+  let end_line = end_line.ok_or("missing or zero `end_line`")?;
+  let start_line = line("start_line").filter(|&n| n > 0).unwrap_or(end_line);
+  let severity = text("severity").and_then(Severity::parse).ok_or("unknown `severity`")?;
+  let category = text("category").map(Category::parse).unwrap_or(Category::Other);
+  let suggested_fix =
+    item.get("suggested_fix").and_then(Value::as_str).filter(|s| !s.trim().is_empty());
+  let confidence = item.get("confidence").and_then(Value::as_f64).unwrap_or(0.5);
+
+  Ok(ReviewFinding {
+    file: file.trim_start_matches("./").to_string(),
+    start_line: start_line.min(end_line),
+    end_line: start_line.max(end_line),
+    severity,
+    category,
+    message: message.to_string(),
+    suggested_fix: suggested_fix.map(str::to_string),
+    confidence: confidence.clamp(0.0, 1.0) as f32,
+  })
+}
+
+/// The outermost `{...}` in a reply, skipping `<think>` blocks and markdown fences around it.
+pub(crate) fn extract_json_object(reply: &str) -> Option<&str> {
+  let reply = match reply.rfind("</think>") {
+    Some(end) => &reply[end + "</think>".len()..],
+    None => reply,
+  };
+  let start = reply.find('{')?;
+  let end = reply.rfind('}')?;
+  (start < end).then(|| &reply[start..=end])
+}
+
+impl ReviewFinding {
+  /// `path:12` or `path:12-14`.
+  pub fn location(&self) -> String {
+    if self.start_line == self.end_line {
+      format!("{}:{}", self.file, self.end_line)
+    } else {
+      format!("{}:{}-{}", self.file, self.start_line, self.end_line)
+    }
+  }
+
+  /// `**Warning** (bug):` prefix followed by the message.
+  pub fn headline(&self) -> String {
+    format!("**{:?}** ({}): {}", self.severity, self.category.as_str(), self.message)
+  }
+}
+
+impl ReviewReport {
+  /// Render the whole report as one markdown comment.
+  pub fn to_markdown(&self) -> String {
+    let mut out = String::from("## Mechanic review\n\n");
+    out.push_str(if self.summary.is_empty() { "No summary." } else { &self.summary });
+    out.push('\n');
+
+    if self.findings.is_empty() {
+      out.push_str("\nNo problems found.\n");
+      return out;
+    }
+    out.push_str("\n### Findings\n");
+    for finding in &self.findings {
+      let _ = writeln!(out, "- `{}` {}", finding.location(), finding.headline());
+      if let Some(fix) = &finding.suggested_fix {
+        let _ = writeln!(out, "\n{}\n", fenced("", fix));
+      }
+    }
+    out
+  }
+}
+
+/// Wrap code in a fence longer than any backtick run inside it, so it can't close early.
+pub fn fenced(info: &str, code: &str) -> String {
+  let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+  let fence = "`".repeat(longest_run.max(2) + 1);
+  let code = code.trim_end_matches('\n');
+  format!("{fence}{info}\n{code}\n{fence}")
+}
@@ -0,0 +1,316 @@
+// functions.rs
+use crate::helpers::diff::{FileDiff, LineKind};
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Language, Node, Parser, Point};
+
+/// A function, method or impl block a diff changes, as it reads at the reviewed commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedFunction {
+  /// Methods are qualified with their type, e.g. `Client::fetch` or `Client.fetch`.
+  pub name: String,
+  pub file: String,
+  pub commit_id: String,
+  pub start_line: u32,
+  pub end_line: u32,
+  pub body: String,
+  /// Functions and methods it calls, each once, in the order they appear.
+  pub dependencies: Vec<String>,
+}
+
+/// Where functions are in one language's syntax tree.
+struct Grammar {
+  language: Language,
+  /// Functions and methods.
+  functions: &'static [&'static str],
+  /// What methods are defined in. Changes inside one but outside any method belong to it.
+  containers: &'static [&'static str],
+  /// Calls, with the field holding what is called.
+  calls: &'static [(&'static str, &'static str)],
+  /// Between a type's name and its method's.
+  separator: &'static str,
+}
+
+const JS_FUNCTIONS: &[&str] = &[
+  "function_declaration",
+  "generator_function_declaration",
+  "method_definition",
+  "function_expression",
+  "arrow_function",
+];
+const JS_CLASSES: &[&str] = &["class_declaration", "abstract_class_declaration", "class"];
+const JS_CALLS: &[(&str, &str)] = &[("call_expression", "function")];
+
+/// The grammar for a file, chosen by its extension; `None` for languages without one.
+fn grammar(path: &str) -> Option<Grammar> {
+  let extension = path.rsplit_once('.').map(|(_, ext)| ext)?;
+  let grammar = match extension {
+    "rs" => Grammar {
+      language: tree_sitter_rust::LANGUAGE.into(),
+      functions: &["function_item", "function_signature_item"],
+      containers: &["impl_item", "trait_item"],
+      calls: &[("call_expression", "function")],
+      separator: "::",
+    },
+    "py" | "pyi" => Grammar {
+      language: tree_sitter_python::LANGUAGE.into(),
+      functions: &["function_definition"],
+      containers: &["class_definition"],
+      calls: &[("call", "function")],
+      separator: ".",
+    },
+    "ts" | "mts" | "cts" => Grammar {
+      language: tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+      functions: JS_FUNCTIONS,
+      containers: JS_CLASSES,
+      calls: JS_CALLS,
+      separator: ".",
+    },
+    "tsx" => Grammar {
+      language: tree_sitter_typescript::LANGUAGE_TSX.into(),
+      functions: JS_FUNCTIONS,
+      containers: JS_CLASSES,
+      calls: JS_CALLS,
+      separator: ".",
+    },
+    "js" | "jsx" | "mjs" | "cjs" => Grammar {
+      language: tree_sitter_javascript::LANGUAGE.into(),
+      functions: JS_FUNCTIONS,
+      containers: JS_CLASSES,
+      calls: JS_CALLS,
+      separator: ".",
+    },
+    "go" => Grammar {
+      language: tree_sitter_go::LANGUAGE.into(),
+      functions: &["function_declaration", "method_declaration"],
+      containers: &[],
+      calls: &[("call_expression", "function")],
+      separator: ".",
+    },
+    "java" => Grammar {
+      language: tree_sitter_java::LANGUAGE.into(),
+      functions: &["method_declaration", "constructor_declaration"],
+      containers: &[
+        "class_declaration",
+        "interface_declaration",
+        "enum_declaration",
+        "record_declaration",
+      ],
+      calls: &[("method_invocation", "name")],
+      separator: ".",
+    },
+    _ => return None,
+  };
+  Some(grammar)
+}
+
+/// Whether functions can be extracted from `path`.
+pub fn supports(path: &str) -> bool {
+  grammar(path).is_some()
+}
+
+/// The functions, methods and impl blocks of `file` that its diff changes, top of the file
+/// first, read from the file's `contents` at `commit_id`. Empty for unsupported languages.
+pub fn extract_functions(file: &FileDiff, contents: &str, commit_id: &str) -> Vec<ChangedFunction> {
+  let Some(grammar) = grammar(&file.path) else { return Vec::new() };
+  let mut parser = Parser::new();
+  if parser.set_language(&grammar.language).is_err() {
+    return Vec::new();
+  }
+  let Some(tree) = parser.parse(contents, None) else { return Vec::new() };
+  let source = contents.as_bytes();
+
+  let lines: Vec<&str> = contents.lines().collect();
+  let mut items: Vec<Node> = Vec::new();
+  for (start, end) in changed_spans(file) {
+    let (from, to) = (line_start(&lines, start), line_end(&lines, end));
+    let Some(node) = tree.root_node().descendant_for_point_range(from, to) else { continue };
+    if let Some(item) = enclosing_item(node, &grammar, source)
+      && !items.contains(&item)
+    {
+      items.push(item);
+    }
+  }
+  items.sort_by_key(|item| item.start_byte());
+
+  let text = |node: Node| node.utf8_text(source).unwrap_or_default().to_string();
+  items
+    .into_iter()
+    .filter_map(|item| {
+      let name = qualified_name(item, &grammar, source)?;
+      let bare = name.rsplit(grammar.separator).next().unwrap_or(&name).to_string();
+      let mut calls = Vec::new();
+      collect_calls(item, &grammar, source, &mut calls);
+      calls.sort();
+      let mut dependencies: Vec<String> = Vec::new();
+      for (_, called) in calls {
+        if called != bare && !dependencies.contains(&called) {
+          dependencies.push(called);
+        }
+      }
+      Some(ChangedFunction {
+        name,
+        file: file.path.clone(),
+        commit_id: commit_id.to_string(),
+        start_line: item.start_position().row as u32 + 1,
+        end_line: item.end_position().row as u32 + 1,
+        body: text(item),
+        dependencies,
+      })
+    })
+    .collect()
+}
+
+/// Head-side line ranges the diff changes. An added line is its own range; a removal sits
+/// between the lines either side of it, so it only belongs to an item holding both.
+fn changed_spans(file: &FileDiff) -> Vec<(u32, u32)> {
+  let mut spans = Vec::new();
+  for hunk in &file.hunks {
+    let mut next = hunk.new_start.max(1);
+    for line in &hunk.lines {
+      match (line.kind, line.new_line) {
+        (LineKind::Removed, _) => spans.push((next.saturating_sub(1).max(1), next)),
+        (kind, Some(new_line)) => {
+          if kind == LineKind::Added {
+            spans.push((new_line, new_line));
+          }
+          next = new_line + 1;
+        }
+        _ => {}
+      }
+    }
+  }
+  spans
+}
+
+/// Where the text of line `number` starts, past its indentation. Column 0 of an indented
+/// line lies outside the method on it, in whatever holds the method.
+fn line_start(lines: &[&str], number: u32) -> Point {
+  let row = number as usize - 1;
+  let line = lines.get(row).copied().unwrap_or_default();
+  Point::new(row, line.len() - line.trim_start().len())
+}
+
+/// Where the text of line `number` ends, or where it starts for a blank line.
+fn line_end(lines: &[&str], number: u32) -> Point {
+  let row = number as usize - 1;
+  let line = lines.get(row).copied().unwrap_or_default();
+  Point::new(row, line.trim_end().len().max(line_start(lines, number).column))
+}
+
+/// The innermost named function around `node`, or failing that the innermost container.
+fn enclosing_item<'t>(node: Node<'t>, grammar: &Grammar, source: &[u8]) -> Option<Node<'t>> {
+  let mut container = None;
+  let mut current = Some(node);
+  while let Some(node) = current {
+    if grammar.functions.contains(&node.kind()) && own_name(node, source).is_some() {
+      return Some(node);
+    }
+    if container.is_none() && grammar.containers.contains(&node.kind()) {
+      container = Some(node);
+    }
+    current = node.parent();
+  }
+  container
+}
+
+/// An item's name as written where it is defined. Anonymous JavaScript functions take the
+/// name of what they are assigned to; callbacks have none.
+fn own_name(node: Node, source: &[u8]) -> Option<String> {
+  let text = |node: Node| node.utf8_text(source).ok().map(str::to_string);
+  match node.kind() {
+    "impl_item" => {
+      let implemented = text(node.child_by_field_name("type")?)?;
+      match node.child_by_field_name("trait").and_then(text) {
+        Some(implemented_trait) => Some(format!("impl {} for {}", implemented_trait, implemented)),
+        None => Some(format!("impl {}", implemented)),
+      }
+    }
+    "arrow_function" | "function_expression" => {
+      if let Some(name) = node.child_by_field_name("name") {
+        return text(name);
+      }
+      let parent = node.parent()?;
+      let field = match parent.kind() {
+        "variable_declarator" | "public_field_definition" => "name",
+        "field_definition" => "property",
+        "pair" => "key",
+        "assignment_expression" => "left",
+        _ => return None,
+      };
+      // Only a function that is the value itself, not a callback somewhere inside it.
+      let value = parent.child_by_field_name("value").or(parent.child_by_field_name("right"));
+      if value != Some(node) {
+        return None;
+      }
+      text(parent.child_by_field_name(field)?)
+    }
+    _ => text(node.child_by_field_name("name")?),
+  }
+}
+
+/// The type methods of `container` belong to.
+fn container_type(container: Node, source: &[u8]) -> Option<String> {
+  let field = if container.kind() == "impl_item" { "type" } else { "name" };
+  container.child_by_field_name(field)?.utf8_text(source).ok().map(str::to_string)
+}
+
+/// The item's name, with the type it is a method of in front.
+fn qualified_name(item: Node, grammar: &Grammar, source: &[u8]) -> Option<String> {
+  let name = own_name(item, source)?;
+  if !grammar.functions.contains(&item.kind()) {
+    return Some(name);
+  }
+  // Go methods name their type in the receiver rather than being nested in it.
+  let receiver = item.child_by_field_name("receiver").and_then(|r| find_kind(r, "type_identifier"));
+  let owner = match receiver {
+    Some(receiver) => receiver.utf8_text(source).ok().map(str::to_string),
+    None => {
+      let mut current = item.parent();
+      while let Some(node) = current
+        && !grammar.containers.contains(&node.kind())
+      {
+        current = node.parent();
+      }
+      current.and_then(|container| container_type(container, source))
+    }
+  };
+  Some(match owner {
+    Some(owner) => format!("{}{}{}", owner, grammar.separator, name),
+    None => name,
+  })
+}
+
+fn find_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+  if node.kind() == kind {
+    return Some(node);
+  }
+  let mut cursor = node.walk();
+  let children: Vec<Node<'t>> = node.children(&mut cursor).collect();
+  children.into_iter().find_map(|child| find_kind(child, kind))
+}
+
+/// Everything called inside `node`, with where the called name starts.
+fn collect_calls(node: Node, grammar: &Grammar, source: &[u8], calls: &mut Vec<(usize, String)>) {
+  for (kind, field) in grammar.calls {
+    if node.kind() == *kind
+      && let Some(called) = node.child_by_field_name(field).and_then(callee)
+      && let Ok(name) = called.utf8_text(source)
+    {
+      calls.push((called.start_byte(), name.to_string()));
+    }
+  }
+  let mut cursor = node.walk();
+  for child in node.children(&mut cursor) {
+    collect_calls(child, grammar, source, calls);
+  }
+}
+
+/// The name a call calls: a plain or path name as written (`retry`, `Duration::from_millis`),
+/// or just the method for calls on a value (`client.fetch` -> `fetch`).
+fn callee(node: Node) -> Option<Node> {
+  match node.kind() {
+    "identifier" | "scoped_identifier" | "field_identifier" | "property_identifier" => Some(node),
+    "generic_function" => callee(node.child_by_field_name("function")?),
+    _ => ["field", "property", "attribute"].into_iter().find_map(|f| node.child_by_field_name(f)),
+  }
+}
@@ -0,0 +1,59 @@
+// glob.rs
+
+/// Match a repository path against a glob: `*` and `?` stay within one path segment, `**`
+/// spans any number of segments. A pattern without a `/` matches the file name anywhere,
+/// like `.gitignore`, so `*.lock` covers `deps/Cargo.lock`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+  let pattern = pattern.trim_start_matches("./");
+  let path = path.trim_start_matches("./");
+  if !pattern.contains('/') {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    return segment_match(pattern.as_bytes(), name.as_bytes());
+  }
+  let pattern: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+  let path: Vec<&str> = path.split('/').collect();
+  segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+  wildcard_match(
+    pattern,
+    path,
+    |p| *p == "**",
+    |p, segment| segment_match(p.as_bytes(), segment.as_bytes()),
+  )
+}
+
+fn segment_match(pattern: &[u8], text: &[u8]) -> bool {
+  wildcard_match(pattern, text, |p| *p == b'*', |p, c| *p == b'?' || p == c)
+}
+
+/// Whether `pattern` matches all of `text`, where an element `is_any` accepts matches any run of
+/// elements, none included, and every other one a single element it `matches`. Filled in row by
+/// row over the pattern, so it takes time proportional to the two lengths multiplied, however
+/// many wildcards the pattern has.
+fn wildcard_match<P, T>(
+  pattern: &[P],
+  text: &[T],
+  is_any: impl Fn(&P) -> bool,
+  matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+  // `row[j]`: the pattern so far matches the first `j` elements of `text`.
+  let mut row = vec![false; text.len() + 1];
+  row[0] = true;
+  for p in pattern {
+    let mut next = vec![false; text.len() + 1];
+    if is_any(p) {
+      next[0] = row[0];
+      for j in 1..=text.len() {
+        next[j] = row[j] || next[j - 1];
+      }
+    } else {
+      for j in 1..=text.len() {
+        next[j] = row[j - 1] && matches(p, &text[j - 1]);
+      }
+    }
+    row = next;
+  }
+  row[text.len()]
+}
@@ -1,5 +1,7 @@
 // jwt.rs
-use chrono::{Duration, Utc};
+use crate::config::GitHubConfig;
+use crate::helpers::redact::{Secret, redact};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -14,17 +16,15 @@ struct Claims {
   iss: String,
 }
 
-pub fn create_jwt() -> Result<String, Box<dyn Error>> {
+/// Sign a short-lived app JWT with the GitHub App's private key.
+pub fn create_jwt(github: &GitHubConfig) -> Result<Secret, Box<dyn Error>> {
   println!("beginning to create jwt");
-  // Hardcoded GitHub App ID and PEM file path
-  let app_id = 1146309; // Replace with your actual GitHub App ID
-  let pem_path = "certs/fuckyou.pem";
-  // print this and check if it exists
-  // Update to your PEM file's location
+  let app_id = github.app_id;
+  let pem_path = &github.private_key_path;
 
   // Attempt to read the private key file
   let key_contents = fs::read_to_string(pem_path)
-    .map_err(|e| format!("Failed to read PEM file from '{}': {}", pem_path, e))?;
+    .map_err(|e| format!("Failed to read PEM file from '{}': {}", pem_path.display(), e))?;
 
   let now = Utc::now();
   let iat = (now.timestamp() - 60) as usize;
@@ -43,15 +43,45 @@ pub fn create_jwt() -> Result<String, Box<dyn Error>> {
   let token =
     encode(&header, &claims, &encoding_key).map_err(|e| format!("Failed to encode JWT: {}", e))?;
 
-  Ok(token)
+  Ok(Secret::new(token))
+}
+
+/// The app's slug, e.g. `mechanic`; its bot comments as `<slug>[bot]`.
+pub async fn fetch_app_slug(api_url: &str, jwt: &Secret) -> Result<String, Box<dyn Error>> {
+  let url = format!("{}/app", api_url.trim_end_matches('/'));
+  let response = Client::new()
+    .get(&url)
+    .header("Authorization", format!("Bearer {}", jwt.expose()))
+    .header("Accept", "application/vnd.github+json")
+    .header("User-Agent", "3mechanic")
+    .send()
+    .await?
+    .error_for_status()?;
+  let json: Value = response.json().await?;
+  match json.get("slug").and_then(|v| v.as_str()) {
+    Some(slug) => Ok(slug.to_string()),
+    None => Err(redact(&format!("Failed to read the app's slug. Response: {:?}", json)).into()),
+  }
+}
+
+/// An installation access token and the moment GitHub stops accepting it.
+#[derive(Debug, Clone)]
+pub struct InstallationToken {
+  pub token: Secret,
+  pub expires_at: DateTime<Utc>,
 }
 
 pub async fn exchange_jwt_for_installation_token(
-  jwt: &str,
+  api_url: &str,
+  jwt: &Secret,
   installation_id: u64,
-) -> Result<String, Box<dyn Error>> {
+) -> Result<InstallationToken, Box<dyn Error>> {
   // Construct the URL to request the installation token.
-  let url = format!("https://api.github.com/app/installations/{}/access_tokens", installation_id);
+  let url = format!(
+    "{}/app/installations/{}/access_tokens",
+    api_url.trim_end_matches('/'),
+    installation_id
+  );
 
   // Create a reqwest client.
   let client = Client::new();
@@ -59,7 +89,7 @@ pub async fn exchange_jwt_for_installation_token(
   // Perform the POST request with the required headers.
   let response = client
     .post(&url)
-    .header("Authorization", format!("Bearer {}", jwt))
+    .header("Authorization", format!("Bearer {}", jwt.expose()))
     .header("Accept", "application/vnd.github+json")
     .header("User-Agent", "3mechanic") // GitHub API requires a User-Agent header.
     .send()
@@ -71,9 +101,19 @@ pub async fn exchange_jwt_for_installation_token(
 
   // Attempt to extract the installation token.
   if let Some(token) = json.get("token").and_then(|v| v.as_str()) {
-    println!("Installation token obtained: xq{}", token);
-    Ok(token.to_string())
+    // Tokens last an hour; assume that if GitHub ever omits the expiry.
+    let expires_at = json
+      .get("expires_at")
+      .and_then(|v| v.as_str())
+      .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+      .map(|v| v.with_timezone(&Utc))
+      .unwrap_or_else(|| Utc::now() + Duration::hours(1));
+    println!(
+      "Installation token obtained for installation {} (expires {})",
+      installation_id, expires_at
+    );
+    Ok(InstallationToken { token: Secret::new(token), expires_at })
   } else {
-    Err(format!("Failed to obtain installation token. Response: {:?}", json).into())
+    Err(redact(&format!("Failed to obtain installation token. Response: {:?}", json)).into())
   }
 }
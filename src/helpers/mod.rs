@@ -1,3 +1,17 @@
+pub mod artifacts;
+pub mod autofix;
+pub mod checks;
+pub mod commands;
+pub mod diff;
 pub mod event;
+pub mod findings;
+pub mod functions;
+pub mod glob;
 pub mod jwt;
 pub mod octo;
+pub mod redact;
+pub mod review;
+pub mod signature;
+pub mod status;
+pub mod sticky;
+pub mod threads;
@@ -1,111 +1,499 @@
+use crate::helpers::autofix::FixedFile;
+use crate::helpers::checks::{ANNOTATIONS_PER_REQUEST, CheckAnnotation, CheckResult};
+use crate::helpers::diff::{FileDiff, parse_unified_diff};
+use crate::helpers::event;
+use crate::helpers::review::PullRequestReview;
+use crate::helpers::status::CommitStatus;
+use crate::helpers::sticky::{STICKY_MARKER, StickyEntry, update_sticky};
+use crate::helpers::threads::{
+  ADDRESSED_MARKER, DISMISSED_MARKER, ReviewThread, parse_finding_marker,
+};
+use chrono::Utc;
 use octocrab::Octocrab;
-use octocrab::models::pulls::PullRequest;
+use octocrab::models::issues::Comment;
 use octocrab::params::{State, repos::Reference};
+use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::error::Error;
 
-/// Initialize Octocrab with a GitHub installation token.
-pub fn init_octocrab(installation_token: String) -> Octocrab {
-  Octocrab::builder()
-    .personal_token(installation_token)
-    .build()
-    .expect("Failed to initialize Octocrab")
+/// Initialize Octocrab with a GitHub installation token, talking to the API at `api_url`.
+pub fn init_octocrab(
+  api_url: &str,
+  installation_token: String,
+) -> Result<Octocrab, Box<dyn Error>> {
+  Ok(Octocrab::builder().base_uri(api_url)?.personal_token(installation_token).build()?)
 }
 
-/// List open pull requests in a repository.
-pub async fn test_list_pull_requests(octo: &Octocrab, owner: &str, repo: &str) {
-  match octo.pulls(owner, repo).list().state(State::Open).per_page(5).send().await {
-    Ok(prs) => {
-      let pr_list: Vec<PullRequest> = prs.items;
-      println!("Open pull requests in {}/{}: {:#?}", owner, repo, pr_list);
-    }
-    Err(err) => {
-      println!("Failed to list pull requests: {:?}", err);
-    }
+/// Create a unique "mechanic-[pr]" branch (then "mechanic-[pr]-1", ...) pointing at `sha`,
+/// for work on pull request `pr_number`. Returns the branch name.
+pub async fn create_mechanic_branch(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  sha: &str,
+) -> Result<String, Box<dyn Error>> {
+  let base_branch = format!("mechanic-{}", pr_number);
+  let mut new_branch = base_branch.clone();
+  let mut counter = 1;
+
+  // Step 1: Check if the branch exists and increment if necessary
+  while octo.repos(owner, repo).get_ref(&Reference::Branch(new_branch.clone())).await.is_ok() {
+    new_branch = format!("{}-{}", base_branch, counter);
+    counter += 1;
   }
+
+  // Step 2: Create the new branch at the commit
+  octo.repos(owner, repo).create_ref(&Reference::Branch(new_branch.clone()), sha).await?;
+  println!("Created new branch: {}", new_branch);
+  Ok(new_branch)
 }
 
-//// Find the latest PR and create a unique "mechanic-[issue]" branch from its latest commit.
-pub async fn create_mechanic_branch(octo: &Octocrab, owner: &str, repo: &str) {
-  // Step 1: Find the latest PR
-  match octo.pulls(owner, repo).list().state(State::Open).per_page(1).send().await {
-    Ok(prs) => {
-      if let Some(pr) = prs.items.first() {
-        let pr_number = pr.number;
-        println!("Found latest PR: #{}", pr_number);
+/// Commit new contents for `files` on top of `parent_sha` with the Git Data API, without
+/// moving any branch. Files keep their mode in the parent, so scripts stay executable; new
+/// files are regular files. Returns the new commit's sha.
+pub async fn commit_files(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  parent_sha: &str,
+  message: &str,
+  files: &[FixedFile],
+) -> Result<String, Box<dyn Error>> {
+  let git = format!("/repos/{}/{}/git", owner, repo);
+  let parent: Value = octo.get(format!("{}/commits/{}", git, parent_sha), None::<&()>).await?;
+  let base_tree = parent["tree"]["sha"].as_str().ok_or("parent commit has no tree")?;
 
-        // Step 2: Get the latest commit SHA of the PR
-        let latest_commit = pr.head.sha.clone();
+  let listing: Value =
+    octo.get(format!("{}/trees/{}", git, base_tree), Some(&[("recursive", "1")])).await?;
+  let modes: HashMap<&str, &str> = listing["tree"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| Some((entry["path"].as_str()?, entry["mode"].as_str()?)))
+    .collect();
 
-        println!("Latest PR commit SHA: {}", latest_commit);
+  let mut entries = Vec::new();
+  for FixedFile { path, contents, .. } in files {
+    let mode = modes.get(path.as_str()).copied().unwrap_or("100644");
+    entries.push(json!({ "path": path, "mode": mode, "type": "blob", "content": contents }));
+  }
+  let tree: Value = octo
+    .post(format!("{}/trees", git), Some(&json!({ "base_tree": base_tree, "tree": entries })))
+    .await?;
+  let tree_sha = tree["sha"].as_str().ok_or("created tree has no sha")?;
 
-        // Step 3: Generate base branch name
-        let base_branch = format!("mechanic-{}", pr_number);
-        let mut new_branch = base_branch.clone();
-        let mut counter = 1;
+  let body = json!({ "message": message, "tree": tree_sha, "parents": [parent_sha] });
+  let commit: Value = octo.post(format!("{}/commits", git), Some(&body)).await?;
+  let sha = commit["sha"].as_str().ok_or("created commit has no sha")?;
+  println!("Committed {} file(s) on top of {}: {}", files.len(), parent_sha, sha);
+  Ok(sha.to_string())
+}
 
-        // Step 4: Check if the branch exists and increment if necessary
-        while octo.repos(owner, repo).get_ref(&Reference::Branch(new_branch.clone())).await.is_ok()
-        {
-          new_branch = format!("{}-{}", base_branch, counter);
-          counter += 1;
-        }
+/// Open a pull request merging `head` into `base`. Returns its URL.
+pub async fn open_pull_request(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  head: &str,
+  base: &str,
+  title: &str,
+  body: &str,
+) -> Result<String, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/pulls", owner, repo);
+  let request = json!({ "title": title, "head": head, "base": base, "body": body });
+  let pull: Value = octo.post(route, Some(&request)).await?;
+  let url = pull["html_url"].as_str().ok_or("created pull request has no URL")?;
+  println!("Opened pull request {} from {} into {}", url, head, base);
+  Ok(url.to_string())
+}
 
-        // Step 5: Create a new branch from the latest commit
-        match octo
-          .repos(owner, repo)
-          .create_ref(&Reference::Branch(new_branch.clone()), &latest_commit)
-          .await
-        {
-          Ok(_) => println!("Created new branch: {}", new_branch),
-          Err(err) => println!("Failed to create new branch: {:?}", err),
-        }
-      } else {
-        println!("No open PRs found.");
-      }
+/// An open pull request of Mechanic's suggested fixes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutofixPullRequest {
+  pub number: u64,
+  pub branch: String,
+  pub url: String,
+}
+
+/// The open pull request `bot_login` opened from a `mechanic-<pr>` branch (or one of its
+/// numbered variants) into `base`, if there is one.
+pub async fn find_autofix_pull_request(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  base: &str,
+  bot_login: &str,
+) -> Result<Option<AutofixPullRequest>, Box<dyn Error>> {
+  let pulls = octo.pulls(owner, repo);
+  let first_page = pulls.list().state(State::Open).base(base).per_page(100).send().await?;
+  let open = octo.all_pages(first_page).await?;
+  let branch = format!("mechanic-{}", pr_number);
+  let is_autofix_branch = |name: &str| {
+    let suffix = name.strip_prefix(branch.as_str());
+    suffix.is_some_and(|s| {
+      s.is_empty() || s.strip_prefix('-').is_some_and(|n| n.parse::<u32>().is_ok())
+    })
+  };
+  let found = open.into_iter().find(|pull| {
+    pull.user.as_ref().is_some_and(|user| user.login == bot_login)
+      && is_autofix_branch(&pull.head.ref_field)
+  });
+  Ok(found.map(|pull| AutofixPullRequest {
+    number: pull.number,
+    branch: pull.head.ref_field,
+    url: pull.html_url.map(|url| url.to_string()).unwrap_or_default(),
+  }))
+}
+
+/// Point `branch` at `sha`, even if that drops commits from it.
+pub async fn force_update_branch(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  branch: &str,
+  sha: &str,
+) -> Result<(), Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/git/refs/heads/{}", owner, repo, branch);
+  let _: Value = octo.patch(route, Some(&json!({ "sha": sha, "force": true }))).await?;
+  println!("Moved branch {} to {}", branch, sha);
+  Ok(())
+}
+
+/// Change an open pull request's fields, e.g. `{"body": ...}` or `{"state": "closed"}`.
+pub async fn update_pull_request(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  fields: &Value,
+) -> Result<(), Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/pulls/{}", owner, repo, pr_number);
+  let _: Value = octo.patch(route, Some(fields)).await?;
+  Ok(())
+}
+
+pub async fn post_markdown_as_comment(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  markdown: &str,
+) -> Result<String, Box<dyn Error>> {
+  let comment = octo.issues(owner, repo).create_comment(pr_number, markdown).await?;
+  println!("Comment posted: {}", comment.html_url);
+  Ok(comment.html_url.to_string())
+}
+
+/// Fetch the pull request's unified diff (base...head) and parse it into per-file hunks.
+pub async fn get_pull_request_diff(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+) -> Result<Vec<FileDiff>, Box<dyn Error>> {
+  let diff = octo.pulls(owner, repo).get_diff(pr_number).await?;
+  let files = parse_unified_diff(&diff);
+  println!("Fetched diff for PR #{}: {} file(s) changed.", pr_number, files.len());
+  Ok(files)
+}
+
+/// Submit a pull request review with its line-anchored comments in one request.
+pub async fn submit_review(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  review: &PullRequestReview,
+) -> Result<Option<String>, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/pulls/{}/reviews", owner, repo, pr_number);
+  let response: Value = octo.post(route, Some(review)).await?;
+  let html_url = response["html_url"].as_str().map(str::to_string);
+  println!(
+    "Review posted ({:?}, {} inline comment(s)): {}",
+    review.event,
+    review.comments.len(),
+    html_url.as_deref().unwrap_or("")
+  );
+  Ok(html_url)
+}
+
+/// Start an in-progress check run on `head_sha` and return its id.
+pub async fn create_check_run(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  name: &str,
+  head_sha: &str,
+) -> Result<u64, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/check-runs", owner, repo);
+  let body = json!({
+    "name": name,
+    "head_sha": head_sha,
+    "status": "in_progress",
+    "started_at": Utc::now().to_rfc3339(),
+  });
+  let response: Value = octo.post(route, Some(&body)).await?;
+  let id = response["id"].as_u64().ok_or("check run response has no id")?;
+  println!("Check run {} started on {}", id, head_sha);
+  Ok(id)
+}
+
+/// Complete a check run with `result`. Annotations go up in batches, the API's limit per
+/// request; the last batch carries the conclusion.
+pub async fn complete_check_run(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  check_run_id: u64,
+  result: &CheckResult,
+) -> Result<(), Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/check-runs/{}", owner, repo, check_run_id);
+  let output = &result.output;
+  let batches: Vec<&[CheckAnnotation]> = if output.annotations.is_empty() {
+    vec![&[]]
+  } else {
+    output.annotations.chunks(ANNOTATIONS_PER_REQUEST).collect()
+  };
+
+  let last = batches.len() - 1;
+  for (i, annotations) in batches.into_iter().enumerate() {
+    let mut body = json!({
+      "output": { "title": output.title, "summary": output.summary, "annotations": annotations },
+    });
+    if i == last {
+      body["status"] = json!("completed");
+      body["conclusion"] = json!(result.conclusion);
+      body["completed_at"] = json!(Utc::now().to_rfc3339());
     }
-    Err(err) => {
-      println!("Failed to fetch PRs: {:?}", err);
+    let _: Value = octo.patch(&route, Some(&body)).await?;
+  }
+  println!(
+    "Check run {} completed ({:?}, {} annotation(s))",
+    check_run_id,
+    result.conclusion,
+    output.annotations.len()
+  );
+  Ok(())
+}
+
+/// Set a commit status on `sha`.
+pub async fn set_commit_status(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  sha: &str,
+  status: &CommitStatus,
+) -> Result<(), Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/statuses/{}", owner, repo, sha);
+  let _: Value = octo.post(route, Some(status)).await?;
+  println!("Commit status {} set to {:?} on {}", status.context, status.state, sha);
+  Ok(())
+}
+
+/// Put `entry` in Mechanic's sticky comment on the pull request, editing the comment left by an
+/// earlier push or creating it on the first. Only comments by `bot_login`, the app's own bot,
+/// count, so nobody else's comment can pose as it. Returns the comment's URL.
+pub async fn upsert_sticky_comment(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  bot_login: &str,
+  entry: StickyEntry,
+) -> Result<String, Box<dyn Error>> {
+  let issues = octo.issues(owner, repo);
+  let first_page = issues.list_comments(pr_number).per_page(100).send().await?;
+  let comments = octo.all_pages(first_page).await?;
+  let existing = comments.into_iter().find(|c| {
+    c.user.login == bot_login
+      && c.body.as_deref().is_some_and(|body| body.starts_with(STICKY_MARKER))
+  });
+
+  let comment = match existing {
+    Some(comment) => {
+      let body = update_sticky(comment.body.as_deref(), entry);
+      let route = format!("/repos/{}/{}/issues/comments/{}", owner, repo, comment.id);
+      octo.patch::<Comment, _, _>(route, Some(&json!({ "body": body }))).await?
     }
+    None => issues.create_comment(pr_number, update_sticky(None, entry)).await?,
+  };
+  println!("Sticky comment updated: {}", comment.html_url);
+  Ok(comment.html_url.to_string())
+}
+
+/// Run a GraphQL query, turning errors reported in the response body into `Err`.
+async fn graphql(octo: &Octocrab, query: &str, variables: Value) -> Result<Value, Box<dyn Error>> {
+  let response: Value = octo.graphql(&json!({ "query": query, "variables": variables })).await?;
+  if let Some(errors) = response["errors"].as_array().filter(|errors| !errors.is_empty()) {
+    let messages: Vec<&str> = errors.iter().filter_map(|e| e["message"].as_str()).collect();
+    return Err(format!("GraphQL error: {}", messages.join("; ")).into());
   }
+  Ok(response)
 }
 
-pub async fn reply_to_latest_pr(octo: &Octocrab, owner: &str, repo: &str) {
-  match octo.pulls(owner, repo).list().state(octocrab::params::State::Open).per_page(1).send().await
-  {
-    Ok(prs) => {
-      if let Some(pr) = prs.items.first() {
-        let pr_number = pr.number;
-        println!("Found latest PR: #{}", pr_number);
-        match octo
-          .issues(owner, repo)
-          .create_comment(pr_number, "Mechanic doesn't have any suggestions to do. Great work!")
-          .await
-        {
-          Ok(comment) => println!("Comment posted: {}", comment.html_url.to_string()),
-          Err(err) => println!("Failed to comment on PR #{}: {:?}", pr_number, err),
+const REVIEW_THREADS_QUERY: &str = "
+query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      reviewThreads(first: 100) {
+        nodes {
+          id isResolved isOutdated path line originalLine
+          comments(first: 100) { nodes { body databaseId viewerDidAuthor } }
         }
-      } else {
-        println!("No open PRs found.");
       }
     }
-    Err(err) => {
-      println!("Failed to fetch PRs: {:?}", err);
-    }
   }
+}";
+
+/// The pull request's review threads (the first 100, with their first 100 comments), tagged
+/// with the category of the finding when Mechanic started them. Markers only count in
+/// comments the app wrote itself.
+pub async fn list_review_threads(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+) -> Result<Vec<ReviewThread>, Box<dyn Error>> {
+  let variables = json!({ "owner": owner, "repo": repo, "number": pr_number });
+  let response = graphql(octo, REVIEW_THREADS_QUERY, variables).await?;
+  let nodes = &response["data"]["repository"]["pullRequest"]["reviewThreads"]["nodes"];
+  let line = |value: &Value| value.as_u64().and_then(|line| u32::try_from(line).ok());
+
+  let threads = nodes.as_array().into_iter().flatten().filter_map(|node| {
+    let comments = node["comments"]["nodes"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let own: Vec<&str> = comments
+      .iter()
+      .filter(|comment| comment["viewerDidAuthor"] == true)
+      .filter_map(|comment| comment["body"].as_str())
+      .collect();
+    let root = comments.first();
+    let root_is_own = root.is_some_and(|root| root["viewerDidAuthor"] == true);
+    let root_body = root.and_then(|root| root["body"].as_str()).filter(|_| root_is_own);
+    let said = |marker: &str| own.iter().any(|body| body.contains(marker));
+    Some(ReviewThread {
+      id: node["id"].as_str()?.to_string(),
+      path: node["path"].as_str()?.to_string(),
+      line: line(&node["line"]),
+      original_line: line(&node["originalLine"]),
+      is_resolved: node["isResolved"].as_bool().unwrap_or(false),
+      is_outdated: node["isOutdated"].as_bool().unwrap_or(false),
+      category: root_body.and_then(parse_finding_marker),
+      marked_addressed: said(ADDRESSED_MARKER),
+      root_comment_id: root.and_then(|root| root["databaseId"].as_u64()),
+      dismissed: said(DISMISSED_MARKER),
+    })
+  });
+  Ok(threads.collect())
 }
 
+/// Mark a review thread as resolved.
+pub async fn resolve_review_thread(octo: &Octocrab, thread_id: &str) -> Result<(), Box<dyn Error>> {
+  let query =
+    "mutation($id: ID!) { resolveReviewThread(input: {threadId: $id}) { thread { id } } }";
+  graphql(octo, query, json!({ "id": thread_id })).await?;
+  Ok(())
+}
 
-pub async fn post_markdown_as_comment(
+/// Add a reply to a review thread.
+pub async fn reply_to_review_thread(
+  octo: &Octocrab,
+  thread_id: &str,
+  body: &str,
+) -> Result<(), Box<dyn Error>> {
+  let query = "mutation($id: ID!, $body: String!) { \
+    addPullRequestReviewThreadReply(input: {pullRequestReviewThreadId: $id, body: $body}) \
+    { comment { id } } }";
+  graphql(octo, query, json!({ "id": thread_id, "body": body })).await?;
+  Ok(())
+}
+
+/// A file's text at `git_ref`, or `None` if it doesn't exist there.
+pub async fn get_file_contents(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  path: &str,
+  git_ref: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+  let contents = octo.repos(owner, repo).get_content().path(path).r#ref(git_ref).send().await;
+  match contents {
+    Ok(mut contents) => Ok(contents.items.pop().and_then(|item| item.decoded_content())),
+    Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// The pull request as the webhook payloads describe it, fetched fresh.
+pub async fn get_pull_request(
   octo: &Octocrab,
   owner: &str,
   repo: &str,
   pr_number: u64,
-  markdown: &str,
+) -> Result<event::PullRequest, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/pulls/{}", owner, repo, pr_number);
+  Ok(octo.get(route, None::<&()>).await?)
+}
+
+/// Whether `login` can push to the repository: the `admin`, `maintain` or `write` role.
+pub async fn has_write_access(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  login: &str,
+) -> Result<bool, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/collaborators/{}/permission", owner, repo, login);
+  let permission: Value = octo.get(route, None::<&()>).await?;
+  // `permission` folds `maintain` into `write`; `role_name` keeps custom roles apart.
+  Ok(matches!(permission["permission"].as_str(), Some("admin" | "write")))
+}
+
+/// React to an issue or pull request comment, e.g. with `+1` or `eyes`.
+pub async fn react_to_comment(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  comment_id: u64,
+  content: &str,
 ) -> Result<(), Box<dyn Error>> {
-  let comment = octo
-      .issues(owner, repo)
-      .create_comment(pr_number, markdown)
-      .await?;
-  println!("Comment posted: {}", comment.html_url);
+  let route = format!("/repos/{}/{}/issues/comments/{}/reactions", owner, repo, comment_id);
+  let _: Value = octo.post(route, Some(&json!({ "content": content }))).await?;
   Ok(())
-}
\ No newline at end of file
+}
+
+/// Every inline review comment on the pull request, oldest first.
+pub async fn list_review_comments(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+) -> Result<Vec<event::ReviewComment>, Box<dyn Error>> {
+  let route = format!("/repos/{}/{}/pulls/{}/comments", owner, repo, pr_number);
+  let mut comments = Vec::new();
+  for page in 1.. {
+    let query = json!({ "per_page": 100, "page": page });
+    let batch: Vec<event::ReviewComment> = octo.get(&route, Some(&query)).await?;
+    let last = batch.len() < 100;
+    comments.extend(batch);
+    if last {
+      break;
+    }
+  }
+  Ok(comments)
+}
+
+/// Reply in the thread started by review comment `comment_id`.
+pub async fn reply_to_review_comment(
+  octo: &Octocrab,
+  owner: &str,
+  repo: &str,
+  pr_number: u64,
+  comment_id: u64,
+  body: &str,
+) -> Result<(), Box<dyn Error>> {
+  let route =
+    format!("/repos/{}/{}/pulls/{}/comments/{}/replies", owner, repo, pr_number, comment_id);
+  let _: Value = octo.post(route, Some(&json!({ "body": body }))).await?;
+  Ok(())
+}
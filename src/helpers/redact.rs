@@ -0,0 +1,87 @@
+// redact.rs
+use serde::Deserialize;
+use std::fmt;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Prefixes of credentials GitHub and Groq hand out.
+const SECRET_PREFIXES: &[&str] =
+  &["ghs_", "ghp_", "gho_", "ghu_", "ghr_", "github_pat_", "gsk_", "sk-"];
+
+/// A credential that never prints its value through `Debug` or `Display`.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+  pub fn new(value: impl Into<String>) -> Self {
+    Secret(value.into())
+  }
+
+  /// Access the raw value. Only call this where the credential is actually sent.
+  pub fn expose(&self) -> &str {
+    &self.0
+  }
+}
+
+impl fmt::Debug for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Secret({})", REDACTED)
+  }
+}
+
+impl fmt::Display for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(REDACTED)
+  }
+}
+
+/// Scrub anything that looks like a token, JWT or API key out of `text`.
+/// Applied to every error message before it is logged or returned to a caller.
+pub fn redact(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut word = String::new();
+  let mut after_bearer = false;
+
+  for c in text.chars() {
+    if is_token_char(c) {
+      word.push(c);
+      continue;
+    }
+    after_bearer = flush_word(&mut out, &mut word, after_bearer);
+    out.push(c);
+  }
+  flush_word(&mut out, &mut word, after_bearer);
+  out
+}
+
+/// Push `word` into `out`, redacted if needed. Returns whether the next word follows `Bearer`.
+fn flush_word(out: &mut String, word: &mut String, after_bearer: bool) -> bool {
+  if word.is_empty() {
+    return after_bearer;
+  }
+
+  let is_scheme = word.eq_ignore_ascii_case("bearer");
+  if (after_bearer && word.len() >= 8) || looks_like_secret(word) {
+    out.push_str(REDACTED);
+  } else {
+    out.push_str(word);
+  }
+  word.clear();
+  is_scheme
+}
+
+fn is_token_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+fn looks_like_secret(word: &str) -> bool {
+  if SECRET_PREFIXES.iter().any(|prefix| word.starts_with(prefix) && word.len() > prefix.len() + 8)
+  {
+    return true;
+  }
+
+  // JWTs are three base64url segments, the first being a JSON header (`{"` encodes to `eyJ`).
+  let segments: Vec<&str> = word.trim_end_matches('.').split('.').collect();
+  segments.len() == 3 && segments[0].starts_with("eyJ") && segments.iter().all(|s| !s.is_empty())
+}
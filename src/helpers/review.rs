@@ -0,0 +1,142 @@
+// review.rs
+use crate::helpers::diff::{FileDiff, LineKind};
+use crate::helpers::findings::{ReviewFinding, ReviewReport, Severity, fenced};
+use crate::helpers::threads::finding_marker;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+  Left,
+  Right,
+}
+
+/// One line-anchored comment in the shape the Pull Request Reviews API expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReviewComment {
+  pub path: String,
+  pub line: u32,
+  pub side: Side,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub start_line: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub start_side: Option<Side>,
+  pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewEvent {
+  Comment,
+  RequestChanges,
+}
+
+/// A pull request review ready to submit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PullRequestReview {
+  pub commit_id: String,
+  pub body: String,
+  pub event: ReviewEvent,
+  pub comments: Vec<ReviewComment>,
+}
+
+/// Index of the hunk showing `line` of `path` in the head version, i.e. where it can carry a
+/// comment. `None` if the line isn't in the diff.
+fn hunk_for(files: &[FileDiff], path: &str, line: u32) -> Option<usize> {
+  files.iter().filter(|file| file.path == path).flat_map(|file| &file.hunks).position(|hunk| {
+    hunk.lines.iter().any(|l| l.kind != LineKind::Removed && l.new_line == Some(line))
+  })
+}
+
+/// Turn a report into a review: findings on lines in the diff become inline comments,
+/// the rest (and any past the first `max_comments`) are listed in the summary body.
+/// Changes are requested when any finding is at least `request_changes_at`.
+pub fn build_review(
+  commit_id: &str,
+  report: &ReviewReport,
+  files: &[FileDiff],
+  request_changes_at: Severity,
+  max_comments: usize,
+) -> PullRequestReview {
+  let mut comments = Vec::new();
+  let mut unanchored = Vec::new();
+  let mut over_limit = Vec::new();
+
+  for finding in &report.findings {
+    if comments.len() >= max_comments {
+      over_limit.push(finding);
+      continue;
+    }
+    match inline_comment(finding, files) {
+      Some(comment) => comments.push(comment),
+      None => unanchored.push(finding),
+    }
+  }
+
+  let mut body = report.summary.clone();
+  if !unanchored.is_empty() {
+    body.push_str("\n\n### Other findings\n");
+    for finding in unanchored {
+      let _ = writeln!(body, "- `{}` {}", finding.location(), finding.headline());
+      if finding.suggested_fix.is_some() {
+        body.push_str("  _Suggested change not attached: the lines are not in the diff._\n");
+      }
+    }
+  }
+  if !over_limit.is_empty() {
+    let _ = write!(body, "\n\n### More findings\n_Only {} are commented inline._\n", max_comments);
+    for finding in over_limit {
+      let _ = writeln!(body, "- `{}` {}", finding.location(), finding.headline());
+    }
+  }
+
+  let event = if report.findings.iter().any(|f| f.severity >= request_changes_at) {
+    ReviewEvent::RequestChanges
+  } else {
+    ReviewEvent::Comment
+  };
+
+  PullRequestReview { commit_id: commit_id.to_string(), body, event, comments }
+}
+
+/// The inline comment for a finding, or `None` if its last line isn't in the diff.
+fn inline_comment(finding: &ReviewFinding, files: &[FileDiff]) -> Option<ReviewComment> {
+  let hunk = hunk_for(files, &finding.file, finding.end_line)?;
+  let ranged = finding.start_line < finding.end_line;
+  // Multi-line comments need both ends in the same hunk; otherwise anchor on the last line.
+  let start_line = Some(finding.start_line)
+    .filter(|&start| ranged && hunk_for(files, &finding.file, start) == Some(hunk));
+
+  let mut body = finding.headline();
+  if let Some(fix) = &finding.suggested_fix {
+    // A suggestion replaces exactly the commented lines, so it only applies if the whole
+    // range the model meant is what the comment covers.
+    if ranged && start_line.is_none() {
+      let _ = write!(
+        body,
+        "\n\n_Suggested change not attached: lines {}-{} are not all in one hunk of the diff._",
+        finding.start_line, finding.end_line
+      );
+    } else {
+      body.push_str("\n\n");
+      body.push_str(&suggestion_block(fix));
+    }
+  }
+  body.push_str("\n\n");
+  body.push_str(&finding_marker(finding.category));
+
+  Some(ReviewComment {
+    path: finding.file.clone(),
+    line: finding.end_line,
+    side: Side::Right,
+    start_line,
+    start_side: start_line.map(|_| Side::Right),
+    body,
+  })
+}
+
+/// Wrap replacement code in a ```` ```suggestion ```` block GitHub can apply in one click.
+pub fn suggestion_block(code: &str) -> String {
+  fenced("suggestion", code)
+}
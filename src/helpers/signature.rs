@@ -0,0 +1,58 @@
+// signature.rs
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header GitHub uses to carry the HMAC-SHA256 of the raw request body.
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+  MissingSecret,
+  MissingSignature,
+  MalformedSignature,
+  Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SignatureError::MissingSecret => write!(f, "webhook secret is not configured"),
+      SignatureError::MissingSignature => write!(f, "missing {} header", SIGNATURE_HEADER),
+      SignatureError::MalformedSignature => write!(f, "malformed {} header", SIGNATURE_HEADER),
+      SignatureError::Mismatch => write!(f, "signature does not match payload"),
+    }
+  }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Verify a `sha256=<hex>` signature header against the raw body.
+/// The digest comparison is constant-time.
+pub fn verify_signature(
+  secret: &[u8],
+  body: &[u8],
+  signature: Option<&str>,
+) -> Result<(), SignatureError> {
+  if secret.is_empty() {
+    return Err(SignatureError::MissingSecret);
+  }
+
+  let signature = signature.ok_or(SignatureError::MissingSignature)?;
+  let hex_digest =
+    signature.trim().strip_prefix("sha256=").ok_or(SignatureError::MalformedSignature)?;
+  let expected = hex::decode(hex_digest).map_err(|_| SignatureError::MalformedSignature)?;
+
+  let mut mac = HmacSha256::new_from_slice(secret).map_err(|_| SignatureError::MissingSecret)?;
+  mac.update(body);
+  mac.verify_slice(&expected).map_err(|_| SignatureError::Mismatch)
+}
+
+/// Compute the `sha256=<hex>` header value GitHub would send for `body`.
+pub fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+  let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+  mac.update(body);
+  format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
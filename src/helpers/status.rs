@@ -0,0 +1,84 @@
+// status.rs
+use crate::helpers::findings::{ReviewReport, Severity};
+use serde::Serialize;
+
+/// GitHub cuts commit status descriptions off at this many characters.
+const MAX_DESCRIPTION_CHARS: usize = 140;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitState {
+  Pending,
+  Success,
+  Failure,
+  Error,
+}
+
+/// A commit status in the shape the Statuses API expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitStatus {
+  pub state: CommitState,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub target_url: Option<String>,
+  pub description: String,
+  pub context: String,
+}
+
+impl CommitStatus {
+  pub fn new(
+    context: &str,
+    state: CommitState,
+    description: &str,
+    target_url: Option<String>,
+  ) -> CommitStatus {
+    let description: String = description.chars().take(MAX_DESCRIPTION_CHARS).collect();
+    let description = description.trim_end().to_string();
+    CommitStatus { state, target_url, description, context: context.to_string() }
+  }
+
+  pub fn queued(context: &str, target_url: Option<String>) -> CommitStatus {
+    CommitStatus::new(context, CommitState::Pending, "Queued for review", target_url)
+  }
+
+  /// A review that could not be completed.
+  pub fn errored(context: &str, message: &str, target_url: Option<String>) -> CommitStatus {
+    let description = format!("Review failed: {}", message);
+    CommitStatus::new(context, CommitState::Error, &description, target_url)
+  }
+
+  /// A commit with nothing to review.
+  pub fn no_changes(context: &str, target_url: Option<String>) -> CommitStatus {
+    CommitStatus::new(context, CommitState::Success, "No changes to review", target_url)
+  }
+
+  /// The commit was left unreviewed with `/mechanic skip`.
+  pub fn skipped(context: &str, login: &str, target_url: Option<String>) -> CommitStatus {
+    let description = format!("Review skipped by @{}", login);
+    CommitStatus::new(context, CommitState::Success, &description, target_url)
+  }
+
+  /// A queued review that was then not run, e.g. because the pull request was paused.
+  pub fn not_reviewed(context: &str, reason: &str, target_url: Option<String>) -> CommitStatus {
+    let description = format!("Not reviewed: {}", reason);
+    CommitStatus::new(context, CommitState::Success, &description, target_url)
+  }
+
+  /// Fail when any finding is at least `fail_at`, succeed otherwise.
+  pub fn for_report(
+    context: &str,
+    report: &ReviewReport,
+    fail_at: Severity,
+    target_url: Option<String>,
+  ) -> CommitStatus {
+    let worst = report.findings.iter().map(|f| f.severity).max();
+    let state = match worst {
+      Some(worst) if worst >= fail_at => CommitState::Failure,
+      _ => CommitState::Success,
+    };
+    let description = match worst {
+      None => "No findings".to_string(),
+      Some(worst) => format!("{} finding(s), worst: {}", report.findings.len(), worst.as_str()),
+    };
+    CommitStatus::new(context, state, &description, target_url)
+  }
+}
@@ -0,0 +1,94 @@
+// sticky.rs
+use std::fmt::Write as _;
+
+/// Hidden first line of the one comment Mechanic keeps up to date on each pull request.
+pub const STICKY_MARKER: &str = "<!-- mechanic:sticky -->";
+/// Earlier commits' results kept in the comment; older ones are dropped.
+const MAX_HISTORY: usize = 10;
+/// GitHub rejects comment bodies longer than this.
+const MAX_COMMENT_CHARS: usize = 65_536;
+
+/// Ends the newest entry when it alone is too long for a comment.
+const TRUNCATED: &str = "\n\n_Truncated: this review is too long for one comment._";
+
+const ENTRY_START: &str = "<!-- mechanic:entry ";
+const ENTRY_END: &str = "<!-- mechanic:entry-end -->";
+
+/// The result for one commit, as shown in the sticky comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StickyEntry {
+  pub commit_sha: String,
+  pub markdown: String,
+}
+
+/// Read the entries back out of a sticky comment, newest first.
+/// Anything outside the entry markers is layout and is rebuilt by [`render_sticky`].
+pub fn parse_sticky(body: &str) -> Vec<StickyEntry> {
+  let mut entries = Vec::new();
+  let mut rest = body;
+  while let Some(start) = rest.find(ENTRY_START) {
+    let after = &rest[start + ENTRY_START.len()..];
+    let Some((commit_sha, after)) = after.split_once(" -->") else { break };
+    let Some(end) = after.find(ENTRY_END) else { break };
+    let markdown = after[..end].trim().to_string();
+    entries.push(StickyEntry { commit_sha: commit_sha.trim().to_string(), markdown });
+    rest = &after[end + ENTRY_END.len()..];
+  }
+  entries
+}
+
+/// Put `latest` on top of the entries from `previous` (the current comment body, if any).
+/// A commit reviewed again replaces its old entry instead of adding another. Older entries
+/// are dropped to fit a comment, and the newest is cut short if it still doesn't.
+pub fn update_sticky(previous: Option<&str>, latest: StickyEntry) -> String {
+  let earlier = previous.map(parse_sticky).unwrap_or_default();
+  let sha = latest.commit_sha.clone();
+  let earlier = earlier.into_iter().filter(|e| e.commit_sha != sha);
+  let mut entries: Vec<StickyEntry> = std::iter::once(latest).chain(earlier).collect();
+  entries.truncate(MAX_HISTORY + 1);
+
+  let mut body = render_sticky(&entries);
+  while body.chars().count() > MAX_COMMENT_CHARS && entries.len() > 1 {
+    entries.pop();
+    body = render_sticky(&entries);
+  }
+  if body.chars().count() > MAX_COMMENT_CHARS {
+    let latest = &mut entries[0];
+    let over = body.chars().count() - MAX_COMMENT_CHARS + TRUNCATED.chars().count();
+    let keep = latest.markdown.trim().chars().count().saturating_sub(over);
+    latest.markdown = latest.markdown.trim().chars().take(keep).collect::<String>() + TRUNCATED;
+    body = render_sticky(&entries);
+  }
+  body
+}
+
+/// The comment body: the newest entry in full, earlier ones folded away under it.
+pub fn render_sticky(entries: &[StickyEntry]) -> String {
+  let mut body = format!("{}\n", STICKY_MARKER);
+  let Some((latest, earlier)) = entries.split_first() else { return body };
+
+  write_entry(&mut body, latest);
+  let _ = writeln!(body, "\n<sub>Latest review: commit `{}`.</sub>", short_sha(&latest.commit_sha));
+  if earlier.is_empty() {
+    return body;
+  }
+
+  let _ = writeln!(body, "\n<details>\n<summary>Earlier commits ({})</summary>\n", earlier.len());
+  for entry in earlier {
+    let _ = writeln!(body, "### Commit `{}`\n", short_sha(&entry.commit_sha));
+    write_entry(&mut body, entry);
+    body.push('\n');
+  }
+  body.push_str("</details>\n");
+  body
+}
+
+fn write_entry(body: &mut String, entry: &StickyEntry) {
+  let _ = writeln!(body, "{}{} -->", ENTRY_START, entry.commit_sha);
+  let _ = writeln!(body, "{}", entry.markdown.trim());
+  let _ = writeln!(body, "{}", ENTRY_END);
+}
+
+fn short_sha(sha: &str) -> &str {
+  sha.get(..7).unwrap_or(sha)
+}
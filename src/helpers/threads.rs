@@ -0,0 +1,88 @@
+// threads.rs
+use crate::helpers::findings::{Category, ReviewFinding, ReviewReport};
+
+const FINDING_MARKER_START: &str = "<!-- mechanic:finding ";
+/// Tags the reply left on a fixed thread that couldn't be resolved, so it's only left once.
+pub const ADDRESSED_MARKER: &str = "<!-- mechanic:addressed -->";
+/// Tags Mechanic's reply agreeing that a thread's finding doesn't apply.
+pub const DISMISSED_MARKER: &str = "<!-- mechanic:dismissed -->";
+/// A re-reported finding may land a few lines away from where it was first flagged.
+const NEARBY_LINES: u32 = 3;
+
+/// Hidden tag appended to each inline comment so later runs can tell their own threads apart.
+pub fn finding_marker(category: Category) -> String {
+  format!("{}{} -->", FINDING_MARKER_START, category.as_str())
+}
+
+/// The category tagged on a comment by [`finding_marker`], if it is one of Mechanic's.
+pub fn parse_finding_marker(body: &str) -> Option<Category> {
+  let start = body.find(FINDING_MARKER_START)? + FINDING_MARKER_START.len();
+  let (category, _) = body[start..].split_once(" -->")?;
+  Some(Category::parse(category))
+}
+
+/// A review thread on the pull request, as returned by the GraphQL API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewThread {
+  /// GraphQL node id, used to resolve or reply to the thread.
+  pub id: String,
+  pub path: String,
+  /// Line on the head commit; `None` once the thread is outdated.
+  pub line: Option<u32>,
+  /// Line on the commit the thread was started on.
+  pub original_line: Option<u32>,
+  pub is_resolved: bool,
+  /// The lines under the thread changed since it was started.
+  pub is_outdated: bool,
+  /// The finding's category if the thread was started by Mechanic.
+  pub category: Option<Category>,
+  /// Mechanic already replied somewhere in the thread that it looks addressed.
+  pub marked_addressed: bool,
+  /// REST id of the thread's first comment, which replies point at.
+  pub root_comment_id: Option<u64>,
+  /// One of Mechanic's replies agreed the finding doesn't apply, however the thread went on.
+  pub dismissed: bool,
+}
+
+impl ReviewThread {
+  /// Whether `finding` is the thread's finding again: same file and category, about the same
+  /// lines.
+  fn flags_again(&self, finding: &ReviewFinding) -> bool {
+    let Some(line) = self.line.or(self.original_line) else { return false };
+    Some(finding.category) == self.category
+      && finding.file == self.path
+      && finding.start_line.saturating_sub(NEARBY_LINES) <= line
+      && line <= finding.end_line + NEARBY_LINES
+  }
+}
+
+/// Mechanic's open threads that a later push has fixed: the code under them changed and the
+/// new report doesn't flag the same kind of problem near the same place.
+/// Threads whose code didn't change are left alone even if the model didn't repeat them, and so
+/// are threads on files outside `reviewed`, which the model didn't see whole.
+pub fn fixed_threads<'a>(
+  threads: &'a [ReviewThread],
+  report: &ReviewReport,
+  reviewed: &[&str],
+) -> Vec<&'a ReviewThread> {
+  threads
+    .iter()
+    .filter(|thread| !thread.is_resolved && !thread.marked_addressed && thread.is_outdated)
+    .filter(|thread| reviewed.contains(&thread.path.as_str()))
+    .filter(|thread| {
+      thread.category.is_some() && !report.findings.iter().any(|f| thread.flags_again(f))
+    })
+    .collect()
+}
+
+/// Drop findings a developer already talked Mechanic out of in a review thread, so they aren't
+/// raised again on every push.
+pub fn drop_dismissed(report: &mut ReviewReport, threads: &[ReviewThread]) {
+  let dismissed: Vec<&ReviewThread> = threads.iter().filter(|thread| thread.dismissed).collect();
+  report.findings.retain(|finding| !dismissed.iter().any(|thread| thread.flags_again(finding)));
+}
+
+/// Reply left on a fixed thread when it can't be resolved.
+pub fn addressed_reply(commit_sha: &str) -> String {
+  format!("This looks addressed in {}.\n\n{}", commit_sha, ADDRESSED_MARKER)
+}
@@ -1,3 +1,6 @@
+pub mod config;
+pub mod fake_github;
 pub mod handlers;
 pub mod helpers;
 pub mod services;
+pub mod state;
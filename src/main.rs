@@ -1,17 +1,109 @@
 // main.rs
-use axum::{Router, routing::post};
-use std::net::SocketAddr;
+use axum::{
+  Router,
+  routing::{get, post},
+};
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+use treehacks25::config::Config;
+use treehacks25::handlers::queue::{get_job_handler, list_jobs_handler, queue_status_handler};
 use treehacks25::handlers::webhook::github_wh_test_handler;
+use treehacks25::services::controls::PullRequestControls;
+use treehacks25::services::dedup::DeliveryLedger;
+use treehacks25::services::llm::provider_from_config;
+use treehacks25::services::policy::PolicyCache;
+use treehacks25::services::queue::{JobQueue, ReviewJob, spawn_workers};
+use treehacks25::services::store::JobStore;
+use treehacks25::services::tokens::InstallationClients;
+use treehacks25::state::AppState;
+
+const USAGE: &str = "usage: treehacks25 [--config <path>]";
+
+/// Parse the command line. The only flag is `--config <path>`.
+fn config_path_from_args() -> Option<PathBuf> {
+  let mut args = std::env::args().skip(1);
+  let mut config_path = None;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--config" => match args.next() {
+        Some(path) => config_path = Some(PathBuf::from(path)),
+        None => {
+          eprintln!("--config needs a path\n{}", USAGE);
+          exit(2);
+        }
+      },
+      "-h" | "--help" => {
+        println!("{}", USAGE);
+        exit(0);
+      }
+      other => match other.strip_prefix("--config=") {
+        Some(path) => config_path = Some(PathBuf::from(path)),
+        None => {
+          eprintln!("unexpected argument {:?}\n{}", other, USAGE);
+          exit(2);
+        }
+      },
+    }
+  }
+  config_path
+}
 
 // Build and serve the Axum app.
-pub async fn serve() {
+pub async fn serve(config: Config) {
+  let config = Arc::new(config);
+  let store = JobStore::open_with_retention(&config.server.job_log, config.server.job_retention())
+    .expect("Failed to open job store");
+  let ledger = DeliveryLedger::new(config.review.dedup_retention());
+  ledger.seed(&store.list());
+  let controls = PullRequestControls::with_store(store.clone());
+  let (queue, receiver) = JobQueue::new(config.review.queue_capacity);
+  let clients = InstallationClients::new(config.github.clone());
+  let llm = provider_from_config(&config.llm);
+  println!(
+    "Reviewing with {} model {} at {}.",
+    llm.name(),
+    config.llm.model,
+    config.llm.base_url()
+  );
+  let state = AppState {
+    queue: queue.clone(),
+    store: store.clone(),
+    ledger,
+    config: config.clone(),
+    clients,
+    policies: PolicyCache::new(),
+    controls,
+    llm,
+  };
+  spawn_workers(&state, receiver, config.review.workers);
+
+  // Pick up whatever was queued or running when the server last stopped.
+  let pending = store.pending();
+  if !pending.is_empty() {
+    println!("Resuming {} unfinished review job(s).", pending.len());
+  }
+  tokio::spawn(async move {
+    for record in pending {
+      let job = ReviewJob { id: record.id, delivery_id: record.delivery_id, event: record.event };
+      if let Err(e) = queue.enqueue_wait(job).await {
+        println!("Failed to resume review job: {}", e);
+      }
+    }
+  });
+
   let app = Router::new()
     // New route to trigger our GitHub event sending.
     // .route("/send-event", post(send_github_event_handler))
     // Your original webhook test route.
-    .route("/github-wh-test", post(github_wh_test_handler));
+    .route("/github-wh-test", post(github_wh_test_handler))
+    .route("/queue", get(queue_status_handler))
+    .route("/jobs", get(list_jobs_handler))
+    .route("/jobs/{id}", get(get_job_handler))
+    .with_state(state);
 
-  let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+  let addr = config.server.bind;
 
   println!("Successfully listening on {}. You can now make requests.", addr);
   axum_server::bind(addr).serve(app.into_make_service()).await.unwrap();
@@ -19,5 +111,12 @@ pub async fn serve() {
 
 #[tokio::main]
 async fn main() {
-  serve().await;
+  let config = match Config::load(config_path_from_args().as_deref()) {
+    Ok(config) => config,
+    Err(e) => {
+      eprintln!("{}", e);
+      exit(1);
+    }
+  };
+  serve(config).await;
 }
@@ -0,0 +1,73 @@
+// controls.rs
+use crate::helpers::event::GitHubEvent;
+use crate::services::dedup::CommitKey;
+use crate::services::store::JobStore;
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct Controls {
+  /// (`owner/repo`, number) of pull requests paused with `/mechanic pause`.
+  paused: HashSet<(String, u64)>,
+  /// Head commits skipped with `/mechanic skip`.
+  skipped: HashSet<CommitKey>,
+}
+
+/// What collaborators switched off with slash commands, checked before every automatic review.
+#[derive(Clone, Default)]
+pub struct PullRequestControls {
+  inner: Arc<Mutex<Controls>>,
+  /// Where pauses are kept so paused pull requests stay paused after a restart. Skipped
+  /// commits are only remembered until then.
+  store: Option<JobStore>,
+}
+
+impl PullRequestControls {
+  /// Controls kept in memory only.
+  pub fn new() -> PullRequestControls {
+    PullRequestControls::default()
+  }
+
+  /// Controls starting from the pull requests paused in `store`, recording later pauses and
+  /// resumes there.
+  pub fn with_store(store: JobStore) -> PullRequestControls {
+    let paused = store.paused().into_iter().collect();
+    let controls = Controls { paused, skipped: HashSet::new() };
+    PullRequestControls { inner: Arc::new(Mutex::new(controls)), store: Some(store) }
+  }
+
+  pub fn pause(&self, repo: &str, pull_number: u64) -> Result<(), Box<dyn Error>> {
+    if let Some(store) = &self.store {
+      store.set_paused(repo, pull_number, true)?;
+    }
+    self.inner.lock().unwrap().paused.insert((repo.to_string(), pull_number));
+    Ok(())
+  }
+
+  pub fn resume(&self, repo: &str, pull_number: u64) -> Result<(), Box<dyn Error>> {
+    if let Some(store) = &self.store {
+      store.set_paused(repo, pull_number, false)?;
+    }
+    self.inner.lock().unwrap().paused.remove(&(repo.to_string(), pull_number));
+    Ok(())
+  }
+
+  pub fn is_paused(&self, repo: &str, pull_number: u64) -> bool {
+    self.inner.lock().unwrap().paused.contains(&(repo.to_string(), pull_number))
+  }
+
+  pub fn skip(&self, commit: CommitKey) {
+    self.inner.lock().unwrap().skipped.insert(commit);
+  }
+
+  /// Whether the automatic review `event` asks for was switched off: its pull request is
+  /// paused or its commit skipped. Commands themselves are never held.
+  pub fn holds(&self, event: &GitHubEvent) -> bool {
+    let GitHubEvent::PullRequest(_) = event else { return false };
+    let Some(commit) = CommitKey::for_event(event) else { return false };
+    let controls = self.inner.lock().unwrap();
+    controls.skipped.contains(&commit)
+      || controls.paused.contains(&(commit.repo.clone(), commit.pull_number))
+  }
+}
@@ -0,0 +1,129 @@
+// conversation.rs
+use crate::config::LlmConfig;
+use crate::helpers::artifacts::Artifacts;
+use crate::helpers::findings::extract_json_object;
+use crate::services::llm::{ChatMessage, ChatRequest, LlmProvider};
+use crate::services::policy::RepoPolicy;
+use serde_json::{Value, json};
+use std::error::Error;
+
+/// One comment in a review thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadMessage {
+  pub author: String,
+  /// Written by Mechanic rather than a person.
+  pub from_mechanic: bool,
+  pub body: String,
+}
+
+/// Mechanic's answer in a review thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadReply {
+  pub reply: String,
+  /// The developer showed the finding doesn't apply; it shouldn't be raised again.
+  pub dismiss: bool,
+}
+
+pub fn thread_reply_schema() -> Value {
+  json!({
+    "type": "object",
+    "additionalProperties": false,
+    "required": ["reply", "dismiss"],
+    "properties": {
+      "reply": { "type": "string" },
+      "dismiss": { "type": "boolean" }
+    }
+  })
+}
+
+/// The conversation sent to the model: what it's looking at, then the thread so far with
+/// Mechanic's comments as its own turns. `thread` is oldest first and ends with the reply to
+/// answer.
+pub fn thread_messages(
+  path: &str,
+  diff_hunk: &str,
+  thread: &[ThreadMessage],
+  policy: &RepoPolicy,
+) -> Vec<ChatMessage> {
+  let mut prompt = format!(
+    "You are Mechanic, an automated code reviewer. You started this review thread on `{}` \
+      with a finding, and a developer has replied. Answer their latest message in a few \
+      sentences of GitHub-flavoured Markdown: explain the problem if they ask why, and agree \
+      when they show the code is intentional or the finding is wrong.
+
+      **Return JSON only**, matching this schema:
+      {}
+
+      - `reply`: your answer to the developer.
+      - `dismiss`: `true` only if the finding doesn't apply and shouldn't be raised again.
+
+      The code the thread is about:\n\n```diff\n{}\n```",
+    path,
+    thread_reply_schema(),
+    diff_hunk.trim_end()
+  );
+  if let Some(instructions) = &policy.instructions {
+    prompt.push_str("\n\nThe repository's maintainers ask reviewers to keep in mind:\n\n");
+    prompt.push_str(instructions.trim());
+  }
+
+  let mut messages = vec![ChatMessage::system(prompt)];
+  for message in thread {
+    let body = without_markers(&message.body);
+    messages.push(match message.from_mechanic {
+      true => ChatMessage::assistant(body),
+      false => ChatMessage::user(format!("@{}: {}", message.author, body)),
+    });
+  }
+  messages
+}
+
+/// Posted instead of a reply that isn't the JSON asked for, which may be anything from a
+/// half-finished thought to the prompt echoed back.
+pub const FALLBACK_REPLY: &str =
+  "Sorry, I couldn't put an answer together for this one. Reply again and I'll have another go.";
+
+/// The model's answer to a thread. A reply that isn't the JSON asked for is logged and answered
+/// with [`FALLBACK_REPLY`], without dismissing; an empty one is an error.
+pub fn parse_thread_reply(reply: &str) -> Result<ThreadReply, String> {
+  if reply.trim().is_empty() {
+    return Err("the model's reply was empty".to_string());
+  }
+  let parsed = extract_json_object(reply).and_then(|text| serde_json::from_str::<Value>(text).ok());
+  let Some(value) = parsed.filter(|value| value["reply"].is_string()) else {
+    println!("The model's thread reply wasn't the JSON asked for, posting a fallback: {}", reply);
+    return Ok(ThreadReply { reply: FALLBACK_REPLY.to_string(), dismiss: false });
+  };
+  let text = value["reply"].as_str().unwrap_or_default().trim();
+  if text.is_empty() {
+    return Err("the model's reply was empty".to_string());
+  }
+  Ok(ThreadReply { reply: text.to_string(), dismiss: value["dismiss"].as_bool().unwrap_or(false) })
+}
+
+/// **Ask the model to answer the latest reply in one of Mechanic's review threads (Async)**
+pub async fn request_thread_reply(
+  path: &str,
+  diff_hunk: &str,
+  thread: &[ThreadMessage],
+  llm: &LlmConfig,
+  policy: &RepoPolicy,
+  provider: &dyn LlmProvider,
+  artifacts: &Artifacts,
+) -> Result<ThreadReply, Box<dyn Error>> {
+  let request = ChatRequest {
+    messages: thread_messages(path, diff_hunk, thread, policy),
+    max_tokens: llm.max_tokens,
+    schema: Some(thread_reply_schema()),
+    model: Some(policy.model.clone()),
+  };
+  let reply = provider.chat(&request).await?;
+  artifacts.save("thread-reply.json", &reply);
+  Ok(parse_thread_reply(&reply)?)
+}
+
+/// Mechanic's hidden `<!-- mechanic:... -->` tags, which mean nothing to the model.
+fn without_markers(body: &str) -> String {
+  let lines = body.lines().filter(|line| !line.trim_start().starts_with("<!-- mechanic:"));
+  lines.collect::<Vec<_>>().join("\n").trim().to_string()
+}
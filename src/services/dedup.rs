@@ -0,0 +1,127 @@
+// dedup.rs
+use crate::helpers::event::GitHubEvent;
+use crate::services::store::{JobRecord, JobState};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Identifies one commit of one pull request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommitKey {
+  pub repo: String,
+  pub pull_number: u64,
+  pub sha: String,
+}
+
+impl CommitKey {
+  pub fn for_event(event: &GitHubEvent) -> Option<CommitKey> {
+    Some(CommitKey {
+      repo: event.repository()?.full_name.clone(),
+      pull_number: event.pull_number()?,
+      sha: event.commit_sha()?.to_string(),
+    })
+  }
+}
+
+/// Why a delivery was not queued.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Duplicate {
+  /// The same `X-GitHub-Delivery` id was seen within the retention window.
+  Delivery,
+  /// This commit of this pull request is already queued, running or reviewed.
+  Commit,
+}
+
+#[derive(Default)]
+struct Ledger {
+  /// Delivery id -> unix time it was first seen.
+  deliveries: HashMap<String, i64>,
+  /// Commits that are queued, running or reviewed -> unix time they were claimed.
+  commits: HashMap<CommitKey, i64>,
+  /// Latest head seen per pull request -> unix time it was seen, used to drop jobs for
+  /// superseded commits.
+  heads: HashMap<(String, u64), (String, i64)>,
+}
+
+/// Remembers recent deliveries and reviewed commits so redeliveries don't post duplicate reviews.
+/// Everything it remembers is forgotten once older than the retention.
+#[derive(Clone)]
+pub struct DeliveryLedger {
+  inner: Arc<Mutex<Ledger>>,
+  retention: Duration,
+}
+
+impl DeliveryLedger {
+  pub fn new(retention: Duration) -> DeliveryLedger {
+    DeliveryLedger { inner: Arc::new(Mutex::new(Ledger::default())), retention }
+  }
+
+  /// Rebuild the ledger from the job store so duplicates are still caught after a restart.
+  pub fn seed(&self, jobs: &[JobRecord]) {
+    let cutoff = self.cutoff();
+    let mut ledger = self.inner.lock().unwrap();
+
+    let mut jobs: Vec<&JobRecord> = jobs.iter().filter(|job| job.created_at >= cutoff).collect();
+    jobs.sort_by_key(|job| job.created_at);
+
+    for job in jobs {
+      if let Some(delivery) = &job.delivery_id {
+        ledger.deliveries.insert(delivery.clone(), job.created_at);
+      }
+      let Some(key) = CommitKey::for_event(&job.event) else { continue };
+      if !matches!(job.state, JobState::Failed { .. }) {
+        ledger.commits.insert(key.clone(), job.created_at);
+      }
+      ledger.heads.insert((key.repo, key.pull_number), (key.sha, job.created_at));
+    }
+  }
+
+  /// Record a delivery and, for pull requests, claim its commit for review.
+  /// Returns why the delivery is a duplicate, or `None` if it should be queued.
+  pub fn admit(&self, delivery_id: Option<&str>, event: &GitHubEvent) -> Option<Duplicate> {
+    let now = Utc::now().timestamp();
+    let cutoff = self.cutoff();
+    let mut ledger = self.inner.lock().unwrap();
+    ledger.deliveries.retain(|_, seen| *seen >= cutoff);
+    ledger.commits.retain(|_, seen| *seen >= cutoff);
+    ledger.heads.retain(|_, (_, seen)| *seen >= cutoff);
+
+    if let Some(delivery) = delivery_id {
+      if ledger.deliveries.contains_key(delivery) {
+        return Some(Duplicate::Delivery);
+      }
+      ledger.deliveries.insert(delivery.to_string(), now);
+    }
+
+    let key = CommitKey::for_event(event)?;
+    if ledger.commits.contains_key(&key) {
+      return Some(Duplicate::Commit);
+    }
+    ledger.heads.insert((key.repo.clone(), key.pull_number), (key.sha.clone(), now));
+    ledger.commits.insert(key, now);
+    None
+  }
+
+  /// Whether a newer commit has been pushed to the pull request since `event` was queued.
+  pub fn is_superseded(&self, event: &GitHubEvent) -> bool {
+    let Some(key) = CommitKey::for_event(event) else { return false };
+    let ledger = self.inner.lock().unwrap();
+    ledger.heads.get(&(key.repo, key.pull_number)).is_some_and(|(head, _)| *head != key.sha)
+  }
+
+  /// Forget a delivery and its commit claim after a failed review so a redelivery can try again.
+  pub fn release(&self, delivery_id: Option<&str>, event: &GitHubEvent) {
+    let mut ledger = self.inner.lock().unwrap();
+    if let Some(delivery) = delivery_id {
+      ledger.deliveries.remove(delivery);
+    }
+    if let Some(key) = CommitKey::for_event(event) {
+      ledger.commits.remove(&key);
+    }
+  }
+
+  fn cutoff(&self) -> i64 {
+    Utc::now().timestamp() - self.retention.as_secs() as i64
+  }
+}
@@ -0,0 +1,43 @@
+// groq.rs
+use super::openai::OpenAiCompatible;
+use super::{ChatRequest, LlmError, LlmProvider};
+use crate::config::LlmConfig;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+/// Groq's hosted models, through its OpenAI-compatible endpoint.
+pub struct Groq(OpenAiCompatible);
+
+impl Groq {
+  pub fn new(llm: &LlmConfig) -> Groq {
+    Groq(OpenAiCompatible::variant(llm, "groq", extra))
+  }
+}
+
+/// Reasoning models would otherwise put their `<think>` trace in the reply. Groq rejects
+/// `reasoning_format` for every other model.
+fn extra(model: &str) -> Map<String, Value> {
+  let mut extra = Map::new();
+  if REASONING_MODELS.iter().any(|family| model.contains(family)) {
+    extra.insert("reasoning_format".to_string(), Value::from("hidden"));
+  }
+  extra
+}
+
+/// Name fragments of the Groq models that take `reasoning_format`.
+const REASONING_MODELS: &[&str] = &["deepseek-r1", "qwq", "qwen3"];
+
+#[async_trait]
+impl LlmProvider for Groq {
+  fn name(&self) -> &str {
+    self.0.name()
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    self.0.chat(request).await
+  }
+
+  fn max_context(&self) -> usize {
+    self.0.max_context()
+  }
+}
@@ -0,0 +1,130 @@
+// mock.rs
+use super::{ChatMessage, ChatRequest, LlmError, LlmProvider};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A recorded completion, stored as `<key>.json` where `key` is [`prompt_key`].
+/// The messages are kept only so recordings can be read and reviewed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+  pub messages: Vec<ChatMessage>,
+  pub reply: String,
+}
+
+/// Stable key for a conversation: the first 16 hex digits of the SHA-256 of its messages.
+pub fn prompt_key(messages: &[ChatMessage]) -> String {
+  let json = serde_json::to_vec(messages).unwrap_or_default();
+  hex::encode(Sha256::digest(json))[..16].to_string()
+}
+
+/// Replays recorded completions instead of calling a model, so reviews run offline and
+/// deterministically. An unrecorded prompt is an error naming its key.
+#[derive(Clone)]
+pub struct MockProvider {
+  replies: Arc<Mutex<HashMap<String, String>>>,
+  context_window: usize,
+}
+
+impl MockProvider {
+  pub fn new() -> MockProvider {
+    MockProvider { replies: Arc::default(), context_window: 131_072 }
+  }
+
+  /// Load every `*.json` recording in `dir`. Unreadable files are skipped with a message.
+  pub fn from_dir(dir: &Path) -> MockProvider {
+    let mock = MockProvider::new();
+    let entries = match std::fs::read_dir(dir) {
+      Ok(entries) => entries,
+      Err(e) => {
+        println!("No recordings loaded from {}: {}", dir.display(), e);
+        return mock;
+      }
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+      let Some(key) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+      if path.extension().is_none_or(|ext| ext != "json") {
+        continue;
+      }
+      match std::fs::read_to_string(&path).map(|text| serde_json::from_str::<Recording>(&text)) {
+        Ok(Ok(recording)) => mock.insert(key, recording.reply),
+        Ok(Err(e)) => println!("Skipping recording {}: {}", path.display(), e),
+        Err(e) => println!("Skipping recording {}: {}", path.display(), e),
+      }
+    }
+    mock
+  }
+
+  /// Reply with `reply` whenever the conversation hashes to `key`.
+  pub fn insert(&self, key: &str, reply: impl Into<String>) {
+    self.replies.lock().unwrap().insert(key.to_string(), reply.into());
+  }
+}
+
+impl Default for MockProvider {
+  fn default() -> Self {
+    MockProvider::new()
+  }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+  fn name(&self) -> &str {
+    "mock"
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    let key = prompt_key(&request.messages);
+    let reply = self.replies.lock().unwrap().get(&key).cloned();
+    reply.ok_or(LlmError::NotRecorded { key })
+  }
+
+  fn max_context(&self) -> usize {
+    self.context_window
+  }
+}
+
+/// Wraps a real provider and saves each completion as a recording in `dir`, for
+/// [`MockProvider::from_dir`] to replay later.
+pub struct Recorder {
+  inner: Arc<dyn LlmProvider>,
+  dir: PathBuf,
+}
+
+impl Recorder {
+  pub fn new(inner: Arc<dyn LlmProvider>, dir: PathBuf) -> Recorder {
+    Recorder { inner, dir }
+  }
+}
+
+#[async_trait]
+impl LlmProvider for Recorder {
+  fn name(&self) -> &str {
+    self.inner.name()
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    let reply = self.inner.chat(request).await?;
+    let recording = Recording { messages: request.messages.clone(), reply: reply.clone() };
+    let path = self.dir.join(format!("{}.json", prompt_key(&request.messages)));
+    let saved = std::fs::create_dir_all(&self.dir).and_then(|_| {
+      std::fs::write(&path, serde_json::to_string_pretty(&recording).unwrap_or_default())
+    });
+    match saved {
+      Ok(()) => println!("Recorded completion {}", path.display()),
+      Err(e) => println!("Failed to record completion {}: {}", path.display(), e),
+    }
+    Ok(reply)
+  }
+
+  fn count_tokens(&self, text: &str) -> usize {
+    self.inner.count_tokens(text)
+  }
+
+  fn max_context(&self) -> usize {
+    self.inner.max_context()
+  }
+}
@@ -0,0 +1,144 @@
+// mod.rs
+pub mod groq;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+
+use crate::config::{LlmConfig, ProviderKind};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  System,
+  User,
+  Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+  pub role: Role,
+  pub content: String,
+}
+
+impl ChatMessage {
+  pub fn system(content: impl Into<String>) -> ChatMessage {
+    ChatMessage { role: Role::System, content: content.into() }
+  }
+
+  pub fn user(content: impl Into<String>) -> ChatMessage {
+    ChatMessage { role: Role::User, content: content.into() }
+  }
+
+  pub fn assistant(content: impl Into<String>) -> ChatMessage {
+    ChatMessage { role: Role::Assistant, content: content.into() }
+  }
+}
+
+/// One chat completion call.
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+  pub messages: Vec<ChatMessage>,
+  /// Upper bound on the reply.
+  pub max_tokens: u32,
+  /// Ask for a JSON reply matching this schema, as far as the backend can enforce it.
+  pub schema: Option<Value>,
+  /// Use this model instead of the configured one.
+  pub model: Option<String>,
+}
+
+impl ChatRequest {
+  /// The model to ask: the request's own, or `configured`.
+  pub fn model_or<'a>(&'a self, configured: &'a str) -> &'a str {
+    self.model.as_deref().unwrap_or(configured)
+  }
+}
+
+#[derive(Debug)]
+pub enum LlmError {
+  /// The request never got a response.
+  Http(reqwest::Error),
+  /// The backend answered with an error status.
+  Status { status: u16, body: String },
+  /// The response didn't contain a reply.
+  MissingContent,
+  /// The mock provider has no recording for this conversation.
+  NotRecorded { key: String },
+}
+
+impl fmt::Display for LlmError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LlmError::Http(e) => write!(f, "model request failed: {}", e),
+      LlmError::Status { status, body } => {
+        write!(f, "model request failed with status {}: {}", status, body)
+      }
+      LlmError::MissingContent => write!(f, "model response has no message content"),
+      LlmError::NotRecorded { key } => write!(
+        f,
+        "no recorded completion for prompt {} (record one by running with llm.recordings set)",
+        key
+      ),
+    }
+  }
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<reqwest::Error> for LlmError {
+  fn from(e: reqwest::Error) -> Self {
+    LlmError::Http(e)
+  }
+}
+
+/// A chat model the reviewer can talk to.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+  /// Short name for logs, e.g. `groq`.
+  fn name(&self) -> &str;
+
+  /// Send the conversation and return the reply text.
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError>;
+
+  /// Estimate how many tokens `text` takes. Roughly four characters per token for code and
+  /// English, which is close enough for budgeting without shipping each model's tokenizer.
+  fn count_tokens(&self, text: &str) -> usize {
+    text.len().div_ceil(4)
+  }
+
+  /// Context window in tokens, shared by the prompt and the reply.
+  fn max_context(&self) -> usize;
+}
+
+/// Build the provider selected in the config. With `llm.recordings` set, the mock provider
+/// replays completions from that directory and every other provider saves its completions there.
+pub fn provider_from_config(llm: &LlmConfig) -> Arc<dyn LlmProvider> {
+  let provider: Arc<dyn LlmProvider> = match llm.provider {
+    ProviderKind::Groq => Arc::new(groq::Groq::new(llm)),
+    ProviderKind::OpenAi => Arc::new(openai::OpenAiCompatible::new(llm)),
+    ProviderKind::Ollama => Arc::new(ollama::Ollama::new(llm)),
+    ProviderKind::Mock => {
+      let dir = llm.recordings.as_deref().unwrap_or(Path::new(""));
+      return Arc::new(mock::MockProvider::from_dir(dir));
+    }
+  };
+  match &llm.recordings {
+    Some(dir) => Arc::new(mock::Recorder::new(provider, dir.clone())),
+    None => provider,
+  }
+}
+
+/// Turn an error status into [`LlmError::Status`], keeping the body for the logs.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, LlmError> {
+  let status = response.status();
+  if status.is_success() {
+    return Ok(response);
+  }
+  let body = response.text().await.unwrap_or_default();
+  Err(LlmError::Status { status: status.as_u16(), body })
+}
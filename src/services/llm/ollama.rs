@@ -0,0 +1,65 @@
+// ollama.rs
+use super::{ChatRequest, LlmError, LlmProvider, check_status};
+use crate::config::{LlmConfig, ResponseFormat};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{Value, json};
+
+/// A local Ollama server, through its native `/api/chat` endpoint.
+pub struct Ollama {
+  client: Client,
+  base_url: String,
+  model: String,
+  response_format: ResponseFormat,
+  context_window: usize,
+}
+
+impl Ollama {
+  pub fn new(llm: &LlmConfig) -> Ollama {
+    Ollama {
+      client: Client::new(),
+      base_url: llm.base_url().trim_end_matches('/').to_string(),
+      model: llm.model.clone(),
+      response_format: llm.response_format,
+      context_window: llm.context_window(),
+    }
+  }
+
+  fn body(&self, request: &ChatRequest) -> Value {
+    let mut body = json!({
+        "model": request.model_or(&self.model),
+        "messages": request.messages,
+        "stream": false,
+        // Ollama loads models with a small context unless told otherwise.
+        "options": { "num_predict": request.max_tokens, "num_ctx": self.context_window },
+    });
+    if let Some(schema) = &request.schema {
+      body["format"] = match self.response_format {
+        ResponseFormat::JsonObject => Value::from("json"),
+        ResponseFormat::JsonSchema => schema.clone(),
+      };
+    }
+    body
+  }
+}
+
+#[async_trait]
+impl LlmProvider for Ollama {
+  fn name(&self) -> &str {
+    "ollama"
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    let url = format!("{}/api/chat", self.base_url);
+    let response =
+      check_status(self.client.post(url).json(&self.body(request)).send().await?).await?;
+
+    let response_json: Value = response.json().await?;
+    let content = response_json["message"]["content"].as_str();
+    content.map(str::to_string).ok_or(LlmError::MissingContent)
+  }
+
+  fn max_context(&self) -> usize {
+    self.context_window
+  }
+}
@@ -0,0 +1,92 @@
+// openai.rs
+use super::{ChatRequest, LlmError, LlmProvider, check_status};
+use crate::config::{LlmConfig, ResponseFormat};
+use crate::helpers::redact::Secret;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{Map, Value, json};
+
+/// Any server speaking the OpenAI chat completions API: OpenAI itself, llama.cpp's
+/// `llama-server`, vLLM, LM Studio and the like.
+pub struct OpenAiCompatible {
+  client: Client,
+  name: &'static str,
+  base_url: String,
+  api_key: Secret,
+  model: String,
+  response_format: ResponseFormat,
+  context_window: usize,
+  /// Provider-specific fields merged into each request body, given the request's model.
+  extra: fn(&str) -> Map<String, Value>,
+}
+
+impl OpenAiCompatible {
+  pub fn new(llm: &LlmConfig) -> OpenAiCompatible {
+    OpenAiCompatible {
+      client: Client::new(),
+      name: "openai",
+      base_url: llm.base_url().trim_end_matches('/').to_string(),
+      api_key: llm.api_key.clone(),
+      model: llm.model.clone(),
+      response_format: llm.response_format,
+      context_window: llm.context_window(),
+      extra: |_| Map::new(),
+    }
+  }
+
+  /// Same API under another name, with the fields `extra` returns for a model added to each
+  /// request.
+  pub(super) fn variant(
+    llm: &LlmConfig,
+    name: &'static str,
+    extra: fn(&str) -> Map<String, Value>,
+  ) -> OpenAiCompatible {
+    OpenAiCompatible { name, extra, ..OpenAiCompatible::new(llm) }
+  }
+
+  fn body(&self, request: &ChatRequest) -> Value {
+    let model = request.model_or(&self.model);
+    let mut body = json!({
+        "model": model,
+        "messages": request.messages,
+        "max_tokens": request.max_tokens,
+    });
+    if let Some(schema) = &request.schema {
+      body["response_format"] = match self.response_format {
+        ResponseFormat::JsonObject => json!({ "type": "json_object" }),
+        ResponseFormat::JsonSchema => json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema, "strict": true }
+        }),
+      };
+    }
+    for (key, value) in (self.extra)(model) {
+      body[key] = value;
+    }
+    body
+  }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    let mut http = self.client.post(format!("{}/chat/completions", self.base_url));
+    // Local servers usually run without a key.
+    if !self.api_key.expose().is_empty() {
+      http = http.bearer_auth(self.api_key.expose());
+    }
+    let response = check_status(http.json(&self.body(request)).send().await?).await?;
+
+    let response_json: Value = response.json().await?;
+    let content = response_json["choices"][0]["message"]["content"].as_str();
+    content.map(str::to_string).ok_or(LlmError::MissingContent)
+  }
+
+  fn max_context(&self) -> usize {
+    self.context_window
+  }
+}
@@ -1 +1,9 @@
-pub mod groq;
+pub mod controls;
+pub mod conversation;
+pub mod dedup;
+pub mod llm;
+pub mod policy;
+pub mod queue;
+pub mod reviewer;
+pub mod store;
+pub mod tokens;
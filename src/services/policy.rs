@@ -0,0 +1,201 @@
+// policy.rs
+use crate::config::Config;
+use crate::helpers::event::PullRequestAction;
+use crate::helpers::findings::Severity;
+use crate::helpers::glob::glob_match;
+use crate::helpers::octo::get_file_contents;
+use octocrab::Octocrab;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Where a repository's policy is looked for, first match wins. The `.yml` file holds the same
+/// settings in YAML.
+pub const POLICY_PATHS: [&str; 3] =
+  [".mechanic.toml", ".github/mechanic.toml", ".github/mechanic.yml"];
+/// The cache is emptied when it grows past this many commits.
+const MAX_CACHED: usize = 1_024;
+
+/// A repository's `.mechanic.toml` or `.github/mechanic.yml`. Anything left out falls back to
+/// the server's config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+  enabled: Option<bool>,
+  events: Option<Vec<PullRequestAction>>,
+  include: Vec<String>,
+  exclude: Vec<String>,
+  request_changes_at: Option<Severity>,
+  model: Option<String>,
+  max_comments: Option<usize>,
+  instructions: Option<String>,
+  autofix: Option<bool>,
+}
+
+/// How Mechanic reviews one repository: its `.mechanic.toml` on top of the server's defaults.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoPolicy {
+  pub enabled: bool,
+  /// Pull request actions that trigger a review.
+  pub events: Vec<PullRequestAction>,
+  /// Only files matching one of these globs are reviewed; empty means every file.
+  pub include: Vec<String>,
+  /// Files matching any of these globs are never reviewed.
+  pub exclude: Vec<String>,
+  pub request_changes_at: Severity,
+  pub model: String,
+  /// Inline comments per review; further findings are listed in the review body.
+  pub max_comments: usize,
+  /// Extra guidance for the model, e.g. the project's conventions.
+  pub instructions: Option<String>,
+  /// Open a pull request with the model's suggested fixes after each review.
+  pub autofix: bool,
+  /// Why the repository's policy file was ignored for these defaults, to show with the review.
+  #[serde(skip)]
+  pub invalid_file: Option<String>,
+}
+
+impl RepoPolicy {
+  /// The installation-wide defaults, used when a repository has no policy file.
+  pub fn defaults(config: &Config) -> RepoPolicy {
+    RepoPolicy {
+      enabled: true,
+      events: vec![
+        PullRequestAction::Opened,
+        PullRequestAction::Synchronize,
+        PullRequestAction::Reopened,
+        PullRequestAction::ReadyForReview,
+      ],
+      include: Vec::new(),
+      exclude: Vec::new(),
+      request_changes_at: config.review.request_changes_at,
+      model: config.llm.model.clone(),
+      max_comments: config.review.max_comments,
+      instructions: None,
+      autofix: config.autofix.enabled,
+      invalid_file: None,
+    }
+  }
+
+  pub fn from_toml(text: &str, config: &Config) -> Result<RepoPolicy, toml::de::Error> {
+    Ok(RepoPolicy::from_file(toml::from_str(text)?, config))
+  }
+
+  pub fn from_yaml(text: &str, config: &Config) -> Result<RepoPolicy, serde_norway::Error> {
+    // An empty document, or one with only comments, is YAML's null rather than a mapping.
+    let file: Option<PolicyFile> = serde_norway::from_str(text)?;
+    Ok(RepoPolicy::from_file(file.unwrap_or_default(), config))
+  }
+
+  /// The policy a file's path says how to read.
+  pub fn parse(path: &str, text: &str, config: &Config) -> Result<RepoPolicy, String> {
+    if path.ends_with(".yml") || path.ends_with(".yaml") {
+      RepoPolicy::from_yaml(text, config).map_err(|e| e.to_string())
+    } else {
+      RepoPolicy::from_toml(text, config).map_err(|e| e.to_string())
+    }
+  }
+
+  fn from_file(file: PolicyFile, config: &Config) -> RepoPolicy {
+    let defaults = RepoPolicy::defaults(config);
+    RepoPolicy {
+      enabled: file.enabled.unwrap_or(defaults.enabled),
+      events: file.events.unwrap_or(defaults.events),
+      include: file.include,
+      exclude: file.exclude,
+      request_changes_at: file.request_changes_at.unwrap_or(defaults.request_changes_at),
+      model: file.model.filter(|m| !m.trim().is_empty()).unwrap_or(defaults.model),
+      max_comments: file.max_comments.unwrap_or(defaults.max_comments),
+      instructions: file.instructions.filter(|i| !i.trim().is_empty()),
+      autofix: file.autofix.unwrap_or(defaults.autofix),
+      invalid_file: None,
+    }
+  }
+
+  /// The policy spelled out as a `.mechanic.toml`, defaults included.
+  pub fn to_toml(&self) -> String {
+    toml::to_string(self).unwrap_or_else(|e| format!("# could not be rendered: {}\n", e))
+  }
+
+  /// Whether a pull request event with `action` should be reviewed.
+  pub fn reviews(&self, action: PullRequestAction) -> bool {
+    self.enabled && self.events.contains(&action)
+  }
+
+  /// Whether changes to `path` should be reviewed.
+  pub fn reviews_path(&self, path: &str) -> bool {
+    let included = self.include.is_empty() || self.include.iter().any(|g| glob_match(g, path));
+    included && !self.exclude.iter().any(|g| glob_match(g, path))
+  }
+}
+
+/// `owner/repo` and commit sha.
+type PolicyKey = (String, String);
+
+/// Repository policies by commit, shared by every job. A commit's files never change, so
+/// entries never go stale.
+#[derive(Clone, Default)]
+pub struct PolicyCache {
+  cache: Arc<Mutex<HashMap<PolicyKey, Arc<RepoPolicy>>>>,
+}
+
+impl PolicyCache {
+  pub fn new() -> PolicyCache {
+    PolicyCache::default()
+  }
+
+  /// The policy of `owner/repo` at `sha`. A missing or invalid policy file means the
+  /// defaults, with the error kept for the review to report; so does a failure to fetch it,
+  /// which isn't cached so the next job retries.
+  pub async fn load(
+    &self,
+    octo: &Octocrab,
+    owner: &str,
+    repo: &str,
+    sha: &str,
+    config: &Config,
+  ) -> Arc<RepoPolicy> {
+    let key = (format!("{}/{}", owner, repo), sha.to_string());
+    if let Some(policy) = self.cache.lock().await.get(&key) {
+      return Arc::clone(policy);
+    }
+
+    let mut policy = RepoPolicy::defaults(config);
+    let mut fetched = true;
+    for path in POLICY_PATHS {
+      match get_file_contents(octo, owner, repo, path, sha).await.map_err(|e| e.to_string()) {
+        Ok(None) => continue,
+        Ok(Some(text)) => {
+          match RepoPolicy::parse(path, &text, config) {
+            Ok(parsed) => {
+              println!("Using {} from {}/{} at {}", path, owner, repo, sha);
+              policy = parsed;
+            }
+            Err(e) => {
+              eprintln!("Ignoring invalid {} in {}/{}: {}", path, owner, repo, e);
+              policy.invalid_file =
+                Some(format!("`{}` is invalid, so the default settings were used: {}", path, e));
+            }
+          }
+          break;
+        }
+        Err(e) => {
+          eprintln!("Failed to fetch {} from {}/{}: {}", path, owner, repo, e);
+          fetched = false;
+          break;
+        }
+      }
+    }
+
+    let policy = Arc::new(policy);
+    if fetched {
+      let mut cache = self.cache.lock().await;
+      if cache.len() >= MAX_CACHED {
+        cache.clear();
+      }
+      cache.insert(key, Arc::clone(&policy));
+    }
+    policy
+  }
+}
@@ -0,0 +1,190 @@
+// queue.rs
+use crate::helpers::artifacts::Artifacts;
+use crate::helpers::event::{AfterPosting, Attempt, GitHubEvent, process_event_and_get_token};
+use crate::helpers::redact::redact;
+use crate::services::store::{JobState, JobStore};
+use crate::state::AppState;
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Mutex, mpsc};
+
+/// A review requested by a webhook delivery.
+#[derive(Debug, Clone)]
+pub struct ReviewJob {
+  /// Id of the job's record in the [`JobStore`].
+  pub id: String,
+  pub delivery_id: Option<String>,
+  pub event: GitHubEvent,
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+  /// Every slot is taken; the caller should tell GitHub to try again later.
+  Full,
+  /// The workers have shut down.
+  Closed,
+}
+
+impl fmt::Display for QueueError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      QueueError::Full => write!(f, "review queue is full"),
+      QueueError::Closed => write!(f, "review queue is closed"),
+    }
+  }
+}
+
+impl std::error::Error for QueueError {}
+
+/// Snapshot of the queue, served on `/queue`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct QueueStats {
+  pub depth: usize,
+  pub in_flight: usize,
+  pub capacity: usize,
+}
+
+/// Bounded queue of review jobs shared between the webhook handler and the workers.
+#[derive(Clone)]
+pub struct JobQueue {
+  sender: mpsc::Sender<ReviewJob>,
+  depth: Arc<AtomicUsize>,
+  in_flight: Arc<AtomicUsize>,
+  capacity: usize,
+}
+
+/// Receiving half of a [`JobQueue`], consumed by [`spawn_workers`].
+pub struct JobReceiver(mpsc::Receiver<ReviewJob>);
+
+impl JobQueue {
+  pub fn new(capacity: usize) -> (JobQueue, JobReceiver) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let queue = JobQueue {
+      sender,
+      depth: Arc::new(AtomicUsize::new(0)),
+      in_flight: Arc::new(AtomicUsize::new(0)),
+      capacity,
+    };
+    (queue, JobReceiver(receiver))
+  }
+
+  /// Queue a job without waiting for a free slot.
+  pub fn enqueue(&self, job: ReviewJob) -> Result<(), QueueError> {
+    // Count the job before sending so a fast worker can't decrement below zero.
+    self.depth.fetch_add(1, Ordering::SeqCst);
+    self.sender.try_send(job).map_err(|e| {
+      self.depth.fetch_sub(1, Ordering::SeqCst);
+      match e {
+        mpsc::error::TrySendError::Full(_) => QueueError::Full,
+        mpsc::error::TrySendError::Closed(_) => QueueError::Closed,
+      }
+    })
+  }
+
+  /// Queue a job, waiting for a free slot. Used when resuming jobs on boot.
+  pub async fn enqueue_wait(&self, job: ReviewJob) -> Result<(), QueueError> {
+    self.depth.fetch_add(1, Ordering::SeqCst);
+    self.sender.send(job).await.map_err(|_| {
+      self.depth.fetch_sub(1, Ordering::SeqCst);
+      QueueError::Closed
+    })
+  }
+
+  /// Number of jobs waiting for a worker.
+  pub fn depth(&self) -> usize {
+    self.depth.load(Ordering::SeqCst)
+  }
+
+  pub fn stats(&self) -> QueueStats {
+    QueueStats {
+      depth: self.depth(),
+      in_flight: self.in_flight.load(Ordering::SeqCst),
+      capacity: self.capacity,
+    }
+  }
+}
+
+/// Start `workers` tasks pulling jobs off the queue until every sender is dropped.
+/// Every state change is written to the job store so the job can be resumed or inspected later.
+/// Jobs whose commit has been superseded in the ledger by a newer push are skipped.
+pub fn spawn_workers(state: &AppState, receiver: JobReceiver, workers: usize) {
+  let receiver = Arc::new(Mutex::new(receiver.0));
+
+  for worker in 0..workers {
+    let receiver = Arc::clone(&receiver);
+    let state = state.clone();
+    tokio::spawn(async move {
+      loop {
+        // Hold the lock only while waiting for the next job, not while running it.
+        let Some(job) = receiver.lock().await.recv().await else { break };
+        state.queue.depth.fetch_sub(1, Ordering::SeqCst);
+        state.queue.in_flight.fetch_add(1, Ordering::SeqCst);
+        if state.ledger.is_superseded(&job.event) {
+          println!("Worker {} skipping job {}: a newer commit was pushed.", worker, job.id);
+          record(&state.store, &job.id, JobState::Succeeded { result: "Superseded".to_string() });
+        } else {
+          let artifacts = Artifacts::for_job(state.config.server.artifact_dir.as_deref(), &job.id);
+          if !run_with_retries(worker, &job, &state, &artifacts).await {
+            state.ledger.release(job.delivery_id.as_deref(), &job.event);
+          }
+        }
+        state.queue.in_flight.fetch_sub(1, Ordering::SeqCst);
+      }
+      println!("Worker {} stopped: queue closed.", worker);
+    });
+  }
+}
+
+/// Run a job until it succeeds or runs out of attempts. Returns whether it succeeded.
+async fn run_with_retries(
+  worker: usize,
+  job: &ReviewJob,
+  state: &AppState,
+  artifacts: &Artifacts,
+) -> bool {
+  let delivery = job.delivery_id.as_deref().unwrap_or("unknown");
+  let store = &state.store;
+  let max_attempts = state.config.review.max_attempts;
+
+  let mut progress = Attempt::default();
+  for attempt in 1..=max_attempts {
+    record(store, &job.id, JobState::Running { attempt });
+    progress.retries_left = attempt < max_attempts;
+    let (error, after_posting) =
+      match process_event_and_get_token(&job.event, state, artifacts, &progress).await {
+        Ok(status) => {
+          println!("Worker {} finished delivery {}: {:?}", worker, delivery, status);
+          record(store, &job.id, JobState::Succeeded { result: format!("{:?}", status) });
+          return true;
+        }
+        // Running a job again after its review went out would post the review a second time.
+        Err(e) => (redact(&e.to_string()), e.is::<AfterPosting>()),
+      };
+
+    if attempt == max_attempts || after_posting {
+      println!("Worker {} giving up on delivery {}: {}", worker, delivery, error);
+      record(store, &job.id, JobState::Failed { error });
+      return false;
+    }
+    record(store, &job.id, JobState::Retrying { attempt, error: error.clone() });
+    // The failure may have been a revoked or rejected token; retry with a fresh one.
+    state.clients.invalidate(job.event.installation_id()).await;
+
+    let backoff = state.config.review.retry_delay(attempt);
+    println!(
+      "Worker {} failed delivery {} (attempt {}/{}): {}. Retrying in {:?}.",
+      worker, delivery, attempt, max_attempts, error, backoff
+    );
+    tokio::time::sleep(backoff).await;
+  }
+  false
+}
+
+/// Persist a state change. A failing disk shouldn't stop the review itself.
+fn record(store: &JobStore, id: &str, state: JobState) {
+  if let Err(e) = store.transition(id, state) {
+    println!("Failed to record state of job {}: {}", id, e);
+  }
+}
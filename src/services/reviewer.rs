@@ -0,0 +1,164 @@
+// reviewer.rs
+use crate::config::{LlmConfig, ReviewConfig};
+use crate::helpers::artifacts::Artifacts;
+use crate::helpers::diff::{FileDiff, PromptDiff, render_for_prompt};
+use crate::helpers::findings::{ReviewReport, parse_report, report_schema};
+use crate::helpers::functions::ChangedFunction;
+use crate::services::llm::{ChatMessage, ChatRequest, LlmProvider};
+use crate::services::policy::RepoPolicy;
+use std::error::Error;
+
+/// Tokens set aside for the instructions and schema around the diff.
+pub const PROMPT_OVERHEAD_TOKENS: usize = 1_500;
+
+/// Render the diff as large as `review.max_diff_chars` allows, shrunk further if needed so the
+/// prompt and a `llm.max_tokens` reply fit in the model's context window.
+pub fn render_changes(
+  files: &[FileDiff],
+  review: &ReviewConfig,
+  llm: &LlmConfig,
+  provider: &dyn LlmProvider,
+) -> PromptDiff {
+  let budget =
+    provider.max_context().saturating_sub(llm.max_tokens as usize + PROMPT_OVERHEAD_TOKENS);
+  let mut max_chars = review.max_diff_chars;
+  loop {
+    let changes = render_for_prompt(files, review.context_lines, max_chars);
+    let tokens = provider.count_tokens(&changes.text);
+    if tokens <= budget || max_chars == 0 {
+      return changes;
+    }
+    // Scale down by the overshoot, with some slack since token counts are estimates.
+    max_chars = max_chars * budget / tokens * 9 / 10;
+  }
+}
+
+/// Render the changed functions, whole and with what they call, in the room `changes` left:
+/// both share `review.max_diff_chars` and the model's context window. Functions that don't
+/// fit are left out. Empty when there are none.
+pub fn render_functions(
+  functions: &[ChangedFunction],
+  changes: &str,
+  review: &ReviewConfig,
+  llm: &LlmConfig,
+  provider: &dyn LlmProvider,
+) -> String {
+  let mut tokens_left = provider
+    .max_context()
+    .saturating_sub(llm.max_tokens as usize + PROMPT_OVERHEAD_TOKENS)
+    .saturating_sub(provider.count_tokens(changes));
+  let mut chars_left = review.max_diff_chars.saturating_sub(changes.len());
+  let mut rendered = String::new();
+  let mut left_out = 0;
+  for function in functions {
+    let calls: Vec<String> = function.dependencies.iter().map(|d| format!("`{}`", d)).collect();
+    let calls = if calls.is_empty() { "nothing".to_string() } else { calls.join(", ") };
+    let text = format!(
+      "### `{}` in `{}` (lines {}-{})\nCalls: {}\n```\n{}\n```\n\n",
+      function.name, function.file, function.start_line, function.end_line, calls, function.body
+    );
+    let tokens = provider.count_tokens(&text);
+    if text.len() > chars_left || tokens > tokens_left {
+      left_out += 1;
+      continue;
+    }
+    chars_left -= text.len();
+    tokens_left -= tokens;
+    rendered.push_str(&text);
+  }
+  if left_out > 0 && !rendered.is_empty() {
+    rendered.push_str(&format!("({} more changed function(s) left out for space.)\n", left_out));
+  }
+  rendered.trim_end().to_string()
+}
+
+/// **Review the pull request's changes and return a validated report (Async)**
+/// `changes` is the text of the diff as rendered by [`render_changes`],
+/// and `functions` the functions it changes as rendered by [`render_functions`].
+/// The repository's `policy` picks the model and may add instructions to the prompt.
+/// A reply that isn't a valid report is sent back to the model to fix, up to `llm.repair_attempts` times.
+pub async fn request_review(
+  commit_sha: &str,
+  changes: &str,
+  functions: &str,
+  llm: &LlmConfig,
+  policy: &RepoPolicy,
+  provider: &dyn LlmProvider,
+  artifacts: &Artifacts,
+) -> Result<ReviewReport, Box<dyn Error>> {
+  let mut prompt = review_prompt(commit_sha, changes);
+  if !functions.is_empty() {
+    prompt.push_str(
+      "\n\nThe functions those changes touch, whole as they read at this commit, with the \
+       functions each one calls. They are context: still only cite lines shown in the diff.\n\n",
+    );
+    prompt.push_str(functions);
+  }
+  if let Some(instructions) = &policy.instructions {
+    prompt.push_str("\n\nThe repository's maintainers ask reviewers to keep in mind:\n\n");
+    prompt.push_str(instructions.trim());
+  }
+  let mut request = ChatRequest {
+    messages: vec![ChatMessage::user(prompt)],
+    max_tokens: llm.max_tokens,
+    schema: Some(report_schema()),
+    model: Some(policy.model.clone()),
+  };
+  let mut reply = provider.chat(&request).await?;
+  artifacts.save("review.json", &reply);
+
+  let mut attempt = 0;
+  loop {
+    match parse_report(&reply) {
+      Ok((report, dropped)) => {
+        for problem in dropped {
+          println!("Dropped invalid finding: {}", problem);
+        }
+        println!("Review report: {} finding(s).", report.findings.len());
+        return Ok(report);
+      }
+      Err(e) if attempt < llm.repair_attempts => {
+        attempt += 1;
+        println!("{}; asking the model to repair it (attempt {}).", e, attempt);
+        request.messages.push(ChatMessage::assistant(reply));
+        request.messages.push(ChatMessage::user(repair_prompt(&e.to_string())));
+        reply = provider.chat(&request).await?;
+        artifacts.save(&format!("review.repair-{}.json", attempt), &reply);
+      }
+      Err(e) => return Err(e.into()),
+    }
+  }
+}
+
+fn review_prompt(commit_sha: &str, changes: &str) -> String {
+  format!(
+    "You are Mechanic, reviewing the changes a pull request makes at commit {}. \
+      The diff below shows changed lines (`+` added, `-` removed) with a little surrounding context, \
+      numbered by their line in the new version of each file.
+
+      **Return JSON only**, matching this schema:
+      {}
+
+      - `summary`: a short GitHub-flavoured Markdown overview of the change and its main risks.
+      - `findings`: one entry per concrete problem in the changed code, most important first. \
+      Use `[]` when there is nothing worth fixing (a README edit, a clean change).
+      - `start_line`/`end_line`: the lines the finding is about, using the numbers shown in the diff. \
+      Only cite lines that appear in it.
+      - `suggested_fix`: the exact new text for lines `start_line` through `end_line`, with their \
+      indentation and nothing else, or `null` if there is no drop-in fix.
+      - `confidence`: how sure you are the finding is real, from 0 to 1.
+
+      Changes:\n\n{}",
+    commit_sha,
+    report_schema(),
+    changes
+  )
+}
+
+fn repair_prompt(error: &str) -> String {
+  format!(
+    "Your reply could not be used: {}. \
+      Reply again with the same review as a single JSON object matching the schema, and nothing else.",
+    error
+  )
+}
@@ -0,0 +1,314 @@
+// store.rs
+use crate::helpers::event::GitHubEvent;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Lifecycle of a review job. Only `Succeeded` and `Failed` are final.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+pub enum JobState {
+  Queued,
+  Running { attempt: u32 },
+  Retrying { attempt: u32, error: String },
+  Succeeded { result: String },
+  Failed { error: String },
+}
+
+impl JobState {
+  pub fn is_finished(&self) -> bool {
+    matches!(self, JobState::Succeeded { .. } | JobState::Failed { .. })
+  }
+}
+
+/// Everything known about one delivery and the review it triggered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+  pub id: String,
+  pub delivery_id: Option<String>,
+  pub event: GitHubEvent,
+  pub state: JobState,
+  pub created_at: i64,
+  pub updated_at: i64,
+  /// Every state the job has been in, oldest first.
+  pub history: Vec<(i64, JobState)>,
+}
+
+/// One line of the append-only log.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+enum LogEntry {
+  Created {
+    id: String,
+    delivery_id: Option<String>,
+    event: GitHubEvent,
+    at: i64,
+  },
+  Transition {
+    id: String,
+    state: JobState,
+    at: i64,
+  },
+  Snapshot {
+    record: JobRecord,
+  },
+  /// A pull request paused or resumed with a slash command.
+  Paused {
+    repo: String,
+    pull_number: u64,
+    paused: bool,
+    at: i64,
+  },
+}
+
+/// How long finished jobs are kept when the store is opened with [`JobStore::open`].
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+/// The log is compacted again once this many lines were appended since the last compaction.
+const COMPACT_AFTER_LINES: usize = 10_000;
+
+struct Inner {
+  path: PathBuf,
+  file: File,
+  jobs: HashMap<String, JobRecord>,
+  /// (`owner/repo`, number) of the pull requests paused right now.
+  paused: HashSet<(String, u64)>,
+  retention: Duration,
+  appended: usize,
+}
+
+impl Inner {
+  fn append(&mut self, entry: LogEntry) -> Result<(), Box<dyn Error>> {
+    let line = serde_json::to_string(&entry)?;
+    writeln!(self.file, "{}", line)?;
+    self.file.flush()?;
+    apply(&mut self.jobs, &mut self.paused, entry);
+
+    self.appended += 1;
+    if self.appended >= COMPACT_AFTER_LINES {
+      prune(&mut self.jobs, self.retention);
+      self.file = compact(&self.path, &self.jobs, &self.paused)?;
+      self.appended = 0;
+    }
+    Ok(())
+  }
+}
+
+/// On-disk record of every review job, kept as a JSON-lines append-only log.
+/// The log is replayed and compacted on open so unfinished jobs can be resumed after a restart,
+/// and compacted again as it grows. Compacting drops finished jobs past their retention.
+#[derive(Clone)]
+pub struct JobStore {
+  inner: Arc<Mutex<Inner>>,
+}
+
+impl JobStore {
+  /// Open (or create) the log at `path` and replay it, keeping finished jobs for
+  /// [`DEFAULT_RETENTION_DAYS`].
+  pub fn open(path: impl AsRef<Path>) -> Result<JobStore, Box<dyn Error>> {
+    JobStore::open_with_retention(path, Duration::from_secs(DEFAULT_RETENTION_DAYS * 24 * 60 * 60))
+  }
+
+  /// Open (or create) the log at `path` and replay it. Finished jobs last updated more than
+  /// `retention` ago are dropped whenever the log is compacted.
+  pub fn open_with_retention(
+    path: impl AsRef<Path>,
+    retention: Duration,
+  ) -> Result<JobStore, Box<dyn Error>> {
+    let path = path.as_ref().to_path_buf();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+      fs::create_dir_all(parent)?;
+    }
+
+    let mut jobs = HashMap::new();
+    let mut paused = HashSet::new();
+    if path.exists() {
+      let reader = BufReader::new(File::open(&path)?);
+      for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+          continue;
+        }
+        // A crash mid-write leaves a truncated last line; skip it rather than refusing to boot.
+        match serde_json::from_str::<LogEntry>(&line) {
+          Ok(entry) => apply(&mut jobs, &mut paused, entry),
+          Err(e) => println!("Skipping unreadable job log line {}: {}", number + 1, e),
+        }
+      }
+    }
+
+    let pruned = prune(&mut jobs, retention);
+    let file = compact(&path, &jobs, &paused)?;
+    println!("Loaded {} job(s) from {} ({} expired).", jobs.len(), path.display(), pruned);
+
+    let inner = Inner { path, file, jobs, paused, retention, appended: 0 };
+    Ok(JobStore { inner: Arc::new(Mutex::new(inner)) })
+  }
+
+  /// Record a new delivery and return the id of its job: the delivery id with a number
+  /// after it (`<delivery>.1`, then `<delivery>.2` for a redelivery), so every job keeps its
+  /// own record and still points at its delivery. Jobs without one are `job-<time>-<n>`.
+  pub fn create(
+    &self,
+    delivery_id: Option<String>,
+    event: &GitHubEvent,
+  ) -> Result<String, Box<dyn Error>> {
+    let at = Utc::now().timestamp();
+    let mut inner = self.inner.lock().unwrap();
+    let numbered = |n: u64| match &delivery_id {
+      Some(delivery) => format!("{}.{}", delivery, n),
+      None => format!("job-{}-{}", at, n),
+    };
+    let id = (1..).map(numbered).find(|id| !inner.jobs.contains_key(id)).expect("endless ids");
+
+    let entry = LogEntry::Created { id: id.clone(), delivery_id, event: event.clone(), at };
+    inner.append(entry)?;
+    Ok(id)
+  }
+
+  /// Move a job to `state`, persisting the transition before updating memory.
+  pub fn transition(&self, id: &str, state: JobState) -> Result<(), Box<dyn Error>> {
+    let entry = LogEntry::Transition { id: id.to_string(), state, at: Utc::now().timestamp() };
+    self.append(entry)
+  }
+
+  pub fn get(&self, id: &str) -> Option<JobRecord> {
+    self.inner.lock().unwrap().jobs.get(id).cloned()
+  }
+
+  /// Every job for the delivery `delivery_id`, newest first.
+  pub fn for_delivery(&self, delivery_id: &str) -> Vec<JobRecord> {
+    let mut jobs: Vec<JobRecord> = self
+      .inner
+      .lock()
+      .unwrap()
+      .jobs
+      .values()
+      .filter(|job| job.delivery_id.as_deref() == Some(delivery_id))
+      .cloned()
+      .collect();
+    jobs.sort_by_key(|job| std::cmp::Reverse((job.created_at, job.id.clone())));
+    jobs
+  }
+
+  /// All jobs, most recently updated first.
+  pub fn list(&self) -> Vec<JobRecord> {
+    let mut jobs: Vec<JobRecord> = self.inner.lock().unwrap().jobs.values().cloned().collect();
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.updated_at));
+    jobs
+  }
+
+  /// Jobs that were queued or running when the process stopped, oldest first.
+  pub fn pending(&self) -> Vec<JobRecord> {
+    let mut jobs: Vec<JobRecord> = self
+      .inner
+      .lock()
+      .unwrap()
+      .jobs
+      .values()
+      .filter(|job| !job.state.is_finished())
+      .cloned()
+      .collect();
+    jobs.sort_by_key(|job| job.created_at);
+    jobs
+  }
+  /// Record that the pull request `pull_number` in `repo` was paused or resumed. Pauses
+  /// outlive the jobs that made them, so they are never pruned.
+  pub fn set_paused(
+    &self,
+    repo: &str,
+    pull_number: u64,
+    paused: bool,
+  ) -> Result<(), Box<dyn Error>> {
+    let at = Utc::now().timestamp();
+    self.append(LogEntry::Paused { repo: repo.to_string(), pull_number, paused, at })
+  }
+
+  /// (`owner/repo`, number) of every pull request paused right now.
+  pub fn paused(&self) -> Vec<(String, u64)> {
+    self.inner.lock().unwrap().paused.iter().cloned().collect()
+  }
+
+  fn append(&self, entry: LogEntry) -> Result<(), Box<dyn Error>> {
+    self.inner.lock().unwrap().append(entry)
+  }
+}
+
+fn apply(
+  jobs: &mut HashMap<String, JobRecord>,
+  paused: &mut HashSet<(String, u64)>,
+  entry: LogEntry,
+) {
+  match entry {
+    LogEntry::Created { id, delivery_id, event, at } => {
+      jobs.insert(
+        id.clone(),
+        JobRecord {
+          id,
+          delivery_id,
+          event,
+          state: JobState::Queued,
+          created_at: at,
+          updated_at: at,
+          history: vec![(at, JobState::Queued)],
+        },
+      );
+    }
+    LogEntry::Transition { id, state, at } => {
+      if let Some(job) = jobs.get_mut(&id) {
+        job.history.push((at, state.clone()));
+        job.state = state;
+        job.updated_at = at;
+      }
+    }
+    LogEntry::Snapshot { record } => {
+      jobs.insert(record.id.clone(), record);
+    }
+    LogEntry::Paused { repo, pull_number, paused: true, .. } => {
+      paused.insert((repo, pull_number));
+    }
+    LogEntry::Paused { repo, pull_number, paused: false, .. } => {
+      paused.remove(&(repo, pull_number));
+    }
+  }
+}
+
+/// Drop finished jobs last updated more than `retention` ago and return how many went.
+fn prune(jobs: &mut HashMap<String, JobRecord>, retention: Duration) -> usize {
+  let cutoff = Utc::now().timestamp().saturating_sub(retention.as_secs() as i64);
+  let before = jobs.len();
+  jobs.retain(|_, job| !job.state.is_finished() || job.updated_at >= cutoff);
+  before - jobs.len()
+}
+
+/// Rewrite the log as one snapshot per job, plus one line per paused pull request, and return
+/// it opened for appending.
+fn compact(
+  path: &Path,
+  jobs: &HashMap<String, JobRecord>,
+  paused: &HashSet<(String, u64)>,
+) -> Result<File, Box<dyn Error>> {
+  let tmp_path = path.with_extension("compact");
+  {
+    let mut tmp = File::create(&tmp_path)?;
+    for record in jobs.values() {
+      let entry = LogEntry::Snapshot { record: record.clone() };
+      writeln!(tmp, "{}", serde_json::to_string(&entry)?)?;
+    }
+    let at = Utc::now().timestamp();
+    for (repo, pull_number) in paused {
+      let entry =
+        LogEntry::Paused { repo: repo.clone(), pull_number: *pull_number, paused: true, at };
+      writeln!(tmp, "{}", serde_json::to_string(&entry)?)?;
+    }
+    tmp.sync_all()?;
+  }
+  fs::rename(&tmp_path, path)?;
+
+  Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
@@ -0,0 +1,85 @@
+// tokens.rs
+use crate::config::GitHubConfig;
+use crate::helpers::jwt::{create_jwt, exchange_jwt_for_installation_token, fetch_app_slug};
+use crate::helpers::octo::init_octocrab;
+use chrono::{DateTime, Duration, Utc};
+use octocrab::Octocrab;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Tokens this close to expiry are replaced before use, so a long review never runs out of time.
+const REFRESH_MARGIN: Duration = Duration::minutes(10);
+
+struct CachedClient {
+  octo: Octocrab,
+  expires_at: DateTime<Utc>,
+}
+
+/// One installation's client, locked on its own so minting its token doesn't hold up the rest.
+type Slot = Arc<tokio::sync::Mutex<Option<CachedClient>>>;
+
+/// Authenticated Octocrab clients per installation, shared by every job.
+/// Each client is rebuilt from a fresh installation token shortly before the old one expires.
+#[derive(Clone)]
+pub struct InstallationClients {
+  github: GitHubConfig,
+  cache: Arc<Mutex<HashMap<u64, Slot>>>,
+  bot_login: Arc<OnceCell<String>>,
+}
+
+impl InstallationClients {
+  pub fn new(github: GitHubConfig) -> InstallationClients {
+    InstallationClients { github, cache: Arc::default(), bot_login: Arc::new(OnceCell::new()) }
+  }
+
+  /// The login the app comments as, e.g. `mechanic[bot]`, looked up once with the app's JWT.
+  pub async fn bot_login(&self) -> Result<String, Box<dyn Error>> {
+    let login = self
+      .bot_login
+      .get_or_try_init(|| async {
+        let jwt = create_jwt(&self.github).map_err(|e| e.to_string())?;
+        let slug = fetch_app_slug(&self.github.api_url, &jwt).await.map_err(|e| e.to_string())?;
+        Ok::<_, String>(format!("{}[bot]", slug))
+      })
+      .await?;
+    Ok(login.clone())
+  }
+
+  /// A client authenticated as `installation_id`, minting a new token only when needed.
+  pub async fn client(&self, installation_id: u64) -> Result<Octocrab, Box<dyn Error>> {
+    // Holding the installation's lock across the exchange stops concurrent jobs from minting
+    // duplicate tokens; other installations go on in the meantime.
+    let slot = self.slot(installation_id);
+    let mut cached = slot.lock().await;
+    if let Some(cached) = cached.as_ref()
+      && cached.expires_at - REFRESH_MARGIN > Utc::now()
+    {
+      return Ok(cached.octo.clone());
+    }
+
+    println!("Refreshing installation token for installation {}", installation_id);
+    let jwt = create_jwt(&self.github)?;
+    let token =
+      exchange_jwt_for_installation_token(&self.github.api_url, &jwt, installation_id).await?;
+    let octo = init_octocrab(&self.github.api_url, token.token.expose().to_string())?;
+    *cached = Some(CachedClient { octo: octo.clone(), expires_at: token.expires_at });
+    Ok(octo)
+  }
+
+  /// Use `octo` for `installation_id` until `expires_at` instead of minting tokens,
+  /// e.g. a client pointed at a test server.
+  pub async fn preload(&self, installation_id: u64, octo: Octocrab, expires_at: DateTime<Utc>) {
+    *self.slot(installation_id).lock().await = Some(CachedClient { octo, expires_at });
+  }
+
+  /// Drop the cached client, e.g. after GitHub rejects its token.
+  pub async fn invalidate(&self, installation_id: u64) {
+    self.slot(installation_id).lock().await.take();
+  }
+
+  fn slot(&self, installation_id: u64) -> Slot {
+    Arc::clone(self.cache.lock().unwrap().entry(installation_id).or_default())
+  }
+}
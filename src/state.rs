@@ -0,0 +1,23 @@
+// state.rs
+use crate::config::Config;
+use crate::services::controls::PullRequestControls;
+use crate::services::dedup::DeliveryLedger;
+use crate::services::llm::LlmProvider;
+use crate::services::policy::PolicyCache;
+use crate::services::queue::JobQueue;
+use crate::services::store::JobStore;
+use crate::services::tokens::InstallationClients;
+use std::sync::Arc;
+
+/// Shared state handed to every axum handler.
+#[derive(Clone)]
+pub struct AppState {
+  pub queue: JobQueue,
+  pub store: JobStore,
+  pub ledger: DeliveryLedger,
+  pub config: Arc<Config>,
+  pub clients: InstallationClients,
+  pub policies: PolicyCache,
+  pub controls: PullRequestControls,
+  pub llm: Arc<dyn LlmProvider>,
+}
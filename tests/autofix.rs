@@ -0,0 +1,255 @@
+mod common;
+
+use axum::http::Method;
+use common::{HEAD_SHA, REPO, app_state, pull_request_payload};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use treehacks25::config::Config;
+use treehacks25::fake_github::FakeGitHub;
+use treehacks25::helpers::autofix::{apply_fixes, autofix_body, fixes_by_file};
+use treehacks25::helpers::event::{DeliveryStatus, GitHubEvent};
+use treehacks25::helpers::findings::{Category, ReviewFinding, ReviewReport, Severity};
+use treehacks25::helpers::octo::{get_file_contents, init_octocrab};
+use treehacks25::services::llm::mock::MockProvider;
+// Key of the review prompt for the fixture pull request with `RETRY_RS` at its head: unlike the
+// one recorded in `fixtures/completions`, it also shows the model the changed `retry` function.
+const REVIEW_KEY: &str = "4424831b0952af6f";
+
+const RETRY_RS: &str = "use std::time::Duration;
+
+pub fn retry<T>(mut f: impl FnMut() -> Option<T>, attempts: u32) -> Option<T> {
+  for _ in 0..attempts {
+    if let Some(v) = f() { return Some(v); }
+    std::thread::sleep(Duration::from_millis(100));
+  }
+  None
+}
+";
+
+fn finding(file: &str, lines: (u32, u32), fix: Option<&str>, confidence: f32) -> ReviewFinding {
+  ReviewFinding {
+    file: file.to_string(),
+    start_line: lines.0,
+    end_line: lines.1,
+    severity: Severity::Warning,
+    category: Category::Style,
+    message: format!("Tidy lines {}-{}.", lines.0, lines.1),
+    suggested_fix: fix.map(String::from),
+    confidence,
+  }
+}
+
+fn config(github_url: &str) -> Config {
+  let mut config = common::config(github_url);
+  config.checks.enabled = false;
+  config.autofix.enabled = true;
+  config
+}
+
+/// The model's review of the fixture pull request, with fixes for two files.
+fn model() -> MockProvider {
+  let reply = json!({
+    "summary": "Adds a `retry` helper.",
+    "findings": [
+      {
+        "file": "src/retry.rs", "start_line": 5, "end_line": 5, "severity": "info",
+        "category": "style", "message": "Put the early return on its own lines.",
+        "suggested_fix": "    if let Some(v) = f() {\n      return Some(v);\n    }",
+        "confidence": 0.9
+      },
+      {
+        "file": "src/retry.rs", "start_line": 6, "end_line": 6, "severity": "warning",
+        "category": "performance", "message": "Blocks the async runtime.",
+        "suggested_fix": "    tokio::time::sleep(Duration::from_millis(100)).await;",
+        "confidence": 0.5
+      },
+      {
+        "file": "src/lib.rs", "start_line": 2, "end_line": 2, "severity": "info",
+        "category": "maintainability", "message": "Export `retry` from the crate root.",
+        "suggested_fix": "pub mod retry;\npub use retry::retry;",
+        "confidence": 0.8
+      }
+    ]
+  });
+  let llm = MockProvider::new();
+  llm.insert(REVIEW_KEY, reply.to_string());
+  llm
+}
+
+async fn fake_github(payload: &Value) -> (FakeGitHub, String) {
+  let (github, url) = common::fake_github(payload).await;
+  github.add_file(REPO, HEAD_SHA, "src/retry.rs", RETRY_RS);
+  github.add_file(REPO, HEAD_SHA, "src/lib.rs", "pub mod client;\npub mod retry;\n");
+  github.add_file(REPO, HEAD_SHA, "README.md", "# mechanic-demo\n");
+  (github, url)
+}
+
+async fn run(payload: &Value, config: &Config) -> DeliveryStatus {
+  let event = GitHubEvent::parse("pull_request", payload).unwrap();
+  common::run(&event, &app_state(config.clone(), Arc::new(model())).0).await
+}
+
+#[test]
+fn fixes_replace_their_lines_and_keep_line_endings() {
+  let contents = "a\nb\nc\nd";
+  let fixes = [
+    finding("f.rs", (4, 4), Some("D"), 1.0),
+    finding("f.rs", (2, 3), Some("B\nC\nC2\n"), 1.0),
+    finding("f.rs", (1, 1), Some("a"), 1.0),
+  ];
+
+  let fixed = apply_fixes("f.rs", contents, &fixes).unwrap();
+  assert_eq!(fixed.contents, "a\nB\nC\nC2\nD");
+  // The unchanged first line isn't a fix; the rest are listed top of the file first.
+  let lines: Vec<u32> = fixed.fixes.iter().map(|f| f.start_line).collect();
+  assert_eq!(lines, vec![2, 4]);
+}
+
+#[test]
+fn overlapping_and_out_of_range_fixes_are_left_out() {
+  let fixes = [
+    finding("f.rs", (2, 3), Some("first"), 1.0),
+    finding("f.rs", (3, 4), Some("overlaps"), 1.0),
+    finding("f.rs", (9, 9), Some("past the end"), 1.0),
+  ];
+  let fixed = apply_fixes("f.rs", "1\n2\n3\n4\n", &fixes).unwrap();
+  assert_eq!(fixed.contents, "1\nfirst\n4\n");
+  assert_eq!(fixed.fixes.len(), 1);
+
+  assert_eq!(apply_fixes("f.rs", "1\n", &[finding("f.rs", (1, 1), Some("1"), 1.0)]), None);
+}
+
+#[test]
+fn only_confident_fixes_are_picked() {
+  let report = ReviewReport {
+    summary: String::new(),
+    findings: vec![
+      finding("b.rs", (1, 1), Some("x"), 0.9),
+      finding("a.rs", (1, 1), Some("x"), 0.5),
+      finding("b.rs", (2, 2), None, 1.0),
+      finding("b.rs", (3, 3), Some("y"), 0.8),
+    ],
+  };
+  let picked = fixes_by_file(&report, 0.8);
+  assert_eq!(picked.keys().collect::<Vec<_>>(), vec!["b.rs"]);
+  assert_eq!(picked["b.rs"].len(), 2);
+}
+
+#[test]
+fn the_body_lists_every_change() {
+  let retry =
+    apply_fixes("src/retry.rs", RETRY_RS, &[finding("src/retry.rs", (5, 5), Some("x"), 1.0)]);
+  let body = autofix_body(7, HEAD_SHA, &[retry.unwrap()]);
+  assert!(body.contains(&format!("review of {} on #7: 1 change in 1 file.", HEAD_SHA)));
+  assert!(body.contains("### `src/retry.rs`"));
+  assert!(body.contains("- `src/retry.rs:5` **Warning** (style): Tidy lines 5-5."));
+}
+
+#[tokio::test]
+async fn opens_a_pull_request_into_the_authors_branch() {
+  let payload = pull_request_payload();
+  let (github, url) = fake_github(&payload).await;
+
+  assert_eq!(run(&payload, &config(&url)).await, DeliveryStatus::Accepted);
+
+  let commits = github.requests_to(Method::POST, "/git/commits");
+  assert_eq!(commits.len(), 1);
+  assert_eq!(commits[0].body["parents"], json!([HEAD_SHA]));
+  let refs = github.requests_to(Method::POST, "/git/refs");
+  assert_eq!(refs[0].body["ref"], "refs/heads/mechanic-7");
+
+  // The new commit has the fixes on top of everything else at the head.
+  let octo = init_octocrab(&url, "ghs_test".to_string()).unwrap();
+  let sha = refs[0].body["sha"].as_str().unwrap();
+  let file = |path: &'static str| get_file_contents(&octo, "vznh", "mechanic-demo", path, sha);
+  let retry = file("src/retry.rs").await.unwrap().unwrap();
+  assert!(retry.contains("    if let Some(v) = f() {\n      return Some(v);\n    }\n"));
+  assert!(retry.contains("std::thread::sleep"), "the unsure fix was applied");
+  let lib = file("src/lib.rs").await.unwrap().unwrap();
+  assert_eq!(lib, "pub mod client;\npub mod retry;\npub use retry::retry;\n");
+  assert_eq!(file("README.md").await.unwrap().as_deref(), Some("# mechanic-demo\n"));
+
+  let pulls = github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/pulls");
+  assert_eq!(pulls.len(), 1);
+  assert_eq!(pulls[0].body["head"], "mechanic-7");
+  assert_eq!(pulls[0].body["base"], "retry-helper");
+  let body = pulls[0].body["body"].as_str().unwrap();
+  assert!(body.contains("`src/retry.rs:5`"));
+  assert!(body.contains("`src/lib.rs:2`"));
+  assert!(!body.contains("`src/retry.rs:6`"));
+
+  let comments = github.requests_to(Method::POST, "/issues/7/comments");
+  let sticky = comments[0].body["body"].as_str().unwrap();
+  assert!(sticky.contains("https://github.com/vznh/mechanic-demo/pull/8"));
+}
+
+#[tokio::test]
+async fn fixed_files_keep_their_mode() {
+  let payload = pull_request_payload();
+  let (github, url) = fake_github(&payload).await;
+  github.set_file_mode(REPO, HEAD_SHA, "src/retry.rs", "100755");
+
+  run(&payload, &config(&url)).await;
+
+  let trees = github.requests_to(Method::POST, "/git/trees");
+  let mode = |path: &str| {
+    let entries = trees[0].body["tree"].as_array().unwrap();
+    entries.iter().find(|entry| entry["path"] == path).unwrap()["mode"].clone()
+  };
+  assert_eq!(mode("src/retry.rs"), "100755");
+  assert_eq!(mode("src/lib.rs"), "100644");
+}
+
+#[tokio::test]
+async fn autofix_is_off_unless_asked() {
+  let payload = pull_request_payload();
+  let (github, url) = fake_github(&payload).await;
+  let mut config = config(&url);
+  config.autofix.enabled = false;
+
+  run(&payload, &config).await;
+  assert_eq!(github.requests_to(Method::POST, "/pulls/7/reviews").len(), 1);
+  assert!(github.requests_to(Method::POST, "/git/commits").is_empty());
+}
+
+#[tokio::test]
+async fn pull_requests_from_forks_are_not_fixed() {
+  let mut payload = pull_request_payload();
+  payload["pull_request"]["head"]["repo"]["full_name"] = json!("someone/mechanic-demo");
+  let (github, url) = fake_github(&payload).await;
+
+  assert_eq!(run(&payload, &config(&url)).await, DeliveryStatus::Accepted);
+  assert!(github.requests_to(Method::POST, "/git/refs").is_empty());
+  assert!(github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/pulls").is_empty());
+}
+
+#[tokio::test]
+async fn later_pushes_update_the_open_autofix_pull_request() {
+  let payload = pull_request_payload();
+  let (github, url) = fake_github(&payload).await;
+  let mut config = config(&url);
+
+  run(&payload, &config).await;
+  run(&payload, &config).await;
+
+  // One branch and pull request; the second push moves the branch and rewrites the body.
+  assert_eq!(github.requests_to(Method::POST, "/git/refs").len(), 1);
+  assert_eq!(github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/pulls").len(), 1);
+  let moved = github.requests_to(Method::PATCH, "/git/refs/heads/mechanic-7");
+  assert_eq!(moved.len(), 1);
+  assert_eq!(moved[0].body["force"], true);
+  assert_eq!(github.requests_to(Method::POST, "/git/commits").len(), 2);
+  let edited = github.requests_to(Method::PATCH, "/pulls/8");
+  assert!(edited[0].body["body"].as_str().unwrap().contains("`src/retry.rs:5`"));
+  let requests = github.requests();
+  let sticky = requests.iter().rfind(|r| r.path.contains("/issues/comments/")).unwrap();
+  let sticky = sticky.body["body"].as_str().unwrap();
+  assert!(sticky.contains("https://github.com/vznh/mechanic-demo/pull/8"));
+
+  // Once no fix is confident enough, the stale pull request is closed.
+  config.autofix.min_confidence = 1.0;
+  run(&payload, &config).await;
+  let edited = github.requests_to(Method::PATCH, "/pulls/8");
+  assert_eq!(edited.last().unwrap().body, json!({ "state": "closed" }));
+  assert_eq!(github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/pulls").len(), 1);
+}
@@ -0,0 +1,74 @@
+use treehacks25::config::ChecksConfig;
+use treehacks25::helpers::checks::{
+  AnnotationLevel, CheckConclusion, CheckResult, build_check, conclusion_for,
+};
+use treehacks25::helpers::findings::{Category, ReviewFinding, ReviewReport, Severity};
+
+fn finding(severity: Severity, start_line: u32, end_line: u32) -> ReviewFinding {
+  ReviewFinding {
+    file: "src/lib.rs".to_string(),
+    start_line,
+    end_line,
+    severity,
+    category: Category::Bug,
+    message: "Off by one.".to_string(),
+    suggested_fix: None,
+    confidence: 0.9,
+  }
+}
+
+fn report(severities: &[Severity]) -> ReviewReport {
+  ReviewReport {
+    summary: "Looks mostly fine.".to_string(),
+    findings: severities.iter().map(|&s| finding(s, 3, 3)).collect(),
+  }
+}
+
+#[test]
+fn conclusion_follows_the_worst_finding() {
+  let checks = ChecksConfig::default();
+  assert_eq!(conclusion_for(&report(&[]), &checks), CheckConclusion::Success);
+  assert_eq!(conclusion_for(&report(&[Severity::Info]), &checks), CheckConclusion::Success);
+  assert_eq!(
+    conclusion_for(&report(&[Severity::Info, Severity::Warning]), &checks),
+    CheckConclusion::Neutral
+  );
+  assert_eq!(
+    conclusion_for(&report(&[Severity::Warning, Severity::Error]), &checks),
+    CheckConclusion::Failure
+  );
+}
+
+#[test]
+fn thresholds_are_configurable() {
+  let strict =
+    ChecksConfig { fail_at: Severity::Warning, neutral_at: Severity::Info, ..Default::default() };
+  assert_eq!(conclusion_for(&report(&[Severity::Info]), &strict), CheckConclusion::Neutral);
+  assert_eq!(conclusion_for(&report(&[Severity::Warning]), &strict), CheckConclusion::Failure);
+}
+
+#[test]
+fn every_finding_becomes_an_annotation() {
+  let mut report = report(&[Severity::Error, Severity::Info]);
+  report.findings[1] = finding(Severity::Info, 0, 0);
+  report.findings[1].suggested_fix = Some("let x = 1;".to_string());
+
+  let check = build_check(&report, &ChecksConfig::default());
+  assert_eq!(check.output.title, "2 finding(s): 1 error(s), 0 warning(s), 1 info");
+  assert_eq!(check.output.summary, "Looks mostly fine.");
+
+  let annotations = &check.output.annotations;
+  assert_eq!(annotations.len(), 2);
+  assert_eq!(annotations[0].annotation_level, AnnotationLevel::Failure);
+  assert_eq!(annotations[0].title, "Error (bug)");
+  assert_eq!((annotations[1].start_line, annotations[1].end_line), (1, 1));
+  assert_eq!(annotations[1].annotation_level, AnnotationLevel::Notice);
+  assert_eq!(annotations[1].raw_details.as_deref(), Some("let x = 1;"));
+}
+
+#[test]
+fn failed_reviews_fail_the_check() {
+  let check = CheckResult::errored("model request failed with status 503");
+  assert_eq!(check.conclusion, CheckConclusion::Failure);
+  assert!(check.output.summary.contains("status 503"));
+}
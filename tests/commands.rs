@@ -0,0 +1,217 @@
+mod common;
+
+use axum::http::Method;
+use common::{BASE_SHA, HEAD_SHA, REPO, app_state, config, pull_request_payload, push_event, run};
+use serde_json::{Value, json};
+use treehacks25::fake_github::FakeGitHub;
+use treehacks25::helpers::commands::{Command, in_scope, parse_command};
+use treehacks25::helpers::event::{DeliveryStatus, GitHubEvent};
+use treehacks25::services::controls::PullRequestControls;
+use treehacks25::services::llm::provider_from_config;
+use treehacks25::services::store::JobStore;
+use treehacks25::state::AppState;
+
+fn comment_event(body: &str, login: &str) -> GitHubEvent {
+  let pr = pull_request_payload();
+  let payload = json!({
+    "action": "created",
+    "issue": { "number": 7, "pull_request": { "url": "https://api.github.com/repos/vznh/mechanic-demo/pulls/7" } },
+    "comment": { "id": 901, "body": body, "user": { "login": login, "id": 5, "type": "User" } },
+    "repository": pr["repository"],
+    "installation": pr["installation"],
+    "sender": { "login": login, "id": 5, "type": "User" },
+  });
+  GitHubEvent::parse("issue_comment", &payload).unwrap()
+}
+
+/// The fake GitHub, where `maintainer` may push, and the app's state pointed at it.
+async fn fake_github() -> (FakeGitHub, AppState) {
+  let (github, url) = common::fake_github(&pull_request_payload()).await;
+  github.set_permission(REPO, "maintainer", "write");
+  let config = config(&url);
+  let llm = provider_from_config(&config.llm);
+  (github, app_state(config, llm).0)
+}
+
+fn reactions(github: &FakeGitHub) -> Vec<Value> {
+  let reactions = github.requests_to(Method::POST, "/issues/comments/901/reactions");
+  reactions.into_iter().map(|r| r.body["content"].clone()).collect()
+}
+
+#[test]
+fn parses_commands() {
+  let review = |path: Option<&str>| Some(Ok(Command::Review { path: path.map(String::from) }));
+  assert_eq!(parse_command("/mechanic review"), review(None));
+  assert_eq!(parse_command("  /mechanic review src/lib.rs  "), review(Some("src/lib.rs")));
+  assert_eq!(parse_command("Thanks!\n/mechanic skip\nmore text"), Some(Ok(Command::Skip)));
+  assert_eq!(parse_command("/mechanic pause"), Some(Ok(Command::Pause)));
+  assert_eq!(parse_command("/mechanic resume"), Some(Ok(Command::Resume)));
+  assert_eq!(parse_command("/mechanic config"), Some(Ok(Command::Config)));
+}
+
+#[test]
+fn comments_not_addressed_to_mechanic_are_not_commands() {
+  assert_eq!(parse_command("Looks good to me"), None);
+  assert_eq!(parse_command("/mechanical review"), None);
+  assert_eq!(parse_command("Try `/mechanic review` later"), None);
+}
+
+#[test]
+fn malformed_commands_are_explained() {
+  assert!(parse_command("/mechanic").unwrap().unwrap_err().contains("no command"));
+  assert!(parse_command("/mechanic explode").unwrap().unwrap_err().contains("`explode`"));
+  assert!(parse_command("/mechanic skip now").unwrap().is_err());
+  assert!(parse_command("/mechanic review a b").unwrap().is_err());
+}
+
+#[test]
+fn review_scope_matches_files_directories_and_globs() {
+  assert!(in_scope("src/retry.rs", "src/retry.rs"));
+  assert!(in_scope("src/", "src/retry.rs"));
+  assert!(in_scope("./src", "src/nested/lib.rs"));
+  assert!(in_scope("*.rs", "src/lib.rs"));
+  assert!(!in_scope("src/re", "src/retry.rs"));
+  assert!(!in_scope("assets", "src/lib.rs"));
+}
+
+#[test]
+fn only_new_comments_from_people_are_commands() {
+  let event = comment_event("/mechanic review", "maintainer");
+  assert!(event.wants_review());
+
+  let GitHubEvent::IssueComment(mut edited) = event.clone() else { unreachable!() };
+  edited.action = "edited".to_string();
+  assert!(GitHubEvent::IssueComment(edited).command().is_none());
+
+  let GitHubEvent::IssueComment(mut from_bot) = event.clone() else { unreachable!() };
+  from_bot.comment.user.kind = Some("Bot".to_string());
+  assert!(GitHubEvent::IssueComment(from_bot).command().is_none());
+
+  let GitHubEvent::IssueComment(mut on_issue) = event else { unreachable!() };
+  on_issue.issue.pull_request = None;
+  assert!(!GitHubEvent::IssueComment(on_issue).wants_review());
+}
+
+#[test]
+fn pauses_survive_a_restart() {
+  let log = std::env::temp_dir().join(format!("mechanic-pauses-{}.log", std::process::id()));
+  let _ = std::fs::remove_file(&log);
+  {
+    let controls = PullRequestControls::with_store(JobStore::open(&log).unwrap());
+    controls.pause(REPO, 7).unwrap();
+    controls.pause(REPO, 8).unwrap();
+    controls.resume(REPO, 8).unwrap();
+  }
+
+  let controls = PullRequestControls::with_store(JobStore::open(&log).unwrap());
+  assert!(controls.holds(&push_event()));
+  assert!(controls.is_paused(REPO, 7));
+  assert!(!controls.is_paused(REPO, 8));
+}
+
+#[tokio::test]
+async fn commands_need_write_access() {
+  let (github, state) = fake_github().await;
+
+  let status = run(&comment_event("/mechanic pause", "drive-by"), &state).await;
+  assert_eq!(status, DeliveryStatus::Rejected);
+  assert_eq!(reactions(&github), vec![json!("-1")]);
+  assert!(!state.controls.holds(&push_event()));
+}
+
+#[tokio::test]
+async fn pause_and_resume_hold_automatic_reviews() {
+  let (github, state) = fake_github().await;
+
+  let status = run(&comment_event("/mechanic pause", "maintainer"), &state).await;
+  assert_eq!(status, DeliveryStatus::Accepted);
+  assert!(state.controls.holds(&push_event()));
+  assert_eq!(run(&push_event(), &state).await, DeliveryStatus::Ignored);
+  assert!(github.requests_to(Method::POST, "/pulls/7/reviews").is_empty());
+  // The push was queued as pending before the pause; that status is settled.
+  let statuses = github.requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA));
+  assert_eq!(statuses.len(), 1);
+  assert_eq!(statuses[0].body["state"], "success");
+  assert_eq!(statuses[0].body["description"], "Not reviewed: reviews are paused");
+
+  run(&comment_event("/mechanic resume", "maintainer"), &state).await;
+  assert!(!state.controls.holds(&push_event()));
+  assert_eq!(reactions(&github), vec![json!("+1"), json!("+1")]);
+}
+
+#[tokio::test]
+async fn skip_settles_the_head_commit_status() {
+  let (github, state) = fake_github().await;
+
+  run(&comment_event("/mechanic skip", "maintainer"), &state).await;
+  assert!(state.controls.holds(&push_event()));
+  let statuses = github.requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA));
+  assert_eq!(statuses.len(), 1);
+  assert_eq!(statuses[0].body["state"], "success");
+  assert_eq!(statuses[0].body["description"], "Review skipped by @maintainer");
+}
+
+#[tokio::test]
+async fn pushes_the_policy_leaves_out_settle_their_status() {
+  let (github, state) = fake_github().await;
+  github.add_file(REPO, BASE_SHA, ".mechanic.toml", "events = [\"opened\"]\n");
+
+  let status = run(&push_event(), &state).await;
+  assert_eq!(status, DeliveryStatus::Ignored);
+  let statuses = github.requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA));
+  assert_eq!(statuses.len(), 1);
+  assert_eq!(statuses[0].body["state"], "success");
+  assert_eq!(
+    statuses[0].body["description"],
+    "Not reviewed: turned off by the repository's policy"
+  );
+}
+
+#[tokio::test]
+async fn config_replies_with_the_effective_policy() {
+  let (github, state) = fake_github().await;
+  github.add_file(REPO, BASE_SHA, ".mechanic.toml", "max_comments = 4\n");
+
+  run(&comment_event("/mechanic config", "maintainer"), &state).await;
+  let comments = github.requests_to(Method::POST, "/issues/7/comments");
+  assert_eq!(comments.len(), 1);
+  let reply = comments[0].body["body"].as_str().unwrap();
+  assert!(reply.contains("```toml"));
+  assert!(reply.contains("max_comments = 4"));
+}
+
+#[tokio::test]
+async fn unknown_commands_get_the_usage() {
+  let (github, state) = fake_github().await;
+
+  let event = comment_event("/mechanic explode", "maintainer");
+  run(&event, &state).await;
+  assert_eq!(reactions(&github), vec![json!("confused")]);
+  let comments = github.requests_to(Method::POST, "/issues/7/comments");
+  assert!(comments[0].body["body"].as_str().unwrap().contains("/mechanic review <path>"));
+}
+
+#[tokio::test]
+async fn review_runs_even_when_paused() {
+  let (github, state) = fake_github().await;
+  state.controls.pause(REPO, 7).unwrap();
+
+  let status = run(&comment_event("/mechanic review", "maintainer"), &state).await;
+  assert_eq!(status, DeliveryStatus::Accepted);
+  let reviews = github.requests_to(Method::POST, "/pulls/7/reviews");
+  assert_eq!(reviews.len(), 1);
+  assert_eq!(reviews[0].body["commit_id"], HEAD_SHA);
+}
+
+#[tokio::test]
+async fn review_can_be_limited_to_a_path() {
+  let (github, state) = fake_github().await;
+
+  let event = comment_event("/mechanic review assets/", "maintainer");
+  run(&event, &state).await;
+  // The only file under `assets/` is binary, so there's nothing for the model to see.
+  assert!(github.requests_to(Method::POST, "/pulls/7/reviews").is_empty());
+  let comments = github.requests_to(Method::POST, "/issues/7/comments");
+  let sticky = comments[0].body["body"].as_str().unwrap();
+  assert!(sticky.contains("Only `assets/` was reviewed"));
+}
@@ -0,0 +1,102 @@
+//! Fixtures shared by the tests that run jobs against the bundled fake GitHub API.
+// Each test binary uses only some of these.
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use treehacks25::config::{Config, ProviderKind};
+use treehacks25::fake_github::{FakeGitHub, FakePullRequest, FakeReviewThread};
+use treehacks25::helpers::artifacts::Artifacts;
+use treehacks25::helpers::event::{
+  Attempt, DeliveryStatus, GitHubEvent, process_event_and_get_token,
+};
+use treehacks25::services::controls::PullRequestControls;
+use treehacks25::services::dedup::DeliveryLedger;
+use treehacks25::services::llm::LlmProvider;
+use treehacks25::services::policy::PolicyCache;
+use treehacks25::services::queue::{JobQueue, JobReceiver};
+use treehacks25::services::store::JobStore;
+use treehacks25::services::tokens::InstallationClients;
+use treehacks25::state::AppState;
+
+pub const REPO: &str = "vznh/mechanic-demo";
+pub const HEAD_SHA: &str = "4e2b8a6c1d9f0e3a7b5c8d2f6a1e9b3c7d0f5a48";
+pub const BASE_SHA: &str = "1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d";
+
+/// The recorded `pull_request` delivery for #7 of `vznh/mechanic-demo`.
+pub fn pull_request_payload() -> Value {
+  serde_json::from_slice(&std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap())
+    .unwrap()
+}
+
+pub fn push_event() -> GitHubEvent {
+  GitHubEvent::parse("pull_request", &pull_request_payload()).unwrap()
+}
+
+/// A fake GitHub serving the pull request in `payload` with the recorded diff, and its URL.
+pub async fn fake_github(payload: &Value) -> (FakeGitHub, String) {
+  let diff = std::fs::read_to_string("tests/fixtures/pull_request.diff").unwrap();
+  let github = FakeGitHub::new();
+  github.add_pull_request(REPO, FakePullRequest::from_event(payload, diff).unwrap());
+  let url = github.start().await.unwrap();
+  (github, url)
+}
+
+/// A review thread on line 6 of `src/retry.rs`; an outdated one no longer has a current line.
+pub fn fake_thread(id: &str, body: &str, is_outdated: bool) -> FakeReviewThread {
+  FakeReviewThread {
+    id: id.to_string(),
+    path: "src/retry.rs".to_string(),
+    line: (!is_outdated).then_some(6),
+    original_line: 6,
+    is_outdated,
+    body: body.to_string(),
+  }
+}
+
+/// The test app installed against the fake GitHub at `github_url`, with recorded completions
+/// and commit statuses on.
+pub fn config(github_url: &str) -> Config {
+  let mut config = Config::default();
+  config.github.api_url = github_url.to_string();
+  config.github.app_id = 1146309;
+  config.github.private_key_path = PathBuf::from("tests/fixtures/test-app.pem");
+  config.llm.provider = ProviderKind::Mock;
+  config.llm.recordings = Some(PathBuf::from("tests/fixtures/completions"));
+  config.statuses.enabled = true;
+  config
+}
+
+/// The app's shared state for `config`, answering with `llm`, and the end of its queue that
+/// workers would pull jobs from.
+pub fn app_state(config: Config, llm: Arc<dyn LlmProvider>) -> (AppState, JobReceiver) {
+  static NEXT_LOG: AtomicUsize = AtomicUsize::new(0);
+  let log = std::env::temp_dir().join(format!(
+    "mechanic-test-{}-{}.log",
+    std::process::id(),
+    NEXT_LOG.fetch_add(1, Ordering::SeqCst)
+  ));
+  let _ = std::fs::remove_file(&log);
+  let (queue, receiver) = JobQueue::new(4);
+  let store = JobStore::open(log).unwrap();
+  let state = AppState {
+    queue,
+    controls: PullRequestControls::with_store(store.clone()),
+    store,
+    ledger: DeliveryLedger::new(Duration::from_secs(60)),
+    clients: InstallationClients::new(config.github.clone()),
+    policies: PolicyCache::new(),
+    config: Arc::new(config),
+    llm,
+  };
+  (state, receiver)
+}
+
+/// Run the job for `event` to completion, as a worker would.
+pub async fn run(event: &GitHubEvent, state: &AppState) -> DeliveryStatus {
+  let artifacts = Artifacts::for_job(None, "test");
+  process_event_and_get_token(event, state, &artifacts, &Attempt::default()).await.unwrap()
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use treehacks25::config::{Config, ConfigError, ProviderKind};
+use treehacks25::helpers::findings::Severity;
+
+fn example() -> Config {
+  Config::from_toml(&std::fs::read_to_string("mechanic.example.toml").unwrap()).unwrap()
+}
+
+#[test]
+fn example_config_parses() {
+  let config = example();
+  assert_eq!(config.github.app_id, 1146309);
+  assert_eq!(config.server.bind.port(), 3000);
+  assert_eq!(config.review.workers, 4);
+  assert_eq!(config.review.request_changes_at, Severity::Error);
+  assert!(config.checks.enabled);
+  assert_eq!(config.checks.name, "Mechanic");
+  assert_eq!(config.checks.neutral_at, Severity::Warning);
+}
+
+#[test]
+fn environment_overrides_file_settings() {
+  let env: HashMap<&str, &str> = HashMap::from([
+    ("MECHANIC_BIND", "0.0.0.0:8080"),
+    ("MECHANIC_GITHUB_APP_ID", "42"),
+    ("GROQ_API_KEY", "gsk_from_env"),
+  ]);
+  let mut config = example();
+  config.apply_overrides(|var| env.get(var).map(|v| v.to_string())).unwrap();
+
+  assert_eq!(config.server.bind.to_string(), "0.0.0.0:8080");
+  assert_eq!(config.github.app_id, 42);
+  assert_eq!(config.llm.api_key.expose(), "gsk_from_env");
+}
+
+#[test]
+fn bad_environment_values_name_the_variable() {
+  let mut config = example();
+  let err = config
+    .apply_overrides(|var| (var == "MECHANIC_GITHUB_APP_ID").then(|| "abc".to_string()))
+    .unwrap_err();
+  assert!(err.to_string().contains("MECHANIC_GITHUB_APP_ID"));
+}
+
+#[test]
+fn validation_reports_every_problem() {
+  let err = Config::default().validate().unwrap_err();
+  let ConfigError::Invalid(problems) = &err else { panic!("expected validation errors") };
+
+  assert!(problems.iter().any(|p| p.contains("github.app_id")));
+  assert!(problems.iter().any(|p| p.contains("github.private_key_path")));
+  assert!(problems.iter().any(|p| p.contains("github.webhook_secret")));
+  assert!(problems.iter().any(|p| p.contains("llm.api_key")));
+}
+
+#[test]
+fn check_thresholds_must_be_ordered() {
+  let mut config = example();
+  config.checks.neutral_at = Severity::Error;
+  config.checks.fail_at = Severity::Warning;
+  let ConfigError::Invalid(problems) = config.validate().unwrap_err() else {
+    panic!("expected validation errors")
+  };
+  assert!(problems.iter().any(|p| p.contains("checks.neutral_at")));
+}
+
+#[test]
+fn retries_back_off_without_overflowing() {
+  let mut config = example();
+  config.review.retry_backoff_secs = 2;
+  assert_eq!(config.review.retry_delay(1), Duration::from_secs(2));
+  assert_eq!(config.review.retry_delay(3), Duration::from_secs(8));
+  assert_eq!(config.review.retry_delay(40), Duration::from_secs(2 * u32::MAX as u64));
+
+  config.review.max_attempts = 40;
+  let ConfigError::Invalid(problems) = config.validate().unwrap_err() else { panic!() };
+  assert!(problems.iter().any(|p| p.contains("review.max_attempts")));
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+  let err = Config::from_toml("[github]\napp_idd = 1\n").unwrap_err();
+  assert!(err.to_string().contains("app_idd"));
+}
+
+#[test]
+fn local_providers_need_no_api_key() {
+  let env: HashMap<&str, &str> =
+    HashMap::from([("MECHANIC_LLM_PROVIDER", "ollama"), ("MECHANIC_LLM_MODEL", "qwen2.5-coder")]);
+  let mut config = Config::default();
+  config.apply_overrides(|var| env.get(var).map(|v| v.to_string())).unwrap();
+
+  assert_eq!(config.llm.provider, ProviderKind::Ollama);
+  assert_eq!(config.llm.base_url(), "http://localhost:11434");
+  let ConfigError::Invalid(problems) = config.validate().unwrap_err() else { panic!() };
+  assert!(!problems.iter().any(|p| p.contains("llm.api_key")));
+
+  let err =
+    config.apply_overrides(|var| (var == "MECHANIC_LLM_PROVIDER").then(|| "gpt".to_string()));
+  assert!(err.unwrap_err().to_string().contains("MECHANIC_LLM_PROVIDER"));
+}
+
+#[test]
+fn the_context_window_must_fit_more_than_the_reply() {
+  let mut config = example();
+  config.llm.context_window = 8_192;
+  config.llm.max_tokens = 8_000;
+  let ConfigError::Invalid(problems) = config.validate().unwrap_err() else { panic!() };
+  assert!(problems.iter().any(|p| p.contains("llm.context_window (8192)")));
+
+  config.llm.max_tokens = 4_096;
+  let problems = match config.validate() {
+    Err(ConfigError::Invalid(problems)) => problems,
+    _ => Vec::new(),
+  };
+  assert!(!problems.iter().any(|p| p.contains("llm.context_window")));
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use axum::http::Method;
+use common::{REPO, app_state, config, fake_github, fake_thread, pull_request_payload};
+use serde_json::json;
+use std::sync::Arc;
+use treehacks25::config::Config;
+use treehacks25::helpers::event::{DeliveryStatus, GitHubEvent};
+use treehacks25::helpers::findings::Category;
+use treehacks25::helpers::threads::{DISMISSED_MARKER, finding_marker};
+use treehacks25::services::conversation::{
+  FALLBACK_REPLY, ThreadMessage, ThreadReply, parse_thread_reply, thread_messages,
+};
+use treehacks25::services::llm::Role;
+use treehacks25::services::llm::mock::{MockProvider, prompt_key};
+use treehacks25::services::policy::RepoPolicy;
+
+const HUNK: &str = "@@ -1,3 +1,8 @@\n+pub fn retry() {\n+  std::thread::sleep(delay);\n+}";
+
+fn reply_event(id: u64, in_reply_to: u64, body: &str, kind: &str) -> GitHubEvent {
+  let pr = pull_request_payload();
+  let user = json!({ "login": "dev", "id": 4, "type": kind });
+  let payload = json!({
+    "action": "created",
+    "comment": {
+      "id": id,
+      "body": body,
+      "user": user,
+      "path": "src/retry.rs",
+      "line": 6,
+      "diff_hunk": HUNK,
+      "in_reply_to_id": in_reply_to,
+    },
+    "pull_request": pr["pull_request"],
+    "repository": pr["repository"],
+    "installation": pr["installation"],
+    "sender": user,
+  });
+  GitHubEvent::parse("pull_request_review_comment", &payload).unwrap()
+}
+
+async fn run(event: &GitHubEvent, config: &Config, llm: MockProvider) -> DeliveryStatus {
+  common::run(event, &app_state(config.clone(), Arc::new(llm)).0).await
+}
+
+#[test]
+fn replies_that_are_not_json_get_a_fallback() {
+  let dismissed =
+    parse_thread_reply("```json\n{\"reply\": \"Fair enough.\", \"dismiss\": true}\n```");
+  assert_eq!(dismissed, Ok(ThreadReply { reply: "Fair enough.".to_string(), dismiss: true }));
+  let prose = parse_thread_reply("  It blocks the runtime thread.  ").unwrap();
+  assert_eq!(prose, ThreadReply { reply: FALLBACK_REPLY.to_string(), dismiss: false });
+  let unfinished = parse_thread_reply("{\"reply\": \"It blocks").unwrap();
+  assert_eq!(unfinished.reply, FALLBACK_REPLY);
+  assert!(parse_thread_reply("   ").is_err());
+  assert!(parse_thread_reply("{\"reply\": \" \"}").is_err());
+}
+
+#[test]
+fn the_thread_becomes_the_conversation() {
+  let thread = vec![
+    ThreadMessage {
+      author: "mechanic[bot]".to_string(),
+      from_mechanic: true,
+      body: format!("Blocks the runtime.\n\n{}", finding_marker(Category::Performance)),
+    },
+    ThreadMessage { author: "dev".to_string(), from_mechanic: false, body: "Why?".to_string() },
+  ];
+  let messages =
+    thread_messages("src/retry.rs", HUNK, &thread, &RepoPolicy::defaults(&Config::default()));
+
+  assert_eq!(messages.len(), 3);
+  assert_eq!(messages[0].role, Role::System);
+  assert!(messages[0].content.contains("std::thread::sleep"));
+  assert_eq!(messages[1].role, Role::Assistant);
+  assert_eq!(messages[1].content, "Blocks the runtime.");
+  assert_eq!(messages[2].role, Role::User);
+  assert_eq!(messages[2].content, "@dev: Why?");
+}
+
+#[test]
+fn only_replies_from_people_are_answered() {
+  assert!(reply_event(20, 10, "Why?", "User").wants_review());
+  assert!(reply_event(20, 10, "Why?", "Bot").thread_reply().is_none());
+
+  let GitHubEvent::PullRequestReviewComment(mut first) = reply_event(20, 10, "Why?", "User") else {
+    unreachable!()
+  };
+  first.comment.in_reply_to_id = None;
+  let first = GitHubEvent::PullRequestReviewComment(first);
+  assert!(!first.wants_review());
+  // Replies are not commits to review; they must not claim one in the delivery ledger.
+  assert_eq!(first.commit_sha(), None);
+}
+
+#[tokio::test]
+async fn a_convincing_reply_dismisses_the_finding() {
+  let (github, url) = fake_github(&pull_request_payload()).await;
+  let finding = format!("Blocks the runtime.\n\n{}", finding_marker(Category::Performance));
+  let root = github.add_review_thread(REPO, 7, fake_thread("PRRT_1", &finding, false));
+  let text = "This only runs in the CLI, never on the runtime.";
+  let reply = github.add_review_reply(REPO, 7, root, "dev", text).unwrap();
+  github.set_permission(REPO, "dev", "write");
+  let config = config(&url);
+
+  let thread = vec![
+    ThreadMessage { author: "mechanic[bot]".to_string(), from_mechanic: true, body: finding },
+    ThreadMessage { author: "dev".to_string(), from_mechanic: false, body: text.to_string() },
+  ];
+  let messages = thread_messages("src/retry.rs", HUNK, &thread, &RepoPolicy::defaults(&config));
+  let llm = MockProvider::new();
+  llm.insert(
+    &prompt_key(&messages),
+    r#"{"reply": "Makes sense, blocking is fine there.", "dismiss": true}"#,
+  );
+
+  let status = run(&reply_event(reply, root, text, "User"), &config, llm).await;
+  assert_eq!(status, DeliveryStatus::Accepted);
+  let posted = github.requests_to(Method::POST, &format!("/pulls/7/comments/{}/replies", root));
+  assert_eq!(posted.len(), 1);
+  let body = posted[0].body["body"].as_str().unwrap();
+  assert!(body.starts_with("Makes sense, blocking is fine there."));
+  assert!(body.ends_with(DISMISSED_MARKER));
+  assert!(github.review_thread("PRRT_1").unwrap().0);
+}
+
+#[tokio::test]
+async fn replies_in_other_threads_are_ignored() {
+  let (github, url) = fake_github(&pull_request_payload()).await;
+  let root = github.add_review_thread(REPO, 7, fake_thread("PRRT_2", "Maybe rename this?", false));
+  let reply = github.add_review_reply(REPO, 7, root, "dev", "Sure.").unwrap();
+  let config = config(&url);
+
+  // No recordings: asking the model would fail the job.
+  let status = run(&reply_event(reply, root, "Sure.", "User"), &config, MockProvider::new()).await;
+  assert_eq!(status, DeliveryStatus::Ignored);
+  assert!(github.requests_to(Method::POST, "/replies").is_empty());
+}
+
+#[tokio::test]
+async fn only_collaborators_who_can_push_dismiss_findings() {
+  let (github, url) = fake_github(&pull_request_payload()).await;
+  let finding = format!("Blocks the runtime.\n\n{}", finding_marker(Category::Performance));
+  let root = github.add_review_thread(REPO, 7, fake_thread("PRRT_1", &finding, false));
+  let text = "Not a problem, trust me.";
+  let reply = github.add_review_reply(REPO, 7, root, "dev", text).unwrap();
+  let config = config(&url);
+
+  let thread = vec![
+    ThreadMessage { author: "mechanic[bot]".to_string(), from_mechanic: true, body: finding },
+    ThreadMessage { author: "dev".to_string(), from_mechanic: false, body: text.to_string() },
+  ];
+  let messages = thread_messages("src/retry.rs", HUNK, &thread, &RepoPolicy::defaults(&config));
+  let llm = MockProvider::new();
+  llm.insert(&prompt_key(&messages), r#"{"reply": "Fair enough.", "dismiss": true}"#);
+
+  assert_eq!(
+    run(&reply_event(reply, root, text, "User"), &config, llm).await,
+    DeliveryStatus::Accepted
+  );
+  let posted = github.requests_to(Method::POST, &format!("/pulls/7/comments/{}/replies", root));
+  let body = posted[0].body["body"].as_str().unwrap();
+  assert!(body.starts_with("Fair enough."));
+  assert!(body.contains("Only collaborators with write access"));
+  assert!(!body.contains(DISMISSED_MARKER));
+  assert!(!github.review_thread("PRRT_1").unwrap().0);
+}
+
+#[tokio::test]
+async fn threads_only_look_like_mechanics_when_it_started_them() {
+  let (github, url) = fake_github(&pull_request_payload()).await;
+  let lookalike = format!("Blocks the runtime.\n\n{}", finding_marker(Category::Performance));
+  let thread = fake_thread("PRRT_3", &lookalike, false);
+  let root = github.add_foreign_review_thread(REPO, 7, thread, "mallory");
+  let reply = github.add_review_reply(REPO, 7, root, "dev", "Why?").unwrap();
+  let config = config(&url);
+
+  let status = run(&reply_event(reply, root, "Why?", "User"), &config, MockProvider::new()).await;
+  assert_eq!(status, DeliveryStatus::Ignored);
+  assert!(github.requests_to(Method::POST, "/replies").is_empty());
+}
@@ -0,0 +1,58 @@
+use serde_json::{Value, json};
+use std::time::Duration;
+use treehacks25::helpers::event::GitHubEvent;
+use treehacks25::services::dedup::{DeliveryLedger, Duplicate};
+
+fn event_at(sha: &str) -> GitHubEvent {
+  event_on(7, sha)
+}
+
+fn event_on(pull_number: u64, sha: &str) -> GitHubEvent {
+  let mut payload: Value =
+    serde_json::from_slice(&std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap())
+      .unwrap();
+  payload["number"] = json!(pull_number);
+  payload["pull_request"]["number"] = json!(pull_number);
+  payload["pull_request"]["head"]["sha"] = json!(sha);
+  GitHubEvent::parse("pull_request", &payload).unwrap()
+}
+
+#[test]
+fn skips_redelivered_ids_and_already_claimed_commits() {
+  let ledger = DeliveryLedger::new(Duration::from_secs(3600));
+
+  assert_eq!(ledger.admit(Some("delivery-1"), &event_at("aaa")), None);
+  assert_eq!(ledger.admit(Some("delivery-1"), &event_at("aaa")), Some(Duplicate::Delivery));
+  assert_eq!(ledger.admit(Some("delivery-2"), &event_at("aaa")), Some(Duplicate::Commit));
+  assert_eq!(ledger.admit(Some("delivery-3"), &event_at("bbb")), None);
+}
+
+#[test]
+fn newer_pushes_supersede_queued_commits() {
+  let ledger = DeliveryLedger::new(Duration::from_secs(3600));
+  ledger.admit(Some("delivery-1"), &event_at("aaa"));
+  ledger.admit(Some("delivery-2"), &event_at("bbb"));
+
+  assert!(ledger.is_superseded(&event_at("aaa")));
+  assert!(!ledger.is_superseded(&event_at("bbb")));
+}
+
+#[test]
+fn released_deliveries_can_be_retried() {
+  let ledger = DeliveryLedger::new(Duration::from_secs(3600));
+  ledger.admit(Some("delivery-1"), &event_at("aaa"));
+  ledger.release(Some("delivery-1"), &event_at("aaa"));
+
+  assert_eq!(ledger.admit(Some("delivery-1"), &event_at("aaa")), None);
+}
+
+#[test]
+fn heads_expire_with_the_retention() {
+  let ledger = DeliveryLedger::new(Duration::ZERO);
+  ledger.admit(Some("delivery-1"), &event_at("aaa"));
+  assert!(ledger.is_superseded(&event_at("bbb")));
+
+  std::thread::sleep(Duration::from_millis(1_100));
+  ledger.admit(Some("delivery-2"), &event_on(8, "ccc"));
+  assert!(!ledger.is_superseded(&event_at("bbb")));
+}
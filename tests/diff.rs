@@ -0,0 +1,64 @@
+use treehacks25::helpers::diff::{LineKind, parse_unified_diff, render_for_prompt};
+
+fn fixture() -> String {
+  std::fs::read_to_string("tests/fixtures/pull_request.diff").unwrap()
+}
+
+#[test]
+fn parses_files_hunks_and_line_numbers() {
+  let files = parse_unified_diff(&fixture());
+  assert_eq!(files.len(), 3);
+
+  let new_file = &files[0];
+  assert_eq!(new_file.path, "src/retry.rs");
+  assert!(new_file.is_new);
+  assert_eq!(new_file.added_lines().collect::<Vec<_>>(), (1..=9).collect::<Vec<_>>());
+
+  let lib = &files[1];
+  assert_eq!(lib.hunks.len(), 2);
+  assert_eq!(lib.added_lines().collect::<Vec<_>>(), vec![2, 11, 42]);
+  assert_eq!(lib.hunks[1].section, "impl Client {");
+
+  let removed = lib.hunks[0].lines.iter().find(|l| l.kind == LineKind::Removed).unwrap();
+  assert_eq!(removed.old_line, Some(10));
+  assert_eq!(removed.new_line, None);
+
+  assert!(files[2].is_binary);
+}
+
+#[test]
+fn prompt_trims_context_and_skips_binaries() {
+  let files = parse_unified_diff(&fixture());
+  let prompt = render_for_prompt(&files, 1, 10_000);
+  assert_eq!(prompt.whole_files, vec!["src/retry.rs", "src/lib.rs"]);
+  let prompt = prompt.text;
+
+  assert!(prompt.contains("📄 File: src/lib.rs"));
+  assert!(prompt.contains("   11 +  body.split(',').map(|s| s.trim().to_string()).collect()"));
+  assert!(!prompt.contains("let client = client::Client::new();"));
+  assert!(!prompt.contains("logo.png"));
+}
+
+#[test]
+fn prompt_lists_files_that_do_not_fit() {
+  let files = parse_unified_diff(&fixture());
+  let retry = render_for_prompt(&files[..1], 3, 10_000).text;
+  let prompt = render_for_prompt(&files, 3, retry.len() + 20);
+
+  assert!(prompt.text.contains("📄 File: src/retry.rs"));
+  assert!(prompt.text.contains("changes to these files were left out: src/lib.rs"));
+  assert_eq!(prompt.whole_files, vec!["src/retry.rs"]);
+}
+
+#[test]
+fn files_that_do_not_fit_keep_the_hunks_that_do() {
+  let files = parse_unified_diff(&fixture());
+  let whole = render_for_prompt(&files[1..2], 1, 10_000).text;
+  let prompt = render_for_prompt(&files[1..2], 1, whole.len() - 10);
+
+  assert!(prompt.text.contains("📄 File: src/lib.rs"));
+  assert!(prompt.text.contains("   11 +  body.split(',').map(|s| s.trim().to_string()).collect()"));
+  assert!(!prompt.text.contains("impl Client {"));
+  assert!(prompt.text.contains("some changes to these files were left out: src/lib.rs"));
+  assert!(prompt.whole_files.is_empty());
+}
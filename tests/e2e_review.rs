@@ -0,0 +1,250 @@
+//! Drives a signed webhook delivery through the handler, the queue and the review pipeline,
+//! with recorded model completions and the bundled fake GitHub API.
+mod common;
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::IntoResponse;
+use common::{BASE_SHA, HEAD_SHA, REPO, config, fake_thread, pull_request_payload, push_event};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use treehacks25::fake_github::{FakeGitHub, FakeReviewThread};
+use treehacks25::handlers::webhook::github_wh_test_handler;
+use treehacks25::helpers::findings::Category;
+use treehacks25::helpers::redact::Secret;
+use treehacks25::helpers::signature::sign_payload;
+use treehacks25::helpers::sticky::STICKY_MARKER;
+use treehacks25::helpers::threads::finding_marker;
+use treehacks25::services::llm::{ChatRequest, LlmError, LlmProvider, provider_from_config};
+use treehacks25::services::queue::{JobReceiver, ReviewJob, spawn_workers};
+use treehacks25::services::store::JobState;
+use treehacks25::state::AppState;
+
+const SECRET: &str = "mechanic-test-secret";
+
+async fn fake_github() -> (FakeGitHub, String) {
+  let (github, url) = common::fake_github(&pull_request_payload()).await;
+  // Threads from the previous push: one problem since fixed, one still reported.
+  for (id, line, category) in
+    [("PRRT_fixed", 12, Category::Bug), ("PRRT_open", 6, Category::Performance)]
+  {
+    let body = format!("An earlier finding.\n\n{}", finding_marker(category));
+    let thread = FakeReviewThread { original_line: line, ..fake_thread(id, &body, true) };
+    github.add_review_thread(REPO, 7, thread);
+  }
+  (github, url)
+}
+
+fn fixture() -> Vec<u8> {
+  std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap()
+}
+
+fn app_state(github_url: &str) -> (AppState, JobReceiver) {
+  let mut config = config(github_url);
+  config.github.webhook_secret = Secret::new(SECRET);
+  config.review.max_attempts = 1;
+  config.validate().unwrap();
+  let llm = provider_from_config(&config.llm);
+  common::app_state(config, llm)
+}
+
+#[tokio::test]
+async fn signed_pull_request_delivery_posts_a_review() {
+  let (github, github_url) = fake_github().await;
+  let (state, receiver) = app_state(&github_url);
+  spawn_workers(&state, receiver, 1);
+
+  let body = fixture();
+  let mut headers = HeaderMap::new();
+  headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+  headers.insert("X-GitHub-Delivery", HeaderValue::from_static("e2e-delivery-1"));
+  let signature = sign_payload(SECRET.as_bytes(), &body);
+  headers.insert("X-Hub-Signature-256", HeaderValue::from_str(&signature).unwrap());
+
+  let response =
+    github_wh_test_handler(State(state.clone()), headers, Bytes::from(body)).await.into_response();
+  assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+  // Wait for the worker to finish the job.
+  let mut job = state.store.for_delivery("e2e-delivery-1").remove(0);
+  assert_eq!(job.id, "e2e-delivery-1.1");
+  for _ in 0..100 {
+    if job.state.is_finished() {
+      break;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    job = state.store.get(&job.id).unwrap();
+  }
+  assert!(matches!(job.state, JobState::Succeeded { .. }), "job ended as {:?}", job.state);
+
+  // The app authenticated as the installation, then posted exactly one review.
+  assert_eq!(
+    github.requests_to(Method::POST, "/app/installations/61234987/access_tokens").len(),
+    1
+  );
+  let reviews = github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/pulls/7/reviews");
+  assert_eq!(reviews.len(), 1);
+  let review = &reviews[0].body;
+  assert_eq!(review["commit_id"], HEAD_SHA);
+  assert_eq!(review["event"], "COMMENT");
+  assert!(review["body"].as_str().unwrap().starts_with("Adds a `retry` helper"));
+  assert_eq!(
+    review["comments"],
+    json!([{
+      "path": "src/retry.rs",
+      "line": 6,
+      "side": "RIGHT",
+      "body": "**Warning** (performance): `retry` blocks the async runtime with `std::thread::sleep`.\n\n<!-- mechanic:finding performance -->"
+    }])
+  );
+
+  // A check run was started on the head commit and finished neutral: one warning, no errors.
+  let started = github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/check-runs");
+  assert_eq!(started.len(), 1);
+  assert_eq!(started[0].body["name"], "Mechanic");
+  assert_eq!(started[0].body["head_sha"], HEAD_SHA);
+  assert_eq!(started[0].body["status"], "in_progress");
+  let completed: Vec<_> =
+    github.requests().into_iter().filter(|r| r.method == Method::PATCH).collect();
+  assert_eq!(completed.len(), 1);
+  let check = &completed[0].body;
+  assert_eq!(check["status"], "completed");
+  assert_eq!(check["conclusion"], "neutral");
+  assert_eq!(check["output"]["annotations"][0]["path"], "src/retry.rs");
+  assert_eq!(check["output"]["annotations"][0]["start_line"], 6);
+  assert_eq!(check["output"]["annotations"][0]["annotation_level"], "warning");
+
+  // The commit status went from pending at queue time to success, linking to the review.
+  let statuses: Vec<Value> = github
+    .requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA))
+    .into_iter()
+    .map(|r| r.body)
+    .collect();
+  assert_eq!(statuses.len(), 2);
+  assert_eq!(statuses[0]["state"], "pending");
+  assert_eq!(statuses[0]["context"], "mechanic/review");
+  assert_eq!(statuses[1]["state"], "success");
+  assert_eq!(statuses[1]["description"], "1 finding(s), worst: warning");
+  assert!(statuses[1]["target_url"].as_str().unwrap().contains("#pullrequestreview-"));
+
+  // The report also went into the pull request's sticky comment.
+  let comments = github.requests_to(Method::POST, "/repos/vznh/mechanic-demo/issues/7/comments");
+  assert_eq!(comments.len(), 1);
+  let sticky = comments[0].body["body"].as_str().unwrap();
+  assert!(sticky.starts_with(STICKY_MARKER));
+  assert!(sticky.contains("## Mechanic review"));
+
+  // The fixed finding's thread was resolved; the one reported again stays open.
+  assert!(github.review_thread("PRRT_fixed").unwrap().0);
+  assert!(!github.review_thread("PRRT_open").unwrap().0);
+}
+
+#[tokio::test]
+async fn a_delivery_that_cannot_be_queued_errors_its_pending_status() {
+  let (github, github_url) = fake_github().await;
+  let (state, receiver) = app_state(&github_url);
+  // No workers: the queue is closed.
+  drop(receiver);
+
+  let body = fixture();
+  let mut headers = HeaderMap::new();
+  headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+  headers.insert("X-GitHub-Delivery", HeaderValue::from_static("e2e-delivery-2"));
+  let signature = sign_payload(SECRET.as_bytes(), &body);
+  headers.insert("X-Hub-Signature-256", HeaderValue::from_str(&signature).unwrap());
+
+  let response =
+    github_wh_test_handler(State(state.clone()), headers, Bytes::from(body)).await.into_response();
+  assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+  let statuses: Vec<Value> = github
+    .requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA))
+    .into_iter()
+    .map(|r| r.body)
+    .collect();
+  assert_eq!(statuses.len(), 2);
+  assert_eq!(statuses[0]["state"], "pending");
+  assert_eq!(statuses[1]["state"], "error");
+  assert_eq!(statuses[1]["description"], "Review failed: review queue is closed");
+  let job = state.store.for_delivery("e2e-delivery-2").remove(0);
+  assert!(matches!(job.state, JobState::Failed { .. }));
+}
+
+/// The recorded completions, except that the first request fails.
+struct FlakyModel {
+  recorded: Arc<dyn LlmProvider>,
+  failed: AtomicBool,
+}
+
+#[async_trait]
+impl LlmProvider for FlakyModel {
+  fn name(&self) -> &str {
+    "flaky"
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    if !self.failed.swap(true, Ordering::SeqCst) {
+      return Err(LlmError::Status { status: 503, body: "overloaded".to_string() });
+    }
+    self.recorded.chat(request).await
+  }
+
+  fn max_context(&self) -> usize {
+    self.recorded.max_context()
+  }
+}
+
+#[tokio::test]
+async fn a_retried_review_reports_once_on_one_check_run() {
+  let (github, github_url) = fake_github().await;
+  let mut config = config(&github_url);
+  config.review.retry_backoff_secs = 0;
+  let recorded = provider_from_config(&config.llm);
+  let llm = FlakyModel { recorded, failed: AtomicBool::new(false) };
+  let (state, receiver) = common::app_state(config, Arc::new(llm));
+  spawn_workers(&state, receiver, 1);
+
+  let job = state.store.create(Some("e2e-delivery-3".to_string()), &push_event()).unwrap();
+  let event = push_event();
+  state.queue.enqueue(ReviewJob { id: job.clone(), delivery_id: None, event }).unwrap();
+  for _ in 0..100 {
+    if state.store.get(&job).unwrap().state.is_finished() {
+      break;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+  }
+  let job = state.store.get(&job).unwrap();
+  assert!(matches!(job.state, JobState::Succeeded { .. }), "job ended as {:?}", job.state);
+
+  // The failed attempt left its check run in progress for the retry to finish.
+  assert_eq!(github.requests_to(Method::POST, "/check-runs").len(), 1);
+  let completed: Vec<_> =
+    github.requests().into_iter().filter(|r| r.method == Method::PATCH).collect();
+  assert_eq!(completed.len(), 1);
+  assert_eq!(completed[0].body["conclusion"], "neutral");
+  let statuses = github.requests_to(Method::POST, &format!("/statuses/{}", HEAD_SHA));
+  assert_eq!(statuses.len(), 1);
+  assert_eq!(statuses[0].body["state"], "success");
+  assert_eq!(github.requests_to(Method::POST, "/pulls/7/reviews").len(), 1);
+}
+
+#[tokio::test]
+async fn an_invalid_policy_file_is_reported_with_the_review() {
+  let (github, github_url) = fake_github().await;
+  github.add_file(REPO, BASE_SHA, ".mechanic.toml", "max_comments = \"lots\"\n");
+  let (state, _receiver) = app_state(&github_url);
+
+  common::run(&push_event(), &state).await;
+
+  let completed: Vec<_> =
+    github.requests().into_iter().filter(|r| r.method == Method::PATCH).collect();
+  let summary = completed[0].body["output"]["summary"].as_str().unwrap();
+  assert!(summary.starts_with("⚠️ `.mechanic.toml` is invalid, so the default settings were used"));
+  let comments = github.requests_to(Method::POST, "/issues/7/comments");
+  let sticky = comments[0].body["body"].as_str().unwrap();
+  assert!(sticky.contains("`.mechanic.toml` is invalid"));
+}
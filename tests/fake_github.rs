@@ -0,0 +1,183 @@
+use axum::http::Method;
+use octocrab::Octocrab;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use treehacks25::config::GitHubConfig;
+use treehacks25::fake_github::{FakeGitHub, FakePullRequest};
+use treehacks25::helpers::jwt::{exchange_jwt_for_installation_token, fetch_app_slug};
+use treehacks25::helpers::octo::{
+  create_mechanic_branch, init_octocrab, post_markdown_as_comment, upsert_sticky_comment,
+};
+use treehacks25::helpers::redact::Secret;
+use treehacks25::helpers::sticky::{STICKY_MARKER, StickyEntry, parse_sticky};
+use treehacks25::services::tokens::InstallationClients;
+
+const REPO: &str = "vznh/mechanic-demo";
+
+fn pull(number: u64, head_sha: &str) -> FakePullRequest {
+  FakePullRequest {
+    number,
+    title: format!("Change {}", number),
+    author: "vznh".to_string(),
+    head_ref: format!("feature-{}", number),
+    head_sha: head_sha.to_string(),
+    base_ref: "main".to_string(),
+    base_sha: "1a2b3c4d".to_string(),
+    diff: "diff --git a/a.rs b/a.rs\n".to_string(),
+  }
+}
+
+async fn start() -> (FakeGitHub, Octocrab, String) {
+  let fake = FakeGitHub::new();
+  fake.add_pull_request(REPO, pull(6, "aaaa1111"));
+  fake.add_pull_request(REPO, pull(7, "bbbb2222"));
+  let url = fake.start().await.unwrap();
+  let octo = init_octocrab(&url, "ghs_test".to_string()).unwrap();
+  (fake, octo, url)
+}
+
+#[tokio::test]
+async fn issues_installation_tokens_for_app_jwts_only() {
+  let (_, _, url) = start().await;
+
+  let token = exchange_jwt_for_installation_token(&url, &Secret::new("a.b.c"), 42).await.unwrap();
+  assert_eq!(token.token.expose(), "ghs_fake_installation_42");
+  assert!(token.expires_at > chrono::Utc::now());
+
+  assert!(exchange_jwt_for_installation_token(&url, &Secret::new("nope"), 42).await.is_err());
+}
+
+#[tokio::test]
+async fn the_bot_login_comes_from_the_apps_slug() {
+  let (_, _, url) = start().await;
+  let github = GitHubConfig {
+    api_url: url.clone(),
+    private_key_path: PathBuf::from("tests/fixtures/test-app.pem"),
+    ..GitHubConfig::default()
+  };
+
+  let clients = InstallationClients::new(github);
+  assert_eq!(clients.bot_login().await.unwrap(), "mechanic[bot]");
+  assert!(fetch_app_slug(&url, &Secret::new("nope")).await.is_err());
+}
+
+#[tokio::test]
+async fn installation_tokens_are_minted_once_per_installation() {
+  let (fake, _, url) = start().await;
+  let github = GitHubConfig {
+    api_url: url.clone(),
+    private_key_path: PathBuf::from("tests/fixtures/test-app.pem"),
+    ..GitHubConfig::default()
+  };
+  let clients = InstallationClients::new(github);
+
+  let (a, b, c) = tokio::join!(clients.client(42), clients.client(42), clients.client(43));
+  assert!(a.is_ok() && b.is_ok() && c.is_ok());
+  let minted = |id: u64| fake.requests_to(Method::POST, &format!("/{}/access_tokens", id)).len();
+  assert_eq!((minted(42), minted(43)), (1, 1));
+
+  clients.invalidate(42).await;
+  clients.client(42).await.unwrap();
+  assert_eq!((minted(42), minted(43)), (2, 1));
+}
+
+#[tokio::test]
+async fn serves_pull_requests_and_diffs() {
+  let (_, octo, _) = start().await;
+
+  let pr = octo.pulls("vznh", "mechanic-demo").get(7).await.unwrap();
+  assert_eq!(pr.head.sha, "bbbb2222");
+  let diff = octo.pulls("vznh", "mechanic-demo").get_diff(7).await.unwrap();
+  assert_eq!(diff, "diff --git a/a.rs b/a.rs\n");
+  assert!(octo.pulls("vznh", "mechanic-demo").get(8).await.is_err());
+}
+
+#[tokio::test]
+async fn records_comments_on_pull_requests() {
+  let (fake, octo, _) = start().await;
+
+  post_markdown_as_comment(&octo, "vznh", "mechanic-demo", 7, "Great work!").await.unwrap();
+  let comments = fake.requests_to(Method::POST, "/issues/7/comments");
+  assert_eq!(comments.len(), 1);
+  assert_eq!(comments[0].body["body"], "Great work!");
+
+  let listed = octo.issues("vznh", "mechanic-demo").list_comments(7).send().await.unwrap();
+  assert_eq!(listed.items.len(), 1);
+}
+
+#[tokio::test]
+async fn creates_branches_that_do_not_exist_yet() {
+  let (fake, octo, _) = start().await;
+
+  // Pull request 6 isn't the latest; the branch is still named after it.
+  let first = create_mechanic_branch(&octo, "vznh", "mechanic-demo", 6, "aaaa1111").await.unwrap();
+  let second = create_mechanic_branch(&octo, "vznh", "mechanic-demo", 6, "cccc3333").await.unwrap();
+  assert_eq!((first.as_str(), second.as_str()), ("mechanic-6", "mechanic-6-1"));
+  let refs: Vec<(Value, Value)> = fake
+    .requests_to(Method::POST, "/git/refs")
+    .into_iter()
+    .map(|r| (r.body["ref"].clone(), r.body["sha"].clone()))
+    .collect();
+  assert_eq!(
+    refs,
+    vec![
+      (json!("refs/heads/mechanic-6"), json!("aaaa1111")),
+      (json!("refs/heads/mechanic-6-1"), json!("cccc3333"))
+    ]
+  );
+}
+
+#[tokio::test]
+async fn serves_file_contents_and_records_check_runs() {
+  let (fake, octo, _) = start().await;
+  fake.add_file(REPO, "bbbb2222", "src/lib.rs", "pub mod retry;\n");
+
+  let contents = octo
+    .repos("vznh", "mechanic-demo")
+    .get_content()
+    .path("src/lib.rs")
+    .r#ref("bbbb2222")
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(contents.items[0].decoded_content().as_deref(), Some("pub mod retry;\n"));
+
+  let created: Value = octo
+    .post(
+      "/repos/vznh/mechanic-demo/check-runs",
+      Some(&json!({ "name": "Mechanic", "head_sha": "bbbb2222", "status": "in_progress" })),
+    )
+    .await
+    .unwrap();
+  let route = format!("/repos/vznh/mechanic-demo/check-runs/{}", created["id"]);
+  let _: Value = octo.patch(route, Some(&json!({ "conclusion": "success" }))).await.unwrap();
+
+  let requests = fake.requests();
+  assert_eq!(requests.len(), 2);
+  assert_eq!(requests[1].method, Method::PATCH);
+  assert_eq!(requests[1].body["conclusion"], "success");
+}
+
+#[tokio::test]
+async fn sticky_comment_is_edited_in_place() {
+  let (fake, octo, _) = start().await;
+  post_markdown_as_comment(&octo, "vznh", "mechanic-demo", 7, "Great work!").await.unwrap();
+  // Someone else's comment that merely looks like the sticky one.
+  let impostor = format!("{}\nNothing to see here.", STICKY_MARKER);
+  fake.add_comment(REPO, 7, "mallory", &impostor);
+
+  for (sha, markdown) in [("aaaa1111", "First."), ("bbbb2222", "Second.")] {
+    let entry = StickyEntry { commit_sha: sha.to_string(), markdown: markdown.to_string() };
+    upsert_sticky_comment(&octo, "vznh", "mechanic-demo", 7, "mechanic[bot]", entry).await.unwrap();
+  }
+
+  // The other comments are left alone; the sticky one is created once, then edited.
+  assert_eq!(fake.requests_to(Method::POST, "/issues/7/comments").len(), 2);
+  assert_eq!(fake.requests().iter().filter(|r| r.method == Method::PATCH).count(), 1);
+  let comments = octo.issues("vznh", "mechanic-demo").list_comments(7).send().await.unwrap();
+  assert_eq!(comments.items.len(), 3);
+  assert_eq!(comments.items[1].body.as_deref(), Some(impostor.as_str()));
+  let entries = parse_sticky(comments.items[2].body.as_deref().unwrap());
+  let shas: Vec<&str> = entries.iter().map(|e| e.commit_sha.as_str()).collect();
+  assert_eq!(shas, vec!["bbbb2222", "aaaa1111"]);
+}
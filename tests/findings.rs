@@ -0,0 +1,66 @@
+use treehacks25::helpers::findings::{Category, Severity, parse_report, report_schema};
+
+#[test]
+fn parses_a_valid_report() {
+  let reply = r#"{
+    "summary": "Adds a retry helper.",
+    "findings": [{
+      "file": "src/retry.rs", "start_line": 4, "end_line": 5, "severity": "warning",
+      "category": "performance", "message": "Sleeps on the caller's thread.",
+      "suggested_fix": null, "confidence": 0.8
+    }]
+  }"#;
+  let (report, dropped) = parse_report(reply).unwrap();
+
+  assert!(dropped.is_empty());
+  assert_eq!(report.summary, "Adds a retry helper.");
+  let finding = &report.findings[0];
+  assert_eq!((finding.start_line, finding.end_line), (4, 5));
+  assert_eq!(finding.severity, Severity::Warning);
+  assert_eq!(finding.category, Category::Performance);
+  assert_eq!(finding.suggested_fix, None);
+}
+
+#[test]
+fn repairs_what_it_can() {
+  let reply = "<think>hmm</think>\n```json\n{\"summary\": \"ok\", \"findings\": [{\
+    \"file\": \"./src/lib.rs\", \"line\": \"11\", \"start_line\": 12, \"severity\": \"High\", \
+    \"category\": \"correctness\", \"message\": \"Off by one.\", \"confidence\": 3}]}\n```";
+  let (report, dropped) = parse_report(reply).unwrap();
+
+  assert!(dropped.is_empty());
+  let finding = &report.findings[0];
+  assert_eq!(finding.file, "src/lib.rs");
+  assert_eq!((finding.start_line, finding.end_line), (11, 12));
+  assert_eq!(finding.severity, Severity::Error);
+  assert_eq!(finding.category, Category::Other);
+  assert_eq!(finding.confidence, 1.0);
+}
+
+#[test]
+fn drops_findings_that_cannot_be_repaired() {
+  let reply = r#"{"summary": "", "findings": [
+    {"file": "src/lib.rs", "end_line": 2, "severity": "catastrophic", "message": "?"},
+    {"file": "src/lib.rs", "severity": "info", "message": "No line."},
+    {"file": "src/lib.rs", "end_line": 2, "severity": "info", "message": "Fine."}
+  ]}"#;
+  let (report, dropped) = parse_report(reply).unwrap();
+
+  assert_eq!(report.findings.len(), 1);
+  assert_eq!(dropped.len(), 2);
+  assert!(dropped[0].contains("severity"));
+}
+
+#[test]
+fn rejects_replies_without_a_report() {
+  assert!(parse_report("I could not review this.").is_err());
+  assert!(parse_report("{\"summary\": \"x\"}").is_err());
+  assert!(parse_report("{\"summary\": \"x\", \"findings\": [").is_err());
+}
+
+#[test]
+fn schema_lists_every_finding_field() {
+  let schema = report_schema();
+  let required = schema["properties"]["findings"]["items"]["required"].as_array().unwrap();
+  assert_eq!(required.len(), 8);
+}
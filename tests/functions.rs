@@ -0,0 +1,271 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+use treehacks25::config::Config;
+use treehacks25::helpers::artifacts::Artifacts;
+use treehacks25::helpers::diff::{FileDiff, parse_unified_diff};
+use treehacks25::helpers::functions::{ChangedFunction, extract_functions, supports};
+use treehacks25::services::llm::{ChatRequest, LlmError, LlmProvider};
+use treehacks25::services::policy::RepoPolicy;
+use treehacks25::services::reviewer::{render_functions, request_review};
+
+const SHA: &str = "4e2b8a6c1d9f0e3a7b5c8d2f6a1e9b3c7d0f5a48";
+
+/// A diff of `path` that adds the `added` lines of `contents` and keeps the rest.
+fn diff(path: &str, contents: &str, added: &[u32]) -> FileDiff {
+  let lines: Vec<&str> = contents.lines().collect();
+  let kept = lines.len() - added.len();
+  let mut text = format!(
+    "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -1,{} +1,{} @@\n",
+    kept,
+    lines.len()
+  );
+  for (number, line) in (1..).zip(&lines) {
+    let prefix = if added.contains(&number) { '+' } else { ' ' };
+    text.push_str(&format!("{}{}\n", prefix, line));
+  }
+  parse_unified_diff(&text).remove(0)
+}
+
+fn extract(path: &str, contents: &str, added: &[u32]) -> Vec<ChangedFunction> {
+  extract_functions(&diff(path, contents, added), contents, SHA)
+}
+
+fn names(functions: &[ChangedFunction]) -> Vec<&str> {
+  functions.iter().map(|f| f.name.as_str()).collect()
+}
+
+const RUST: &str = "use std::time::Duration;
+
+pub fn retry<T>(mut f: impl FnMut() -> Option<T>, attempts: u32) -> Option<T> {
+  for _ in 0..attempts {
+    if let Some(v) = f() { return Some(v); }
+    std::thread::sleep(Duration::from_millis(100));
+  }
+  None
+}
+
+impl Client {
+  const TIMEOUT: u64 = 60;
+
+  pub fn fetch(&self, url: &str) -> String {
+    let body = self.get(url);
+    retry(|| self.parse(&body), 3).unwrap_or_default()
+  }
+}
+";
+
+#[test]
+fn rust_changes_map_to_functions_methods_and_impls() {
+  let functions = extract("src/retry.rs", RUST, &[6, 12, 15]);
+  assert_eq!(names(&functions), vec!["retry", "impl Client", "Client::fetch"]);
+
+  let retry = &functions[0];
+  assert_eq!((retry.file.as_str(), retry.commit_id.as_str()), ("src/retry.rs", SHA));
+  assert_eq!((retry.start_line, retry.end_line), (3, 9));
+  assert!(retry.body.starts_with("pub fn retry<T>") && retry.body.ends_with("  None\n}"));
+  assert_eq!(retry.dependencies, vec!["f", "Some", "std::thread::sleep", "Duration::from_millis"]);
+
+  let fetch = &functions[2];
+  assert_eq!((fetch.start_line, fetch.end_line), (14, 17));
+  assert_eq!(fetch.dependencies, vec!["get", "retry", "parse", "unwrap_or_default"]);
+}
+
+#[test]
+fn unchanged_functions_are_left_out() {
+  assert!(extract("src/retry.rs", RUST, &[1]).is_empty());
+  assert_eq!(names(&extract("src/retry.rs", RUST, &[16, 17])), vec!["Client::fetch"]);
+}
+
+#[test]
+fn changed_signatures_of_indented_methods_belong_to_the_method() {
+  assert_eq!(names(&extract("src/retry.rs", RUST, &[14])), vec!["Client::fetch"]);
+
+  let python = "class Client:\n    def fetch(self, url, timeout=30):\n        return get(url)\n";
+  assert_eq!(names(&extract("client.py", python, &[2])), vec!["Client.fetch"]);
+
+  let java = "class A {\n  String fetch(String url, int timeout) {\n    return get(url);\n  }\n}\n";
+  assert_eq!(names(&extract("src/A.java", java, &[2])), vec!["A.fetch"]);
+
+  let typescript = "export class Client {\n  fetch(url: string, timeout = 30): string {\n    return get(url);\n  }\n}\n";
+  assert_eq!(names(&extract("src/client.ts", typescript, &[2])), vec!["Client.fetch"]);
+}
+
+#[test]
+fn removed_lines_belong_to_the_function_around_them() {
+  let diff = |removed_after: u32| {
+    let text = format!(
+      "diff --git a/src/retry.rs b/src/retry.rs\n--- a/src/retry.rs\n+++ b/src/retry.rs\n\
+       @@ -{0},3 +{0},2 @@\n {1}\n-  removed();\n {2}\n",
+      removed_after,
+      RUST.lines().nth(removed_after as usize - 1).unwrap(),
+      RUST.lines().nth(removed_after as usize).unwrap()
+    );
+    parse_unified_diff(&text).remove(0)
+  };
+  let inside = extract_functions(&diff(5), RUST, SHA);
+  assert_eq!(names(&inside), vec!["retry"]);
+  // Between `retry` and the impl: a function removed there belonged to neither.
+  assert!(extract_functions(&diff(9), RUST, SHA).is_empty());
+}
+
+#[test]
+fn python_methods_are_qualified_with_their_class() {
+  let source = "import time
+
+class Client:
+    def fetch(self, url):
+        body = self.session.get(url)
+        return parse(body)
+
+def parse(body):
+    return [part.strip() for part in body.split(',')]
+";
+  let functions = extract("client.py", source, &[5, 9]);
+  assert_eq!(names(&functions), vec!["Client.fetch", "parse"]);
+  assert_eq!(functions[0].dependencies, vec!["get", "parse"]);
+  assert_eq!(functions[1].dependencies, vec!["strip", "split"]);
+}
+
+#[test]
+fn typescript_arrow_functions_take_their_variables_name() {
+  let source = "export class Client {
+  fetch(url: string): Promise<string> {
+    return this.http.get(url);
+  }
+}
+
+export const retry = async <T>(f: () => Promise<T>): Promise<T> => {
+  return [1, 2, 3].reduce((last) => last.catch(() => f()), f());
+};
+";
+  let functions = extract("src/client.ts", source, &[3, 8]);
+  assert_eq!(names(&functions), vec!["Client.fetch", "retry"]);
+  // The callbacks are anonymous, so their changes belong to `retry`.
+  assert_eq!(functions[1].dependencies, vec!["reduce", "catch", "f"]);
+}
+
+#[test]
+fn javascript_functions_and_object_methods() {
+  let source = "function parse(body) {
+  return body.split(',');
+}
+
+module.exports = {
+  fetch: function (url) {
+    return parse(get(url));
+  },
+};
+";
+  let functions = extract("lib/client.js", source, &[2, 7]);
+  assert_eq!(names(&functions), vec!["parse", "fetch"]);
+  assert_eq!(functions[1].dependencies, vec!["parse", "get"]);
+}
+
+#[test]
+fn go_methods_are_qualified_with_their_receiver() {
+  let source = "package client
+
+func (c *Client) Fetch(url string) string {
+\tresp := c.http.Get(url)
+\ttime.Sleep(time.Second)
+\treturn parse(resp)
+}
+";
+  let functions = extract("client/client.go", source, &[5]);
+  assert_eq!(names(&functions), vec!["Client.Fetch"]);
+  assert_eq!(functions[0].dependencies, vec!["Get", "Sleep", "parse"]);
+}
+
+#[test]
+fn java_methods_and_constructors() {
+  let source = "class Client {
+  private final Http http;
+
+  Client(Http http) {
+    this.http = Objects.requireNonNull(http);
+  }
+
+  String fetch(String url) {
+    return parse(http.get(url));
+  }
+}
+";
+  let functions = extract("src/Client.java", source, &[2, 5, 9]);
+  assert_eq!(names(&functions), vec!["Client", "Client.Client", "Client.fetch"]);
+  assert_eq!(functions[1].dependencies, vec!["requireNonNull"]);
+  assert_eq!(functions[2].dependencies, vec!["parse", "get"]);
+}
+
+#[test]
+fn other_files_have_no_functions() {
+  assert!(supports("src/lib.rs") && supports("web/app.tsx") && supports("Main.java"));
+  assert!(!supports("README.md") && !supports("Makefile"));
+  assert!(extract("README.md", "# Title\n", &[1]).is_empty());
+}
+
+/// Answers every review with no findings and keeps the prompts it was sent.
+#[derive(Default)]
+struct Recorder {
+  prompts: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl LlmProvider for Recorder {
+  fn name(&self) -> &str {
+    "recorder"
+  }
+
+  async fn chat(&self, request: &ChatRequest) -> Result<String, LlmError> {
+    self.prompts.lock().unwrap().push(request.messages[0].content.clone());
+    Ok(r#"{"summary": "Fine.", "findings": []}"#.to_string())
+  }
+
+  fn max_context(&self) -> usize {
+    131_072
+  }
+}
+
+#[tokio::test]
+async fn changed_functions_reach_the_review_prompt() {
+  let config = Config::default();
+  let file = diff("src/retry.rs", RUST, &[6, 15]);
+  let functions = extract_functions(&file, RUST, SHA);
+  let changes = "src/retry.rs\n+  std::thread::sleep(Duration::from_millis(100));";
+  let recorder = Recorder::default();
+  let rendered = render_functions(&functions, changes, &config.review, &config.llm, &recorder);
+
+  let policy = RepoPolicy::defaults(&config);
+  let artifacts = Artifacts::for_job(None, "functions");
+  request_review(SHA, changes, &rendered, &config.llm, &policy, &recorder, &artifacts)
+    .await
+    .unwrap();
+
+  let prompt = recorder.prompts.lock().unwrap().remove(0);
+  assert!(prompt.contains("### `retry` in `src/retry.rs` (lines 3-9)"));
+  assert!(prompt.contains("### `Client::fetch` in `src/retry.rs` (lines 14-17)"));
+  assert!(prompt.contains("Calls: `get`, `retry`, `parse`, `unwrap_or_default`"));
+  assert!(prompt.contains(&functions[0].body) && prompt.contains(&functions[1].body));
+  // The functions come after the diff, as context for it.
+  assert!(prompt.find(changes).unwrap() < prompt.find("### `retry`").unwrap());
+}
+
+#[test]
+fn functions_only_take_the_room_the_diff_leaves() {
+  let mut config = Config::default();
+  let functions = extract("src/retry.rs", RUST, &[6, 15]);
+  let recorder = Recorder::default();
+  let (review, llm) = (&mut config.review, &config.llm);
+
+  review.max_diff_chars = 10_000;
+  let both = render_functions(&functions, "diff", review, llm, &recorder);
+  assert!(both.contains("`retry`") && both.contains("`Client::fetch`"));
+
+  // Room for the short method only.
+  review.max_diff_chars = "diff".len() + 300;
+  let one = render_functions(&functions, "diff", review, llm, &recorder);
+  assert!(!one.contains("### `retry`") && one.contains("`Client::fetch`"));
+  assert!(one.ends_with("(1 more changed function(s) left out for space.)"));
+
+  assert_eq!(render_functions(&functions, &"x".repeat(10_000), review, llm, &recorder), "");
+  assert_eq!(render_functions(&[], "diff", review, llm, &recorder), "");
+}
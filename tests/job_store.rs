@@ -0,0 +1,123 @@
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::time::Duration;
+use treehacks25::helpers::event::GitHubEvent;
+use treehacks25::services::controls::PullRequestControls;
+use treehacks25::services::store::{JobState, JobStore};
+
+fn event() -> GitHubEvent {
+  let payload: Value =
+    serde_json::from_slice(&std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap())
+      .unwrap();
+  GitHubEvent::parse("pull_request", &payload).unwrap()
+}
+
+fn log_path(name: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mechanic-{}-{}.log", name, std::process::id()));
+  let _ = std::fs::remove_file(&path);
+  path
+}
+
+#[test]
+fn unfinished_jobs_survive_a_restart() {
+  let path = log_path("restart");
+  {
+    let store = JobStore::open(&path).unwrap();
+    let done = store.create(Some("delivery-1".into()), &event()).unwrap();
+    store.transition(&done, JobState::Running { attempt: 1 }).unwrap();
+    store.transition(&done, JobState::Succeeded { result: "Accepted".into() }).unwrap();
+
+    let interrupted = store.create(Some("delivery-2".into()), &event()).unwrap();
+    store.transition(&interrupted, JobState::Running { attempt: 1 }).unwrap();
+    store.create(None, &event()).unwrap();
+  }
+
+  let store = JobStore::open(&path).unwrap();
+  let pending: Vec<String> = store.pending().into_iter().map(|job| job.id).collect();
+  assert_eq!(pending.len(), 2);
+  assert!(pending.contains(&"delivery-2.1".to_string()));
+  assert!(!pending.contains(&"delivery-1.1".to_string()));
+
+  let done = store.get("delivery-1.1").unwrap();
+  assert_eq!(done.state, JobState::Succeeded { result: "Accepted".into() });
+  assert_eq!(done.history.len(), 3);
+  assert_eq!(done.event.pull_number(), Some(7));
+}
+
+#[test]
+fn skips_a_truncated_last_line() {
+  let path = log_path("truncated");
+  {
+    let store = JobStore::open(&path).unwrap();
+    store.create(Some("delivery-1".into()), &event()).unwrap();
+  }
+  let mut contents = std::fs::read_to_string(&path).unwrap();
+  contents.push_str("{\"kind\":\"transition\",\"id\":\"deliv");
+  std::fs::write(&path, contents).unwrap();
+
+  let store = JobStore::open(&path).unwrap();
+  assert_eq!(store.get("delivery-1.1").unwrap().state, JobState::Queued);
+}
+
+#[test]
+fn redeliveries_get_their_own_jobs() {
+  let store = JobStore::open(log_path("redelivery")).unwrap();
+  let first = store.create(Some("delivery-1".into()), &event()).unwrap();
+  store.transition(&first, JobState::Failed { error: "queue full".into() }).unwrap();
+  let second = store.create(Some("delivery-1".into()), &event()).unwrap();
+
+  assert_eq!((first.as_str(), second.as_str()), ("delivery-1.1", "delivery-1.2"));
+  let jobs = store.for_delivery("delivery-1");
+  assert_eq!(
+    jobs.iter().map(|job| job.id.as_str()).collect::<Vec<_>>(),
+    vec![second.as_str(), first.as_str()]
+  );
+  assert!(matches!(jobs[1].state, JobState::Failed { .. }));
+  assert_eq!(jobs[0].delivery_id.as_deref(), Some("delivery-1"));
+  assert_ne!(store.create(None, &event()).unwrap(), store.create(None, &event()).unwrap());
+}
+
+fn command(body: &str) -> GitHubEvent {
+  let pr: Value =
+    serde_json::from_slice(&std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap())
+      .unwrap();
+  let payload = json!({
+    "action": "created",
+    "issue": { "number": 7, "pull_request": { "url": "https://api.github.com/repos/vznh/mechanic-demo/pulls/7" } },
+    "comment": { "id": 1, "body": body, "user": { "login": "vznh", "id": 2, "type": "User" } },
+    "repository": pr["repository"],
+    "installation": pr["installation"],
+    "sender": { "login": "vznh", "id": 2, "type": "User" },
+  });
+  GitHubEvent::parse("issue_comment", &payload).unwrap()
+}
+
+#[test]
+fn finished_jobs_expire_but_unfinished_jobs_and_pauses_stay() {
+  let path = log_path("retention");
+  let accepted = JobState::Succeeded { result: "Accepted".into() };
+  {
+    let store = JobStore::open(&path).unwrap();
+    let done = store.create(Some("done".into()), &event()).unwrap();
+    store.transition(&done, accepted.clone()).unwrap();
+    store.create(Some("waiting".into()), &event()).unwrap();
+    let pause = store.create(Some("pause".into()), &command("/mechanic pause")).unwrap();
+    store.transition(&pause, accepted.clone()).unwrap();
+    PullRequestControls::with_store(store.clone()).pause("vznh/mechanic-demo", 7).unwrap();
+  }
+
+  // Everything finished is already past a retention of nothing.
+  std::thread::sleep(std::time::Duration::from_millis(1_100));
+  let store = JobStore::open_with_retention(&path, Duration::ZERO).unwrap();
+  assert!(store.get("done.1").is_none());
+  assert!(store.get("waiting.1").is_some());
+  assert!(store.get("pause.1").is_none());
+  // The pause itself outlives the job that made it.
+  let controls = PullRequestControls::with_store(store.clone());
+  assert!(controls.is_paused("vznh/mechanic-demo", 7));
+
+  // Dropped jobs are gone from the log itself, not just from memory.
+  drop(store);
+  let log = std::fs::read_to_string(&path).unwrap();
+  assert!(!log.contains("\"done\""));
+}
@@ -0,0 +1,181 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use treehacks25::config::{LlmConfig, ProviderKind, ResponseFormat};
+use treehacks25::helpers::redact::Secret;
+use treehacks25::services::llm::mock::{MockProvider, prompt_key};
+use treehacks25::services::llm::{
+  ChatMessage, ChatRequest, LlmError, LlmProvider, provider_from_config,
+};
+
+/// Request bodies and `Authorization` headers the stub server received.
+type Seen = Arc<Mutex<Vec<(Value, Option<String>)>>>;
+
+async fn record(seen: &Seen, headers: &HeaderMap, body: Value) {
+  let auth = headers.get("authorization").map(|v| v.to_str().unwrap().to_string());
+  seen.lock().unwrap().push((body, auth));
+}
+
+/// Serve both the OpenAI-style and the Ollama chat endpoints on a free local port.
+async fn stub_server() -> (String, Seen) {
+  let seen: Seen = Arc::default();
+  let app = Router::new()
+    .route(
+      "/v1/chat/completions",
+      post(|State(seen): State<Seen>, headers: HeaderMap, Json(body): Json<Value>| async move {
+        record(&seen, &headers, body).await;
+        Json(json!({ "choices": [{ "message": { "role": "assistant", "content": "hi from openai" } }] }))
+      }),
+    )
+    .route(
+      "/api/chat",
+      post(|State(seen): State<Seen>, headers: HeaderMap, Json(body): Json<Value>| async move {
+        record(&seen, &headers, body).await;
+        Json(json!({ "message": { "role": "assistant", "content": "hi from ollama" }, "done": true }))
+      }),
+    )
+    .route("/broken/chat/completions", post(|| async { (axum::http::StatusCode::BAD_GATEWAY, "down") }))
+    .with_state(seen.clone());
+
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let addr = listener.local_addr().unwrap();
+  tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+  (format!("http://{}", addr), seen)
+}
+
+fn request() -> ChatRequest {
+  ChatRequest {
+    messages: vec![ChatMessage::user("Review this.")],
+    max_tokens: 256,
+    schema: Some(json!({ "type": "object" })),
+    model: None,
+  }
+}
+
+#[tokio::test]
+async fn openai_compatible_sends_chat_completions() {
+  let (base, seen) = stub_server().await;
+  let llm = LlmConfig {
+    provider: ProviderKind::OpenAi,
+    base_url: format!("{}/v1/", base),
+    model: "local-model".to_string(),
+    response_format: ResponseFormat::JsonSchema,
+    ..LlmConfig::default()
+  };
+  let provider = provider_from_config(&llm);
+
+  assert_eq!(provider.chat(&request()).await.unwrap(), "hi from openai");
+  let (body, auth) = seen.lock().unwrap()[0].clone();
+  assert_eq!(body["model"], "local-model");
+  assert_eq!(body["messages"][0], json!({ "role": "user", "content": "Review this." }));
+  assert_eq!(body["response_format"]["type"], "json_schema");
+  assert_eq!(body["response_format"]["json_schema"]["schema"], json!({ "type": "object" }));
+  assert!(body.get("reasoning_format").is_none());
+  assert_eq!(auth, None);
+  assert_eq!(provider.max_context(), 128_000);
+}
+
+#[tokio::test]
+async fn groq_adds_its_options_and_key() {
+  let (base, seen) = stub_server().await;
+  let llm = LlmConfig {
+    base_url: format!("{}/v1", base),
+    api_key: Secret::new("gsk_test"),
+    ..LlmConfig::default()
+  };
+  let provider = provider_from_config(&llm);
+
+  assert_eq!(provider.name(), "groq");
+  provider.chat(&request()).await.unwrap();
+  let (body, auth) = seen.lock().unwrap()[0].clone();
+  assert_eq!(body["reasoning_format"], "hidden");
+  assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+  assert_eq!(auth.as_deref(), Some("Bearer gsk_test"));
+}
+
+#[tokio::test]
+async fn groq_only_hides_reasoning_for_reasoning_models() {
+  let (base, seen) = stub_server().await;
+  let llm = LlmConfig {
+    base_url: format!("{}/v1", base),
+    model: "llama-3.3-70b-versatile".to_string(),
+    ..LlmConfig::default()
+  };
+  let provider = provider_from_config(&llm);
+
+  provider.chat(&request()).await.unwrap();
+  let reasoning = ChatRequest { model: Some("qwen/qwen3-32b".to_string()), ..request() };
+  provider.chat(&reasoning).await.unwrap();
+  let seen = seen.lock().unwrap();
+  assert!(seen[0].0.get("reasoning_format").is_none());
+  assert_eq!(seen[1].0["reasoning_format"], "hidden");
+}
+
+#[tokio::test]
+async fn ollama_uses_its_native_api() {
+  let (base, seen) = stub_server().await;
+  let llm = LlmConfig {
+    provider: ProviderKind::Ollama,
+    base_url: base,
+    model: "qwen2.5-coder".to_string(),
+    context_window: 16_384,
+    ..LlmConfig::default()
+  };
+  let provider = provider_from_config(&llm);
+
+  assert_eq!(provider.chat(&request()).await.unwrap(), "hi from ollama");
+  let (body, _) = seen.lock().unwrap()[0].clone();
+  assert_eq!(body["stream"], false);
+  assert_eq!(body["format"], "json");
+  assert_eq!(body["options"], json!({ "num_predict": 256, "num_ctx": 16_384 }));
+  assert_eq!(provider.max_context(), 16_384);
+}
+
+#[tokio::test]
+async fn error_statuses_keep_the_body() {
+  let (base, _) = stub_server().await;
+  let llm = LlmConfig {
+    provider: ProviderKind::OpenAi,
+    base_url: format!("{}/broken", base),
+    ..LlmConfig::default()
+  };
+
+  let err = provider_from_config(&llm).chat(&request()).await.unwrap_err();
+  assert!(matches!(err, LlmError::Status { status: 502, ref body } if body == "down"));
+}
+
+#[tokio::test]
+async fn mock_replays_by_prompt_key() {
+  let mock = MockProvider::new();
+  let key = prompt_key(&request().messages);
+  assert!(
+    matches!(mock.chat(&request()).await, Err(LlmError::NotRecorded { key: ref k }) if *k == key)
+  );
+
+  mock.insert(&key, "recorded reply");
+  assert_eq!(mock.chat(&request()).await.unwrap(), "recorded reply");
+}
+
+#[tokio::test]
+async fn recordings_round_trip_through_a_directory() {
+  let (base, _) = stub_server().await;
+  let dir = std::env::temp_dir().join(format!("mechanic-recordings-{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+
+  let recording = LlmConfig {
+    provider: ProviderKind::OpenAi,
+    base_url: format!("{}/v1", base),
+    recordings: Some(dir.clone()),
+    ..LlmConfig::default()
+  };
+  provider_from_config(&recording).chat(&request()).await.unwrap();
+  assert!(dir.join(format!("{}.json", prompt_key(&request().messages))).is_file());
+
+  let replay = LlmConfig { provider: ProviderKind::Mock, ..recording };
+  let mock = provider_from_config(&replay);
+  assert_eq!(mock.name(), "mock");
+  assert_eq!(mock.chat(&request()).await.unwrap(), "hi from openai");
+}
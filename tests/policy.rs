@@ -0,0 +1,164 @@
+use treehacks25::config::Config;
+use treehacks25::fake_github::FakeGitHub;
+use treehacks25::helpers::event::PullRequestAction;
+use treehacks25::helpers::findings::Severity;
+use treehacks25::helpers::glob::glob_match;
+use treehacks25::helpers::octo::init_octocrab;
+use treehacks25::services::policy::{PolicyCache, RepoPolicy};
+
+const REPO: &str = "octo-org/octo-repo";
+const SHA: &str = "b5a2f6c1d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8";
+
+#[test]
+fn globs_match_paths() {
+  assert!(glob_match("*.lock", "Cargo.lock"));
+  assert!(glob_match("*.lock", "deps/web/yarn.lock"));
+  assert!(glob_match("src/**/*.rs", "src/main.rs"));
+  assert!(glob_match("src/**/*.rs", "src/helpers/octo.rs"));
+  assert!(glob_match("vendor/**", "vendor/a/b.c"));
+  assert!(glob_match("docs/?.md", "docs/a.md"));
+  assert!(!glob_match("src/*.rs", "src/helpers/octo.rs"));
+  assert!(!glob_match("src/**/*.rs", "tests/review.rs"));
+  assert!(!glob_match("docs/?.md", "docs/ab.md"));
+}
+
+#[test]
+fn many_wildcards_do_not_blow_up() {
+  let name = format!("{}b", "a".repeat(200));
+  assert!(!glob_match(&format!("{}c", "*a".repeat(30)), &name));
+  let path = format!("{}x", "a/".repeat(100));
+  assert!(!glob_match(&format!("{}y", "**/".repeat(30)), &path));
+  assert!(glob_match(&format!("{}x", "**/".repeat(30)), &path));
+}
+
+#[test]
+fn missing_settings_fall_back_to_the_server_config() {
+  let config = Config::default();
+  let policy = RepoPolicy::from_toml("", &config).unwrap();
+  assert_eq!(policy, RepoPolicy::defaults(&config));
+  assert!(policy.reviews(PullRequestAction::Opened));
+  assert!(policy.reviews_path("anything/at/all.rs"));
+}
+
+#[test]
+fn policy_file_overrides_defaults() {
+  let config = Config::default();
+  let policy = RepoPolicy::from_toml(
+    r#"
+events = ["opened"]
+include = ["src/**"]
+exclude = ["**/generated/**", "*.lock"]
+request_changes_at = "warning"
+model = "llama-3.1-8b-instant"
+max_comments = 5
+instructions = "We use anyhow for errors."
+"#,
+    &config,
+  )
+  .unwrap();
+
+  assert!(policy.reviews(PullRequestAction::Opened));
+  assert!(!policy.reviews(PullRequestAction::Synchronize));
+  assert!(policy.reviews_path("src/lib.rs"));
+  assert!(!policy.reviews_path("README.md"));
+  assert!(!policy.reviews_path("src/generated/api.rs"));
+  assert!(!policy.reviews_path("src/Cargo.lock"));
+  assert_eq!(policy.request_changes_at, Severity::Warning);
+  assert_eq!(policy.model, "llama-3.1-8b-instant");
+  assert_eq!(policy.max_comments, 5);
+  assert_eq!(policy.instructions.as_deref(), Some("We use anyhow for errors."));
+}
+
+#[test]
+fn disabled_repositories_review_nothing() {
+  let policy = RepoPolicy::from_toml("enabled = false", &Config::default()).unwrap();
+  assert!(!policy.reviews(PullRequestAction::Opened));
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+  assert!(RepoPolicy::from_toml("max_coments = 3", &Config::default()).is_err());
+}
+
+#[test]
+fn yaml_policies_hold_the_same_settings() {
+  let config = Config::default();
+  let policy = RepoPolicy::from_yaml(
+    "# Mechanic's settings for this repository.
+events: [opened, ready_for_review]
+include:
+  - src/**
+exclude: ['*.lock']
+request_changes_at: error
+max_comments: 4
+instructions: |
+  We use anyhow for errors.
+autofix: true
+",
+    &config,
+  )
+  .unwrap();
+
+  assert!(policy.reviews(PullRequestAction::ReadyForReview));
+  assert!(!policy.reviews(PullRequestAction::Synchronize));
+  assert!(policy.reviews_path("src/lib.rs") && !policy.reviews_path("src/Cargo.lock"));
+  assert_eq!(policy.request_changes_at, Severity::Error);
+  assert_eq!(policy.max_comments, 4);
+  assert_eq!(policy.instructions.as_deref(), Some("We use anyhow for errors.\n"));
+  assert!(policy.autofix);
+
+  assert_eq!(
+    RepoPolicy::from_yaml("# nothing yet\n", &config).unwrap(),
+    RepoPolicy::defaults(&config)
+  );
+  assert!(RepoPolicy::from_yaml("max_coments: 3", &config).is_err());
+  assert!(RepoPolicy::from_yaml("- opened", &config).is_err());
+}
+
+#[tokio::test]
+async fn the_yaml_policy_is_read_from_the_github_directory() {
+  let github = FakeGitHub::new();
+  github.add_file(REPO, SHA, ".github/mechanic.yml", "max_comments: 2\nenabled: false\n");
+  let url = github.start().await.unwrap();
+  let octo = init_octocrab(&url, "token".to_string()).unwrap();
+
+  let policy =
+    PolicyCache::new().load(&octo, "octo-org", "octo-repo", SHA, &Config::default()).await;
+  assert_eq!(policy.max_comments, 2);
+  assert!(!policy.enabled);
+}
+
+#[tokio::test]
+async fn policies_are_cached_per_commit() {
+  let github = FakeGitHub::new();
+  github.add_file(REPO, SHA, ".github/mechanic.toml", "max_comments = 3\n");
+  let url = github.start().await.unwrap();
+  let octo = init_octocrab(&url, "token".to_string()).unwrap();
+  let config = Config::default();
+  let cache = PolicyCache::new();
+
+  let policy = cache.load(&octo, "octo-org", "octo-repo", SHA, &config).await;
+  assert_eq!(policy.max_comments, 3);
+
+  // A commit's files can't change, so this is never fetched.
+  github.add_file(REPO, SHA, ".mechanic.toml", "max_comments = 7\n");
+  let again = cache.load(&octo, "octo-org", "octo-repo", SHA, &config).await;
+  assert_eq!(again.max_comments, 3);
+}
+
+#[tokio::test]
+async fn missing_or_invalid_policies_use_the_defaults() {
+  let github = FakeGitHub::new();
+  github.add_file(REPO, "broken", ".mechanic.toml", "max_comments = \"lots\"\n");
+  let url = github.start().await.unwrap();
+  let octo = init_octocrab(&url, "token".to_string()).unwrap();
+  let config = Config::default();
+  let cache = PolicyCache::new();
+
+  let missing = cache.load(&octo, "octo-org", "octo-repo", SHA, &config).await;
+  assert_eq!(*missing, RepoPolicy::defaults(&config));
+  let broken = cache.load(&octo, "octo-org", "octo-repo", "broken", &config).await;
+  assert_eq!(RepoPolicy { invalid_file: None, ..(*broken).clone() }, RepoPolicy::defaults(&config));
+  let problem = broken.invalid_file.as_deref().unwrap();
+  assert!(problem.starts_with("`.mechanic.toml` is invalid, so the default settings were used: "));
+}
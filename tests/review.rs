@@ -0,0 +1,126 @@
+use serde_json::json;
+use treehacks25::helpers::diff::{FileDiff, parse_unified_diff};
+use treehacks25::helpers::findings::{Category, ReviewFinding, ReviewReport, Severity};
+use treehacks25::helpers::review::{ReviewEvent, Side, build_review, suggestion_block};
+
+const SHA: &str = "4e2b8a6c1d9f0e3a7b5c8d2f6a1e9b3c7d0f5a48";
+
+fn finding(file: &str, line: u32, severity: Severity) -> ReviewFinding {
+  ReviewFinding {
+    file: file.to_string(),
+    start_line: line,
+    end_line: line,
+    severity,
+    category: Category::Bug,
+    message: "Check this.".to_string(),
+    suggested_fix: None,
+    confidence: 0.9,
+  }
+}
+
+fn report(findings: Vec<ReviewFinding>) -> ReviewReport {
+  ReviewReport { summary: "Summary".to_string(), findings }
+}
+
+fn files() -> Vec<FileDiff> {
+  parse_unified_diff(&std::fs::read_to_string("tests/fixtures/pull_request.diff").unwrap())
+}
+
+#[test]
+fn findings_in_the_diff_become_inline_comments() {
+  let mut ranged = finding("src/retry.rs", 6, Severity::Info);
+  ranged.start_line = 4;
+  let report = report(vec![finding("src/lib.rs", 11, Severity::Warning), ranged]);
+  let review = build_review(SHA, &report, &files(), Severity::Error, 30);
+
+  assert_eq!(review.event, ReviewEvent::Comment);
+  assert_eq!(review.body, "Summary");
+  assert_eq!(review.comments.len(), 2);
+  assert_eq!(review.comments[0].side, Side::Right);
+  assert_eq!(review.comments[1].start_line, Some(4));
+
+  let body = serde_json::to_value(&review).unwrap();
+  assert_eq!(body["commit_id"], SHA);
+  assert_eq!(body["event"], "COMMENT");
+  assert_eq!(
+    body["comments"][0],
+    json!({"path": "src/lib.rs", "line": 11, "side": "RIGHT", "body": "**Warning** (bug): Check this.\n\n<!-- mechanic:finding bug -->"})
+  );
+}
+
+#[test]
+fn findings_outside_the_diff_go_into_the_summary() {
+  let report = report(vec![
+    finding("src/lib.rs", 30, Severity::Error),
+    finding("README.md", 1, Severity::Info),
+  ]);
+  let review = build_review(SHA, &report, &files(), Severity::Error, 30);
+
+  assert!(review.comments.is_empty());
+  assert!(review.body.contains("### Other findings"));
+  assert!(review.body.contains("- `src/lib.rs:30` **Error** (bug): Check this."));
+  assert_eq!(review.event, ReviewEvent::RequestChanges);
+}
+
+#[test]
+fn threshold_picks_the_review_event() {
+  let report = report(vec![finding("src/lib.rs", 42, Severity::Warning)]);
+  let strict = build_review(SHA, &report, &files(), Severity::Warning, 30);
+  let lenient = build_review(SHA, &report, &files(), Severity::Error, 30);
+
+  assert_eq!(strict.event, ReviewEvent::RequestChanges);
+  assert_eq!(lenient.event, ReviewEvent::Comment);
+}
+
+#[test]
+fn suggestions_that_map_to_the_diff_become_suggestion_blocks() {
+  let mut ranged = finding("src/retry.rs", 5, Severity::Warning);
+  ranged.start_line = 4;
+  ranged.suggested_fix =
+    Some("    if let Some(v) = f() {\n      return Some(v);\n    }\n".to_string());
+  let review = build_review(SHA, &report(vec![ranged]), &files(), Severity::Error, 30);
+
+  assert_eq!(review.comments[0].start_line, Some(4));
+  assert!(
+    review.comments[0]
+      .body
+      .contains("```suggestion\n    if let Some(v) = f() {\n      return Some(v);\n    }\n```\n\n")
+  );
+}
+
+#[test]
+fn suggestions_that_do_not_map_are_skipped_with_a_reason() {
+  // Lines 2 and 42 of src/lib.rs are in different hunks.
+  let mut split = finding("src/lib.rs", 42, Severity::Info);
+  split.start_line = 2;
+  split.suggested_fix = Some("    60".to_string());
+  let mut outside = finding("src/lib.rs", 30, Severity::Info);
+  outside.suggested_fix = Some("x".to_string());
+  let review = build_review(SHA, &report(vec![split, outside]), &files(), Severity::Error, 30);
+
+  assert_eq!(review.comments[0].start_line, None);
+  assert!(!review.comments[0].body.contains("```suggestion"));
+  assert!(review.comments[0].body.contains("lines 2-42 are not all in one hunk"));
+  assert!(review.body.contains("the lines are not in the diff"));
+}
+
+#[test]
+fn suggestion_fence_outgrows_backticks_in_the_code() {
+  assert_eq!(suggestion_block("let s = \"```\";"), "````suggestion\nlet s = \"```\";\n````");
+}
+
+#[test]
+fn findings_past_the_comment_limit_go_in_the_body() {
+  let report = report(vec![
+    finding("src/lib.rs", 11, Severity::Warning),
+    finding("src/retry.rs", 6, Severity::Info),
+    finding("src/lib.rs", 30, Severity::Info),
+  ]);
+  let review = build_review(SHA, &report, &files(), Severity::Error, 1);
+
+  assert_eq!(review.comments.len(), 1);
+  assert_eq!(review.comments[0].path, "src/lib.rs");
+  assert!(review.body.contains("### More findings\n_Only 1 are commented inline._\n"));
+  assert!(review.body.contains("- `src/retry.rs:6` **Info** (bug): Check this."));
+  assert!(review.body.contains("- `src/lib.rs:30` **Info** (bug): Check this."));
+}
@@ -0,0 +1,64 @@
+use serde_json::json;
+use treehacks25::helpers::findings::{Category, ReviewFinding, ReviewReport, Severity};
+use treehacks25::helpers::status::{CommitState, CommitStatus};
+
+fn report(severities: &[Severity]) -> ReviewReport {
+  let finding = |severity| ReviewFinding {
+    file: "src/lib.rs".to_string(),
+    start_line: 3,
+    end_line: 3,
+    severity,
+    category: Category::Bug,
+    message: "Off by one.".to_string(),
+    suggested_fix: None,
+    confidence: 0.9,
+  };
+  ReviewReport {
+    summary: "Looks fine.".to_string(),
+    findings: severities.iter().map(|&s| finding(s)).collect(),
+  }
+}
+
+#[test]
+fn fails_only_at_the_threshold() {
+  let warning =
+    CommitStatus::for_report("ctx", &report(&[Severity::Warning]), Severity::Error, None);
+  assert_eq!(warning.state, CommitState::Success);
+  assert_eq!(warning.description, "1 finding(s), worst: warning");
+
+  let error = CommitStatus::for_report(
+    "ctx",
+    &report(&[Severity::Info, Severity::Error]),
+    Severity::Error,
+    None,
+  );
+  assert_eq!(error.state, CommitState::Failure);
+
+  let clean = CommitStatus::for_report("ctx", &report(&[]), Severity::Info, None);
+  assert_eq!((clean.state, clean.description.as_str()), (CommitState::Success, "No findings"));
+}
+
+#[test]
+fn serializes_for_the_statuses_api() {
+  let url = Some("https://github.com/vznh/mechanic-demo/pull/7".to_string());
+  assert_eq!(
+    serde_json::to_value(CommitStatus::queued("mechanic/review", url)).unwrap(),
+    json!({
+      "state": "pending",
+      "target_url": "https://github.com/vznh/mechanic-demo/pull/7",
+      "description": "Queued for review",
+      "context": "mechanic/review"
+    })
+  );
+  let errored = serde_json::to_value(CommitStatus::errored("ctx", "boom", None)).unwrap();
+  assert_eq!(
+    errored,
+    json!({ "state": "error", "description": "Review failed: boom", "context": "ctx" })
+  );
+}
+
+#[test]
+fn long_descriptions_are_cut_to_githubs_limit() {
+  let status = CommitStatus::errored("ctx", &"x".repeat(500), None);
+  assert_eq!(status.description.chars().count(), 140);
+}
@@ -0,0 +1,72 @@
+use treehacks25::helpers::sticky::{
+  STICKY_MARKER, StickyEntry, parse_sticky, render_sticky, update_sticky,
+};
+
+fn entry(commit_sha: &str, markdown: &str) -> StickyEntry {
+  StickyEntry { commit_sha: commit_sha.to_string(), markdown: markdown.to_string() }
+}
+
+#[test]
+fn renders_the_latest_entry_with_a_hidden_marker() {
+  let body = update_sticky(None, entry("4e2b8a6c1d9f", "## Mechanic review\n\nAll good."));
+  assert!(body.starts_with(STICKY_MARKER));
+  assert!(body.contains("All good."));
+  assert!(body.contains("commit `4e2b8a6`"));
+  assert!(!body.contains("<details>"));
+  assert_eq!(parse_sticky(&body), vec![entry("4e2b8a6c1d9f", "## Mechanic review\n\nAll good.")]);
+}
+
+#[test]
+fn earlier_commits_fold_into_the_history() {
+  let first = update_sticky(None, entry("aaaaaaa111", "First pass."));
+  let second = update_sticky(Some(&first), entry("bbbbbbb222", "Second pass."));
+  let third = update_sticky(Some(&second), entry("ccccccc333", "Third pass."));
+
+  assert_eq!(
+    parse_sticky(&third),
+    vec![
+      entry("ccccccc333", "Third pass."),
+      entry("bbbbbbb222", "Second pass."),
+      entry("aaaaaaa111", "First pass.")
+    ]
+  );
+  let (latest, history) = third.split_once("<details>").unwrap();
+  assert!(latest.contains("Third pass.") && !latest.contains("Second pass."));
+  assert!(history.contains("<summary>Earlier commits (2)</summary>"));
+  assert!(history.find("bbbbbbb").unwrap() < history.find("aaaaaaa").unwrap());
+}
+
+#[test]
+fn reviewing_a_commit_again_replaces_its_entry() {
+  let first = update_sticky(None, entry("aaaaaaa111", "Attempt one."));
+  let again = update_sticky(Some(&first), entry("aaaaaaa111", "Attempt two."));
+  assert_eq!(parse_sticky(&again), vec![entry("aaaaaaa111", "Attempt two.")]);
+}
+
+#[test]
+fn history_is_capped() {
+  let mut body = render_sticky(&[]);
+  for i in 0..15 {
+    body = update_sticky(Some(&body), entry(&format!("{:040}", i), "Pass."));
+  }
+  let entries = parse_sticky(&body);
+  assert_eq!(entries.len(), 11);
+  assert_eq!(entries[0].commit_sha, format!("{:040}", 14));
+}
+
+#[test]
+fn oversized_history_is_dropped_to_fit_a_comment() {
+  let big = "x".repeat(40_000);
+  let first = update_sticky(None, entry("aaaaaaa111", &big));
+  let second = update_sticky(Some(&first), entry("bbbbbbb222", &big));
+  assert_eq!(parse_sticky(&second), vec![entry("bbbbbbb222", &big)]);
+}
+
+#[test]
+fn an_oversized_latest_entry_is_cut_short_with_a_marker() {
+  let body = update_sticky(None, entry("aaaaaaa111", &"x".repeat(100_000)));
+  assert!(body.chars().count() <= 65_536);
+  let latest = &parse_sticky(&body)[0];
+  assert!(latest.markdown.starts_with("xxxx"));
+  assert!(latest.markdown.ends_with("_Truncated: this review is too long for one comment._"));
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use common::{REPO, fake_thread};
+use treehacks25::fake_github::FakeGitHub;
+use treehacks25::helpers::findings::{Category, ReviewFinding, ReviewReport, Severity};
+use treehacks25::helpers::octo::{
+  init_octocrab, list_review_threads, reply_to_review_thread, resolve_review_thread,
+};
+use treehacks25::helpers::threads::{
+  ADDRESSED_MARKER, DISMISSED_MARKER, ReviewThread, addressed_reply, drop_dismissed,
+  finding_marker, fixed_threads, parse_finding_marker,
+};
+
+const REVIEWED: &[&str] = &["src/retry.rs"];
+
+fn thread(id: &str, line: Option<u32>, is_outdated: bool) -> ReviewThread {
+  ReviewThread {
+    id: id.to_string(),
+    path: "src/retry.rs".to_string(),
+    line,
+    original_line: Some(6),
+    is_resolved: false,
+    is_outdated,
+    category: Some(Category::Performance),
+    marked_addressed: false,
+    root_comment_id: None,
+    dismissed: false,
+  }
+}
+
+fn report(file: &str, line: u32, category: Category) -> ReviewReport {
+  ReviewReport {
+    summary: String::new(),
+    findings: vec![ReviewFinding {
+      file: file.to_string(),
+      start_line: line,
+      end_line: line,
+      severity: Severity::Warning,
+      category,
+      message: "Still blocking.".to_string(),
+      suggested_fix: None,
+      confidence: 0.8,
+    }],
+  }
+}
+
+#[test]
+fn markers_round_trip() {
+  let body = format!("**Warning** (bug): Off by one.\n\n{}", finding_marker(Category::Bug));
+  assert_eq!(parse_finding_marker(&body), Some(Category::Bug));
+  assert_eq!(parse_finding_marker("A human comment."), None);
+}
+
+#[test]
+fn outdated_threads_not_reported_again_are_fixed() {
+  let threads = vec![thread("T1", None, true)];
+  let clean = ReviewReport::default();
+  assert_eq!(fixed_threads(&threads, &clean, REVIEWED).len(), 1);
+
+  // Same problem reported near the same place: not fixed.
+  let again = report("src/retry.rs", 8, Category::Performance);
+  assert!(fixed_threads(&threads, &again, REVIEWED).is_empty());
+
+  // A different kind of problem there, or the same kind elsewhere, doesn't keep it open.
+  assert_eq!(fixed_threads(&threads, &report("src/retry.rs", 6, Category::Bug), REVIEWED).len(), 1);
+  assert_eq!(
+    fixed_threads(&threads, &report("src/retry.rs", 40, Category::Performance), REVIEWED).len(),
+    1
+  );
+
+  // Unless the file wasn't reviewed this time, e.g. because a command limited the review.
+  assert!(fixed_threads(&threads, &clean, &["src/lib.rs"]).is_empty());
+}
+
+#[test]
+fn current_foreign_and_handled_threads_are_left_alone() {
+  let mut foreign = thread("T2", None, true);
+  foreign.category = None;
+  let mut resolved = thread("T3", None, true);
+  resolved.is_resolved = true;
+  let mut replied = thread("T4", None, true);
+  replied.marked_addressed = true;
+  // The model may simply not repeat a finding whose code didn't change.
+  let current = thread("T5", Some(6), false);
+
+  let threads = vec![foreign, resolved, replied, current];
+  assert!(fixed_threads(&threads, &ReviewReport::default(), REVIEWED).is_empty());
+}
+
+#[test]
+fn dismissed_findings_are_not_raised_again() {
+  let mut dismissed = thread("T6", Some(6), false);
+  dismissed.dismissed = true;
+  let threads = vec![dismissed, thread("T7", Some(30), false)];
+
+  let mut again = report("src/retry.rs", 7, Category::Performance);
+  drop_dismissed(&mut again, &threads);
+  assert!(again.findings.is_empty());
+
+  // Only the dismissed thread's finding goes; others nearby or elsewhere stay.
+  let mut other = report("src/retry.rs", 7, Category::Bug);
+  drop_dismissed(&mut other, &threads);
+  assert_eq!(other.findings.len(), 1);
+  let mut open = report("src/retry.rs", 30, Category::Performance);
+  drop_dismissed(&mut open, &threads);
+  assert_eq!(open.findings.len(), 1);
+}
+
+#[tokio::test]
+async fn threads_are_read_and_resolved_through_graphql() {
+  let github = FakeGitHub::new();
+  let ours =
+    format!("**Warning** (performance): Blocks.\n\n{}", finding_marker(Category::Performance));
+  github.add_review_thread(REPO, 7, fake_thread("PRRT_1", &ours, true));
+  github.add_review_thread(REPO, 7, fake_thread("PRRT_2", "Why not async?", true));
+  let octo = init_octocrab(&github.start().await.unwrap(), "ghs_test".to_string()).unwrap();
+
+  let threads = list_review_threads(&octo, "vznh", "mechanic-demo", 7).await.unwrap();
+  assert_eq!(threads.len(), 2);
+  assert_eq!(threads[0].category, Some(Category::Performance));
+  assert_eq!((threads[0].line, threads[0].original_line), (None, Some(6)));
+  assert_eq!(threads[1].category, None);
+  assert!(threads[0].root_comment_id.is_some());
+
+  resolve_review_thread(&octo, "PRRT_1").await.unwrap();
+  assert!(github.review_thread("PRRT_1").unwrap().0);
+  assert!(resolve_review_thread(&octo, "PRRT_9").await.is_err());
+}
+
+#[tokio::test]
+async fn unresolvable_threads_get_one_reply() {
+  let github = FakeGitHub::new();
+  let ours = format!("Blocks.\n\n{}", finding_marker(Category::Performance));
+  github.add_review_thread(REPO, 7, fake_thread("PRRT_1", &ours, true));
+  github.forbid_resolving_threads();
+  let octo = init_octocrab(&github.start().await.unwrap(), "ghs_test".to_string()).unwrap();
+
+  let err = resolve_review_thread(&octo, "PRRT_1").await.unwrap_err();
+  assert!(err.to_string().contains("Resource not accessible"));
+  reply_to_review_thread(&octo, "PRRT_1", &addressed_reply("4e2b8a6")).await.unwrap();
+
+  let threads = list_review_threads(&octo, "vznh", "mechanic-demo", 7).await.unwrap();
+  assert!(threads[0].marked_addressed);
+  assert!(fixed_threads(&threads, &ReviewReport::default(), REVIEWED).is_empty());
+  let (resolved, comments) = github.review_thread("PRRT_1").unwrap();
+  assert!(!resolved);
+  assert!(comments[1].ends_with(ADDRESSED_MARKER));
+}
+
+#[tokio::test]
+async fn markers_count_anywhere_in_mechanics_own_comments() {
+  let github = FakeGitHub::new();
+  let ours = format!("Blocks.\n\n{}", finding_marker(Category::Performance));
+  let root = github.add_review_thread(REPO, 7, fake_thread("PRRT_1", &ours, true));
+  let octo = init_octocrab(&github.start().await.unwrap(), "ghs_test".to_string()).unwrap();
+  let dismissal = format!("Fair enough.\n\n{}", DISMISSED_MARKER);
+  reply_to_review_thread(&octo, "PRRT_1", &dismissal).await.unwrap();
+  github.add_review_reply(REPO, 7, root, "dev", "Thanks!").unwrap();
+
+  // The developer had the last word, but the finding stays dismissed.
+  let threads = list_review_threads(&octo, "vznh", "mechanic-demo", 7).await.unwrap();
+  assert!(threads[0].dismissed);
+
+  // Markers pasted by someone else don't count, and neither does a thread they started.
+  let theirs = github.add_foreign_review_thread(
+    "vznh/mechanic-demo",
+    7,
+    fake_thread("PRRT_2", &ours, true),
+    "mallory",
+  );
+  github.add_review_reply(REPO, 7, theirs, "mallory", &dismissal).unwrap();
+  github.add_review_reply(REPO, 7, theirs, "mallory", ADDRESSED_MARKER).unwrap();
+  let threads = list_review_threads(&octo, "vznh", "mechanic-demo", 7).await.unwrap();
+  assert_eq!(threads[1].category, None);
+  assert!(!threads[1].dismissed && !threads[1].marked_addressed);
+}
@@ -0,0 +1,41 @@
+use serde_json::{Value, json};
+use treehacks25::helpers::event::{EventError, GitHubEvent, PullRequestAction};
+
+fn fixture() -> Value {
+  serde_json::from_slice(&std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap())
+    .unwrap()
+}
+
+#[test]
+fn parses_recorded_pull_request_synchronize() {
+  let event = GitHubEvent::parse("pull_request", &fixture()).unwrap();
+
+  let GitHubEvent::PullRequest(pr) = &event else { panic!("expected a pull_request event") };
+  assert_eq!(pr.action, PullRequestAction::Synchronize);
+  assert_eq!(event.installation_id(), 61234987);
+  assert_eq!(event.pull_number(), Some(7));
+  assert_eq!(event.commit_sha(), Some("4e2b8a6c1d9f0e3a7b5c8d2f6a1e9b3c7d0f5a48"));
+  assert_eq!(event.repository().unwrap().full_name, "vznh/mechanic-demo");
+}
+
+#[test]
+fn unsupported_events_and_actions_are_not_errors_in_the_payload() {
+  let mut payload = fixture();
+  payload["action"] = json!("closed");
+
+  assert!(matches!(
+    GitHubEvent::parse("pull_request", &payload),
+    Err(EventError::Unsupported { action: Some(ref action), .. }) if action == "closed"
+  ));
+  assert!(matches!(GitHubEvent::parse("star", &payload), Err(EventError::Unsupported { .. })));
+}
+
+#[test]
+fn rejects_malformed_payloads() {
+  let mut payload = fixture();
+  payload.as_object_mut().unwrap().remove("installation");
+
+  let err = GitHubEvent::parse("pull_request", &payload).unwrap_err();
+  assert!(matches!(err, EventError::Malformed { .. }));
+  assert!(err.to_string().contains("installation"));
+}
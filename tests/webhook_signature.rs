@@ -0,0 +1,138 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use treehacks25::config::Config;
+use treehacks25::handlers::webhook::github_wh_test_handler;
+use treehacks25::helpers::redact::Secret;
+use treehacks25::helpers::signature::{SignatureError, sign_payload, verify_signature};
+use treehacks25::services::controls::PullRequestControls;
+use treehacks25::services::dedup::DeliveryLedger;
+use treehacks25::services::llm::provider_from_config;
+use treehacks25::services::policy::PolicyCache;
+use treehacks25::services::queue::{JobQueue, JobReceiver};
+use treehacks25::services::store::JobStore;
+use treehacks25::services::tokens::InstallationClients;
+use treehacks25::state::AppState;
+
+const FIXTURE_SECRET: &[u8] = b"mechanic-test-secret";
+
+// Signature recorded alongside `fixtures/pull_request_synchronize.json`.
+const FIXTURE_SIGNATURE: &str =
+  "sha256=16782e7b06f136aab8096705b18cea27cdffb493c85fde5d5ce9d6cdd9a67d6c";
+
+fn state() -> (State<AppState>, JobReceiver) {
+  let (queue, receiver) = JobQueue::new(1);
+  static NEXT_LOG: AtomicUsize = AtomicUsize::new(0);
+  let log = std::env::temp_dir().join(format!(
+    "mechanic-signature-{}-{}.log",
+    std::process::id(),
+    NEXT_LOG.fetch_add(1, Ordering::SeqCst)
+  ));
+  let _ = std::fs::remove_file(&log);
+  let store = JobStore::open(log).unwrap();
+  let ledger = DeliveryLedger::new(Duration::from_secs(60));
+  let mut config = Config::default();
+  config.github.webhook_secret = Secret::new("mechanic-test-secret");
+  let clients = InstallationClients::new(config.github.clone());
+  let llm = provider_from_config(&config.llm);
+  let policies = PolicyCache::new();
+  let controls = PullRequestControls::new();
+  (
+    State(AppState {
+      queue,
+      store,
+      ledger,
+      config: Arc::new(config),
+      clients,
+      policies,
+      controls,
+      llm,
+    }),
+    receiver,
+  )
+}
+
+fn fixture() -> Vec<u8> {
+  std::fs::read("tests/fixtures/pull_request_synchronize.json").unwrap()
+}
+
+#[test]
+fn accepts_github_documentation_example() {
+  // Example from GitHub's "Validating webhook deliveries" guide.
+  let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+  assert_eq!(
+    verify_signature(b"It's a Secret to Everybody", b"Hello, World!", Some(signature)),
+    Ok(())
+  );
+}
+
+#[test]
+fn accepts_recorded_delivery() {
+  let body = fixture();
+  assert_eq!(verify_signature(FIXTURE_SECRET, &body, Some(FIXTURE_SIGNATURE)), Ok(()));
+  assert_eq!(sign_payload(FIXTURE_SECRET, &body), FIXTURE_SIGNATURE);
+}
+
+#[test]
+fn rejects_tampered_or_unsigned_delivery() {
+  let mut body = fixture();
+  assert_eq!(verify_signature(FIXTURE_SECRET, &body, None), Err(SignatureError::MissingSignature));
+  assert_eq!(
+    verify_signature(FIXTURE_SECRET, &body, Some("sha1=abc")),
+    Err(SignatureError::MalformedSignature)
+  );
+  assert_eq!(
+    verify_signature(b"wrong-secret", &body, Some(FIXTURE_SIGNATURE)),
+    Err(SignatureError::Mismatch)
+  );
+  assert_eq!(
+    verify_signature(b"", &body, Some(FIXTURE_SIGNATURE)),
+    Err(SignatureError::MissingSecret)
+  );
+
+  body.extend_from_slice(b" ");
+  assert_eq!(
+    verify_signature(FIXTURE_SECRET, &body, Some(FIXTURE_SIGNATURE)),
+    Err(SignatureError::Mismatch)
+  );
+}
+
+#[tokio::test]
+async fn handler_rejects_unsigned_delivery() {
+  let mut headers = HeaderMap::new();
+  headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+
+  let response =
+    github_wh_test_handler(state().0, headers, Bytes::from(fixture())).await.into_response();
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn handler_rejects_mismatched_signature() {
+  let mut headers = HeaderMap::new();
+  headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+  let forged = sign_payload(b"attacker-secret", &fixture());
+  headers.insert("X-Hub-Signature-256", HeaderValue::from_str(&forged).unwrap());
+
+  let response =
+    github_wh_test_handler(state().0, headers, Bytes::from(fixture())).await.into_response();
+  assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn handler_queues_signed_delivery() {
+  let (state, _receiver) = state();
+  let mut headers = HeaderMap::new();
+  headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+  headers
+    .insert("X-GitHub-Delivery", HeaderValue::from_static("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+  headers.insert("X-Hub-Signature-256", HeaderValue::from_static(FIXTURE_SIGNATURE));
+
+  let response =
+    github_wh_test_handler(state, headers, Bytes::from(fixture())).await.into_response();
+  assert_eq!(response.status(), StatusCode::ACCEPTED);
+}